@@ -1,10 +1,34 @@
-use anyhow::{Context, Result};
+use crate::fuzzy::{
+    DEFAULT_MIN_SCORE, MAX_CHOICES, SearchScope, ambiguous_matches, find_confident_match,
+    match_quality, rank_bookmarks, ranked_matches, ranked_score,
+};
+use crate::output::{Verbosity, debug, info, note, verbosity};
+use crate::store::BookmarkStore;
+use anyhow::{Context, Result, bail};
+use arboard::Clipboard;
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat, TimeDelta};
+use clap::ValueEnum;
 use home::home_dir;
-use serde::{Deserialize, Serialize};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use url::Url;
+use webbrowser::Browser;
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+/// Environment variable that, when set, overrides the bookmarks file location.
+pub const BOOKMARKS_PATH_ENV: &str = "BMK_PATH";
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 pub struct Bookmark {
     pub name: String,
     pub url: String,
@@ -12,33 +36,732 @@ pub struct Bookmark {
     pub desc: String,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tags: Vec<String>,
+    /// Longer free-form text, such as why the bookmark was saved. Only searched
+    /// with [`SearchScope::All`](crate::fuzzy::SearchScope::All)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// Emoji or short glyph shown before the name in the TUI
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub icon: String,
+    /// Number of times the bookmark has been opened
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub visits: u64,
+    /// When the bookmark was last opened, as an RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_opened: Option<String>,
+    /// Browser to open this bookmark in instead of the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser: Option<String>,
+    /// Further URLs opened together with `url`, for bookmarks that are link sets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+    /// When the bookmark was added, as an RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// When the bookmark was last edited, as an RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// Other names the bookmark can be opened, edited or deleted by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl Bookmark {
+    /// `url` followed by any additional `urls`, in the order they are opened.
+    pub fn all_urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.url.as_str()).chain(self.urls.iter().map(String::as_str))
+    }
+
+    /// Whether `name` is this bookmark's name or one of its aliases.
+    pub fn answers_to(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
 }
 
 pub type Bookmarks = Vec<Bookmark>;
 
-fn get_bookmarks_path() -> Result<PathBuf> {
-    let home = home_dir().context("Failed to find the home directory")?;
-    let config_dir = home.join(".config").join("bmk");
-    Ok(config_dir.join("bookmarks.yaml"))
+/// Version of the bookmarks file layout written by this build. Older files
+/// are upgraded by [`migrate`] when loaded.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Contents of a bookmarks file:
+///
+/// ```yaml
+/// version: 1
+/// bookmarks:
+///   - name: GitHub
+///     url: https://github.com
+/// ```
+///
+/// Files from before versioning hold just the list of bookmarks and are read
+/// as version 0.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Document {
+    pub version: u32,
+    pub bookmarks: Bookmarks,
+}
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Versioned {
+            version: u32,
+            #[serde(default)]
+            bookmarks: Bookmarks,
+        }
+
+        struct DocumentVisitor;
+
+        impl<'de> Visitor<'de> for DocumentVisitor {
+            type Value = Document;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a list of bookmarks or a map with `version` and `bookmarks`")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> std::result::Result<Document, A::Error> {
+                Ok(Document {
+                    version: 0,
+                    bookmarks: Bookmarks::deserialize(SeqAccessDeserializer::new(seq))?,
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> std::result::Result<Document, A::Error> {
+                let versioned = Versioned::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(Document {
+                    version: versioned.version,
+                    bookmarks: versioned.bookmarks,
+                })
+            }
+
+            // An empty YAML file
+            fn visit_unit<E>(self) -> std::result::Result<Document, E> {
+                Ok(Document::default())
+            }
+        }
+
+        deserializer.deserialize_any(DocumentVisitor)
+    }
+}
+
+/// What [`save_bookmarks_to`] writes, borrowing the bookmarks.
+#[derive(Serialize)]
+struct DocumentRef<'a> {
+    version: u32,
+    bookmarks: &'a Bookmarks,
+}
+
+/// Upgrade a document read from an older file to [`SCHEMA_VERSION`].
+pub fn migrate(mut document: Document) -> Result<Document> {
+    if document.version > SCHEMA_VERSION {
+        bail!(
+            "Bookmarks file has version {}, but this bmk only reads up to version {}; please upgrade bmk",
+            document.version,
+            SCHEMA_VERSION
+        );
+    }
+    // Version 0 is the bare list. Its fields didn't change, so only the version is new.
+    if document.version == 0 {
+        document.version = 1;
+    }
+    Ok(document)
+}
+
+/// Serialization format of a bookmarks file, chosen by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Yaml,
+    Json,
+}
+
+impl FileFormat {
+    /// Detect the format from the file extension. Files without an extension are read as YAML.
+    /// For encrypted files the extension before `.age` decides.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        if is_encrypted_path(path)
+            && let Some(stem) = path.file_stem()
+        {
+            return FileFormat::from_path(Path::new(stem));
+        }
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            None => Ok(FileFormat::Yaml),
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Ok(FileFormat::Yaml)
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(FileFormat::Json),
+            Some(ext) => bail!(
+                "Unsupported bookmarks file extension '.{}' for '{}' (expected .yaml, .yml or .json)",
+                ext,
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Extension of passphrase-encrypted bookmark files, e.g. `bookmarks.yaml.age`.
+pub const ENCRYPTED_EXTENSION: &str = "age";
+
+pub fn is_encrypted_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(ENCRYPTED_EXTENSION))
+}
+
+#[cfg(feature = "encryption")]
+fn decrypt_contents(path: &Path, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    use crate::encryption::{decrypt, passphrase};
+    decrypt(ciphertext, &passphrase(path, false)?)
+}
+
+#[cfg(feature = "encryption")]
+fn encrypt_contents(path: &Path, plaintext: &[u8]) -> Result<Vec<u8>> {
+    use crate::encryption::{encrypt, passphrase};
+    encrypt(plaintext, &passphrase(path, !path.exists())?)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn decrypt_contents(path: &Path, _ciphertext: &[u8]) -> Result<Vec<u8>> {
+    bail!(
+        "'{}' is encrypted, but bmk was built without the `encryption` feature",
+        path.display()
+    )
+}
+
+#[cfg(not(feature = "encryption"))]
+fn encrypt_contents(path: &Path, _plaintext: &[u8]) -> Result<Vec<u8>> {
+    decrypt_contents(path, &[])
+}
+
+pub fn get_bookmarks_path() -> Result<PathBuf> {
+    if let Some(path) = env::var_os(BOOKMARKS_PATH_ENV).filter(|p| !p.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
+    Ok(default_bookmarks_path(config_dir()))
+}
+
+/// Name of the bookmarks file in the config directory.
+pub const BOOKMARKS_FILE: &str = "bookmarks.yaml";
+
+/// [`BOOKMARKS_FILE`] in `config_dir`, or when there is none because the home
+/// directory is unknown (as in some containers), in the working directory,
+/// with a warning saying how to pick the file instead.
+pub fn default_bookmarks_path(config_dir: Result<PathBuf>) -> PathBuf {
+    match config_dir {
+        Ok(dir) => dir.join(BOOKMARKS_FILE),
+        Err(e) => {
+            eprintln!(
+                "Warning: {:#}; using '{}' in the current directory",
+                e, BOOKMARKS_FILE
+            );
+            PathBuf::from(BOOKMARKS_FILE)
+        }
+    }
+}
+
+/// Environment variable naming the base directory for configuration files,
+/// per the XDG Base Directory specification.
+pub const XDG_CONFIG_HOME_ENV: &str = "XDG_CONFIG_HOME";
+
+/// Directory holding bmk's configuration: `$XDG_CONFIG_HOME/bmk`, or
+/// `~/.config/bmk` when that is unset. The spec ignores relative paths.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os(XDG_CONFIG_HOME_ENV)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir.join("bmk"));
+    }
+
+    let home = home_dir().with_context(|| {
+        format!(
+            "Failed to find the home directory (set {} or --config to choose the bookmarks file, or {} for the config directory)",
+            BOOKMARKS_PATH_ENV, XDG_CONFIG_HOME_ENV
+        )
+    })?;
+    Ok(home.join(".config").join("bmk"))
 }
 
 pub fn load_bookmarks() -> Result<Bookmarks> {
-    let path = get_bookmarks_path()?;
+    load_and_upgrade(&get_bookmarks_path()?)
+}
+
+/// Load the bookmarks at `path`, upgraded in memory with [`migrate`]. A
+/// missing file has no bookmarks. The file itself is left as it is, so this
+/// suits files that are only read, like the other file of a merge.
+pub fn load_bookmarks_from(path: &Path) -> Result<Bookmarks> {
+    Ok(migrate(load_document_at(path)?)?.bookmarks)
+}
+
+/// Like [`load_bookmarks_from`], for the bookmarks file bmk manages: a file
+/// written by an older bmk is rewritten once in the current layout, so later
+/// loads don't need to migrate it again.
+pub fn load_and_upgrade(path: &Path) -> Result<Bookmarks> {
+    let document = load_document_at(path)?;
+    let version = document.version;
+    let document = migrate(document)?;
+    if version < SCHEMA_VERSION
+        && path.exists()
+        && let Err(e) = save_bookmarks_to(path, &document.bookmarks)
+    {
+        eprintln!("Warning: failed to upgrade '{}': {:#}", path.display(), e);
+    }
+    Ok(document.bookmarks)
+}
+
+/// Read the bookmarks file at `path` as stored, without upgrading it. A
+/// missing file is an empty document.
+fn load_document_at(path: &Path) -> Result<Document> {
+    let format = FileFormat::from_path(path)?;
     if !path.exists() {
-        return Ok(Vec::new());
+        return Ok(Document::default());
     }
 
-    let file = File::open(&path)
+    let content = read_bookmarks_file(path)?;
+    load_document_from_reader(content.as_slice(), format)
+        .with_context(|| format!("Failed to parse bookmarks from '{}'", path.display()))
+}
+
+/// The contents of the bookmarks file at `path`, decrypted if it is encrypted.
+fn read_bookmarks_file(path: &Path) -> Result<Vec<u8>> {
+    let mut file = File::open(path)
         .with_context(|| format!("Failed to open bookmarks file at '{}'", path.display()))?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)
+        .with_context(|| format!("Failed to read bookmarks file at '{}'", path.display()))?;
+    if is_encrypted_path(path) {
+        content = decrypt_contents(path, &content)
+            .with_context(|| format!("Failed to decrypt '{}'", path.display()))?;
+    }
+    Ok(content)
+}
+
+/// Read bookmarks from any version of the file layout, upgraded with [`migrate`].
+pub fn load_bookmarks_from_reader(reader: impl Read, format: FileFormat) -> Result<Bookmarks> {
+    Ok(migrate(load_document_from_reader(reader, format)?)?.bookmarks)
+}
+
+/// Read bookmarks piped in without a file name to go by, e.g. for `bmk --stdin`:
+/// JSON when the input starts with `{` or `[`, YAML otherwise. Empty input is
+/// an empty set.
+pub fn load_piped_bookmarks(mut reader: impl Read) -> Result<Bookmarks> {
+    let mut input = Vec::new();
+    reader
+        .read_to_end(&mut input)
+        .context("Failed to read bookmarks from stdin")?;
+    let start = input.iter().find(|b| !b.is_ascii_whitespace());
+    let format = match start {
+        None => return Ok(Vec::new()),
+        Some(b'{' | b'[') => FileFormat::Json,
+        Some(_) => FileFormat::Yaml,
+    };
+    load_bookmarks_from_reader(input.as_slice(), format)
+        .context("Failed to parse bookmarks from stdin")
+}
+
+/// Read a bookmarks file as stored, without upgrading it.
+pub fn load_document_from_reader(reader: impl Read, format: FileFormat) -> Result<Document> {
+    Ok(parse_from_reader(reader, format)?)
+}
+
+fn parse_from_reader<T: DeserializeOwned>(
+    reader: impl Read,
+    format: FileFormat,
+) -> std::result::Result<T, ParseError> {
+    let value = match format {
+        FileFormat::Yaml => serde_yaml::from_reader(reader).map_err(|e| ParseError {
+            format,
+            line: e.location().map(|l| l.line()),
+            column: e.location().map(|l| l.column()),
+            message: e.to_string(),
+        })?,
+        FileFormat::Json => serde_json::from_reader(reader).map_err(|e| ParseError {
+            format,
+            // serde_json reports line 0 for errors that have no position, like I/O errors
+            line: (e.line() > 0).then(|| e.line()),
+            column: (e.line() > 0).then(|| e.column()),
+            message: e.to_string(),
+        })?,
+    };
+    Ok(value)
+}
+
+/// A bookmarks file that could not be parsed. Can be downcast from the errors
+/// returned by [`load_bookmarks_from`] to tell a damaged file from other failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub format: FileFormat,
+    /// 1-based position of the error, when the parser knows it
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self.format {
+            FileFormat::Yaml => "YAML",
+            FileFormat::Json => "JSON",
+        };
+        write!(f, "Invalid {}: {}", format, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A problem in a bookmarks file found by [`validate_bookmarks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// Where the problem is, like `line 3, column 5` for a syntax error or
+    /// `bookmarks[2].tags` for a field, followed by the bookmark's name
+    pub location: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(location: impl Into<String>, message: impl std::fmt::Display) -> Self {
+        Self {
+            location: location.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Check the contents of a bookmarks file strictly, collecting every problem
+/// instead of stopping at the first like loading does. Besides what loading
+/// rejects, this reports fields bmk doesn't know, which loading ignores,
+/// unreadable timestamps and names or aliases used twice.
+pub fn validate_bookmarks(content: &[u8], format: FileFormat) -> Vec<ValidationIssue> {
+    let document: serde_yaml::Value = match parse_from_reader(content, format) {
+        Ok(document) => document,
+        Err(e) => {
+            let location = match (e.line, e.column) {
+                (Some(line), Some(column)) => format!("line {}, column {}", line, column),
+                _ => "file".to_string(),
+            };
+            return vec![ValidationIssue::new(location, e)];
+        }
+    };
+
+    let mut issues = Vec::new();
+    let (prefix, entries): (&str, &[serde_yaml::Value]) = match &document {
+        serde_yaml::Value::Null => return issues,
+        serde_yaml::Value::Sequence(entries) => ("", entries),
+        serde_yaml::Value::Mapping(map) => {
+            for (key, value) in map {
+                match key.as_str() {
+                    Some("version") => match serde_yaml::from_value::<u32>(value.clone()) {
+                        Ok(version) if version > SCHEMA_VERSION => {
+                            issues.push(ValidationIssue::new(
+                                "version",
+                                format!(
+                                    "{} is newer than this bmk reads ({})",
+                                    version, SCHEMA_VERSION
+                                ),
+                            ))
+                        }
+                        Ok(_) => {}
+                        Err(e) => issues.push(ValidationIssue::new("version", e)),
+                    },
+                    Some("bookmarks") => {}
+                    _ => issues.push(ValidationIssue::new(
+                        yaml_key(key),
+                        "unknown field, expected `version` or `bookmarks`",
+                    )),
+                }
+            }
+            if !map.contains_key("version") {
+                issues.push(ValidationIssue::new("file", "missing field `version`"));
+            }
+            match map.get("bookmarks") {
+                None => ("", &[]),
+                Some(serde_yaml::Value::Sequence(entries)) => ("bookmarks", entries),
+                Some(_) => {
+                    issues.push(ValidationIssue::new("bookmarks", "expected a list"));
+                    ("", &[])
+                }
+            }
+        }
+        _ => {
+            issues.push(ValidationIssue::new(
+                "file",
+                "expected a list of bookmarks or a map with `version` and `bookmarks`",
+            ));
+            ("", &[])
+        }
+    };
+
+    // Name or alias, and the location of the first bookmark using it
+    let mut names: HashMap<String, String> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let entry_at = format!("{}[{}]", prefix, i);
+        let serde_yaml::Value::Mapping(fields) = entry else {
+            issues.push(ValidationIssue::new(
+                entry_at,
+                "expected a map of bookmark fields",
+            ));
+            continue;
+        };
+        let name = fields.get("name").and_then(serde_yaml::Value::as_str);
+        let at = |field: &str| match name {
+            Some(name) => format!("{}.{} ({})", entry_at, field, name),
+            None => format!("{}.{}", entry_at, field),
+        };
+
+        for required in ["name", "url"] {
+            if !fields.contains_key(required) {
+                issues.push(ValidationIssue::new(at(required), "missing field"));
+            }
+        }
+        for (key, value) in fields {
+            let Some(key) = key.as_str() else {
+                issues.push(ValidationIssue::new(
+                    at(&yaml_key(key)),
+                    "field names must be text",
+                ));
+                continue;
+            };
+            if let Err(message) = check_bookmark_field(key, value) {
+                issues.push(ValidationIssue::new(at(key), message));
+            }
+        }
+
+        let aliases = fields
+            .get("aliases")
+            .and_then(serde_yaml::Value::as_sequence)
+            .into_iter()
+            .flatten()
+            .filter_map(serde_yaml::Value::as_str)
+            .map(|alias| ("aliases", alias.trim()));
+        for (field, used) in name.map(|name| ("name", name)).into_iter().chain(aliases) {
+            match names.get(used) {
+                Some(first) if first != &entry_at => issues.push(ValidationIssue::new(
+                    at(field),
+                    format!("'{}' is also the name or an alias of {}", used, first),
+                )),
+                Some(_) => {}
+                None => {
+                    names.insert(used.to_string(), entry_at.clone());
+                }
+            }
+        }
+    }
+
+    // Whatever the checks above miss still keeps the file from loading
+    if issues.is_empty()
+        && let Err(e) = load_document_from_reader(content, format).and_then(migrate)
+    {
+        issues.push(ValidationIssue::new("file", format!("{:#}", e)));
+    }
+    issues
+}
+
+/// A YAML map key as written, for locations in [`ValidationIssue`]s.
+fn yaml_key(key: &serde_yaml::Value) -> String {
+    match key {
+        serde_yaml::Value::String(key) => key.clone(),
+        key => serde_yaml::to_string(key)
+            .map(|key| key.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Check one field of a bookmark entry: that [`Bookmark`] has it, that the
+/// value has the field's type and that timestamps can be read. Fields added
+/// to [`Bookmark`] need adding here too.
+fn check_bookmark_field(key: &str, value: &serde_yaml::Value) -> std::result::Result<(), String> {
+    fn check<T: DeserializeOwned>(value: &serde_yaml::Value) -> std::result::Result<(), String> {
+        serde_yaml::from_value::<T>(value.clone())
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+
+    match key {
+        "name" | "url" | "desc" | "notes" | "icon" => check::<String>(value),
+        "tags" | "urls" | "aliases" => check::<Vec<String>>(value),
+        "visits" => check::<u64>(value),
+        "browser" => check::<Option<String>>(value),
+        "last_opened" | "created_at" | "updated_at" => {
+            check::<Option<String>>(value)?;
+            match value.as_str() {
+                Some(ts) if DateTime::parse_from_rfc3339(ts).is_err() => {
+                    Err(format!("'{}' is not an RFC 3339 timestamp", ts))
+                }
+                _ => Ok(()),
+            }
+        }
+        _ => Err("unknown field".to_string()),
+    }
+}
+
+/// Why adding, updating or renaming a bookmark was refused. Library callers
+/// can match on it; the CLI shows its message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookmarkError {
+    /// No bookmark has this name
+    NotFound(String),
+    /// No bookmark matches this query
+    NoMatch(String),
+    /// No bookmark has this tag
+    NoneTagged(String),
+    /// A bookmark with this name already exists
+    DuplicateName(String),
+    /// The URL is already bookmarked under this name
+    DuplicateUrl(String),
+    EmptyUrl,
+    /// The URL as typed, and what is wrong with it
+    InvalidUrl {
+        url: String,
+        reason: String,
+    },
+    /// The URL as typed, and why the URL parser rejected it
+    MalformedUrl {
+        url: String,
+        source: url::ParseError,
+    },
+    /// An alias equal to the bookmark's own name
+    AliasIsOwnName(String),
+    /// An alias that is already the name or an alias of `owner`
+    AliasInUse {
+        alias: String,
+        owner: String,
+    },
+    /// A tag as given, and why it can't be stored (see [`validate_tag`])
+    InvalidTag {
+        tag: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for BookmarkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(name) => write!(f, "Bookmark '{}' not found", name),
+            Self::NoMatch(query) => write!(f, "No bookmark found matching: {}", query),
+            Self::NoneTagged(tag) => write!(f, "No bookmarks tagged '{}'", tag),
+            Self::DuplicateName(name) => write!(f, "Bookmark '{}' already exists", name),
+            Self::DuplicateUrl(name) => write!(f, "URL already bookmarked under name '{}'", name),
+            Self::EmptyUrl => write!(f, "URL must not be empty"),
+            Self::InvalidUrl { url, reason } => write!(f, "Invalid URL '{}': {}", url, reason),
+            Self::MalformedUrl { url, .. } => write!(f, "Invalid URL '{}'", url),
+            Self::AliasIsOwnName(alias) => {
+                write!(f, "Alias '{}' is the bookmark's own name", alias)
+            }
+            Self::AliasInUse { alias, owner } => {
+                write!(f, "Alias '{}' is already used by '{}'", alias, owner)
+            }
+            Self::InvalidTag { tag, reason } => write!(f, "Invalid tag '{}': {}", tag, reason),
+        }
+    }
+}
+
+impl std::error::Error for BookmarkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::MalformedUrl { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl BookmarkError {
+    /// Whether this means nothing matched what was asked for, as opposed to
+    /// a request that was refused.
+    pub fn is_no_match(&self) -> bool {
+        matches!(
+            self,
+            Self::NotFound(_) | Self::NoMatch(_) | Self::NoneTagged(_)
+        )
+    }
+}
+
+/// Exit status when no bookmark matches the query, name or tag given.
+pub const EXIT_NO_MATCH: u8 = 2;
+
+/// Exit status for `err`: [`EXIT_NO_MATCH`] when it, or an error it wraps,
+/// is a [`BookmarkError`] meaning nothing matched, and 1 for anything else.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    let no_match = err.chain().any(|cause| {
+        cause
+            .downcast_ref::<BookmarkError>()
+            .is_some_and(BookmarkError::is_no_match)
+    });
+    if no_match { EXIT_NO_MATCH } else { 1 }
+}
+
+/// Where [`recover_corrupt_file`] moves a bookmarks file that can't be parsed.
+pub fn corrupt_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".corrupt");
+    path.with_file_name(name)
+}
+
+/// Move an unparseable bookmarks file out of the way to [`corrupt_path`], so
+/// the next load starts with no bookmarks. Returns where the file went.
+pub fn recover_corrupt_file(path: &Path) -> Result<PathBuf> {
+    let corrupt = corrupt_path(path);
+    fs::rename(path, &corrupt)
+        .with_context(|| format!("Failed to move '{}' aside", path.display()))?;
+    Ok(corrupt)
+}
 
-    let bookmarks: Bookmarks = serde_yaml::from_reader(file)
-        .with_context(|| format!("Failed to parse YAML from '{}'", path.display()))?;
+/// Like [`load_and_upgrade`], but when the file can't be parsed, show
+/// where and offer to set it aside and start fresh. For interactive use.
+pub fn load_bookmarks_or_recover(path: &Path) -> Result<Bookmarks> {
+    let err = match load_and_upgrade(path) {
+        Ok(bookmarks) => return Ok(bookmarks),
+        Err(err) if err.downcast_ref::<ParseError>().is_some() => err,
+        Err(err) => return Err(err),
+    };
 
-    Ok(bookmarks)
+    eprintln!("Error: {:#}", err);
+    eprintln!("`bmk restore` can bring back an earlier version from a backup.");
+    let prompt = format!(
+        "Move it to '{}' and start with no bookmarks?",
+        corrupt_path(path).display()
+    );
+    if !confirm(&prompt)? {
+        return Err(err);
+    }
+    let corrupt = recover_corrupt_file(path)?;
+    info(format!("Moved the damaged file to '{}'", corrupt.display()));
+    Ok(Vec::new())
 }
 
 pub fn save_bookmarks(bookmarks: &Bookmarks) -> Result<()> {
-    let path = get_bookmarks_path()?;
+    save_bookmarks_to(&get_bookmarks_path()?, bookmarks)
+}
+
+pub fn save_bookmarks_to(path: &Path, bookmarks: &Bookmarks) -> Result<()> {
+    write_bookmarks(path, bookmarks, true)
+}
+
+/// Like [`save_bookmarks_to`], but without a backup, for saves that only
+/// record a visit. Only [`MAX_BACKUPS`] are kept, so a backup per open would
+/// soon rotate out the ones taken before real changes.
+pub fn save_visits_to(path: &Path, bookmarks: &Bookmarks) -> Result<()> {
+    write_bookmarks(path, bookmarks, false)
+}
+
+fn write_bookmarks(path: &Path, bookmarks: &Bookmarks, backup: bool) -> Result<()> {
+    let format = FileFormat::from_path(path)?;
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).with_context(|| {
             format!(
@@ -48,21 +771,360 @@ pub fn save_bookmarks(bookmarks: &Bookmarks) -> Result<()> {
         })?;
     }
 
-    let yaml_string = serde_yaml::to_string(bookmarks)?;
+    let document = DocumentRef {
+        version: SCHEMA_VERSION,
+        bookmarks,
+    };
+    let contents = match format {
+        FileFormat::Yaml => serde_yaml::to_string(&document)?,
+        FileFormat::Json => serde_json::to_string_pretty(&document)? + "\n",
+    };
+    let contents = if is_encrypted_path(path) {
+        encrypt_contents(path, contents.as_bytes())
+            .with_context(|| format!("Failed to encrypt '{}'", path.display()))?
+    } else {
+        contents.into_bytes()
+    };
 
-    fs::write(&path, yaml_string)
-        .with_context(|| format!("Failed to write bookmarks to '{}'", path.display()))?;
+    if backup && path.is_file() {
+        backup_file(path)?;
+    }
+
+    write_atomically(path, &contents)
+        .with_context(|| format!("Failed to write bookmarks to '{}'", path.display()))
+}
+
+/// Number of backups kept next to the bookmarks file; older ones are pruned.
+pub const MAX_BACKUPS: usize = 10;
+
+/// Backups of `path`, named `<file>.<timestamp>.bak`, newest first.
+pub fn list_backups(path: &Path) -> Result<Vec<PathBuf>> {
+    let prefix = format!(
+        "{}.",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read backups in '{}'", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy())
+                .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".bak"))
+        })
+        .collect();
+    // Timestamps sort lexicographically, so reversing the name order puts the newest first
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// Copy the current file to a timestamped backup and prune the oldest ones.
+fn backup_file(path: &Path) -> Result<()> {
+    let stamp = Local::now().format("%Y%m%dT%H%M%S%6f");
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.bak", stamp));
+    let backup = path.with_file_name(name);
+
+    fs::copy(path, &backup)
+        .with_context(|| format!("Failed to back up bookmarks to '{}'", backup.display()))?;
+
+    for old in list_backups(path)?.into_iter().skip(MAX_BACKUPS) {
+        let _ = fs::remove_file(old);
+    }
     Ok(())
 }
 
-pub fn add_bookmark(bookmarks: &mut Bookmarks, bookmark: Bookmark) {
+/// Replace the bookmarks file with a backup, after checking the backup parses.
+/// The current file is itself backed up first, so a restore can be undone.
+pub fn restore_backup(path: &Path, backup: &Path) -> Result<()> {
+    let format = FileFormat::from_path(path)?;
+    let contents = fs::read(backup)
+        .with_context(|| format!("Failed to read backup '{}'", backup.display()))?;
+    // Backups of an encrypted file are encrypted too, and are restored as they are
+    let plaintext = if is_encrypted_path(path) {
+        Cow::Owned(
+            decrypt_contents(path, &contents)
+                .with_context(|| format!("Failed to decrypt backup '{}'", backup.display()))?,
+        )
+    } else {
+        Cow::Borrowed(contents.as_slice())
+    };
+    load_bookmarks_from_reader(plaintext.as_ref(), format).with_context(|| {
+        format!(
+            "Backup '{}' is not a valid bookmarks file",
+            backup.display()
+        )
+    })?;
+
+    if path.is_file() {
+        backup_file(path)?;
+    }
+    write_atomically(path, &contents)
+        .with_context(|| format!("Failed to restore bookmarks to '{}'", path.display()))
+}
+
+/// Temporary file written next to `path` before being renamed over it.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Write to a temporary file in the same directory, then rename it over `path`.
+/// The rename is atomic on the same filesystem, so an interrupted save never
+/// leaves a truncated file behind.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = temp_path(path);
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Checks applied by [`add_bookmark_with`]. The default runs every check.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AddOptions {
+    /// Allow adding a URL that is already bookmarked under another name
+    pub allow_duplicate_url: bool,
+    /// Store the URL as given, without validating it or adding a scheme
+    pub skip_url_validation: bool,
+    /// Fill an empty description with the page title (`network` feature,
+    /// used by [`handle_add_command`] only)
+    pub fetch_title: bool,
+}
+
+pub fn add_bookmark(bookmarks: &mut Bookmarks, bookmark: Bookmark) -> Result<(), BookmarkError> {
+    add_bookmark_with(bookmarks, bookmark, AddOptions::default())
+}
+
+pub fn add_bookmark_with(
+    bookmarks: &mut Bookmarks,
+    bookmark: Bookmark,
+    options: AddOptions,
+) -> Result<(), BookmarkError> {
+    let mut bookmark = bookmark;
+    if !options.skip_url_validation {
+        bookmark.url = validate_url(&bookmark.url)?;
+    }
+    bookmark.tags = check_tags(bookmark.tags)?;
+    check_name(bookmarks, None, &bookmark.name)?;
+    check_aliases(bookmarks, None, &mut bookmark)?;
+
+    if !options.allow_duplicate_url
+        && let Some(existing) = find_by_url(bookmarks, &bookmark.url)
+    {
+        return Err(BookmarkError::DuplicateUrl(existing.clone()));
+    }
+
+    bookmark.created_at = Some(timestamp());
     bookmarks.push(bookmark);
+    Ok(())
 }
 
-pub fn update_bookmark(bookmarks: &mut Bookmarks, index: usize, bookmark: Bookmark) {
+pub fn update_bookmark(
+    bookmarks: &mut Bookmarks,
+    index: usize,
+    bookmark: Bookmark,
+) -> Result<(), BookmarkError> {
+    let mut bookmark = bookmark;
+    // A URL stored with `--no-validate` is left alone unless it is changed
+    if bookmarks.get(index).is_none_or(|b| b.url != bookmark.url) {
+        bookmark.url = validate_url(&bookmark.url)?;
+    }
+    bookmark.tags = check_tags(bookmark.tags)?;
+    check_name(bookmarks, Some(index), &bookmark.name)?;
+    check_aliases(bookmarks, Some(index), &mut bookmark)?;
+
     if index < bookmarks.len() {
+        if bookmark.created_at.is_none() {
+            bookmark.created_at = bookmarks[index].created_at.take();
+        }
+        bookmark.updated_at = Some(timestamp());
         bookmarks[index] = bookmark;
     }
+    Ok(())
+}
+
+/// Index of a bookmark other than the one at `except` that answers to `name`
+/// as its name or an alias, ignoring case like [`resolve_name`] does.
+fn name_taken(bookmarks: &Bookmarks, except: Option<usize>, name: &str) -> Option<usize> {
+    let folded = name.to_lowercase();
+    let matches = |candidate: &String| candidate.to_lowercase() == folded;
+    bookmarks
+        .iter()
+        .enumerate()
+        .position(|(i, b)| Some(i) != except && (matches(&b.name) || b.aliases.iter().any(matches)))
+}
+
+/// Reject a name that another bookmark than the one at `index` already
+/// answers to, since lookups by name could no longer tell them apart.
+fn check_name(
+    bookmarks: &Bookmarks,
+    index: Option<usize>,
+    name: &str,
+) -> Result<(), BookmarkError> {
+    match name_taken(bookmarks, index, name) {
+        Some(_) => Err(BookmarkError::DuplicateName(name.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Trim and deduplicate the aliases of `bookmark`, then make sure none of them
+/// is its own name or a name or alias of another bookmark. `index` is the
+/// bookmark's current position when it is being updated.
+fn check_aliases(
+    bookmarks: &Bookmarks,
+    index: Option<usize>,
+    bookmark: &mut Bookmark,
+) -> Result<(), BookmarkError> {
+    let mut aliases: Vec<String> = Vec::with_capacity(bookmark.aliases.len());
+    for alias in &bookmark.aliases {
+        let alias = alias.trim();
+        if !alias.is_empty()
+            && !aliases
+                .iter()
+                .any(|a| a.to_lowercase() == alias.to_lowercase())
+        {
+            aliases.push(alias.to_string());
+        }
+    }
+
+    for alias in &aliases {
+        if alias.to_lowercase() == bookmark.name.to_lowercase() {
+            return Err(BookmarkError::AliasIsOwnName(alias.clone()));
+        }
+        if let Some(other) = name_taken(bookmarks, index, alias) {
+            return Err(BookmarkError::AliasInUse {
+                alias: alias.clone(),
+                owner: bookmarks[other].name.clone(),
+            });
+        }
+    }
+
+    bookmark.aliases = aliases;
+    Ok(())
+}
+
+/// Reject a tag that wouldn't survive being listed and read back. The CLI
+/// splits `--tags` on commas and the TUI joins tags with `, `, so a comma
+/// inside a tag would silently turn it into two. Surrounding whitespace and
+/// control characters such as newlines are refused for the same reason.
+pub fn validate_tag(tag: &str) -> Result<(), BookmarkError> {
+    let reason = if tag.is_empty() {
+        "tags must not be empty"
+    } else if tag.contains(',') {
+        "tags must not contain commas"
+    } else if tag.trim() != tag {
+        "tags must not start or end with whitespace"
+    } else if tag.chars().any(char::is_control) {
+        "tags must not contain control characters"
+    } else {
+        return Ok(());
+    };
+    Err(BookmarkError::InvalidTag {
+        tag: tag.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+/// [`normalize_tags`], then [`validate_tag`] on each of the results.
+fn check_tags(tags: Vec<String>) -> Result<Vec<String>, BookmarkError> {
+    let tags = normalize_tags(tags);
+    for tag in &tags {
+        validate_tag(tag)?;
+    }
+    Ok(tags)
+}
+
+/// Whether [`normalize_tags`] lowercases tags. Set to `false` for case-sensitive tags.
+pub const LOWERCASE_TAGS: bool = true;
+
+/// Trim tags, lowercase them (see [`LOWERCASE_TAGS`]), and drop empty and
+/// duplicate ones, keeping the first occurrence's position.
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim();
+        let tag = if LOWERCASE_TAGS {
+            tag.to_lowercase()
+        } else {
+            tag.to_string()
+        };
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// Check that `input` is a usable URL, prepending `https://` when it has no scheme.
+///
+/// `github.com/rust-lang` becomes `https://github.com/rust-lang`, while URLs with
+/// an explicit scheme such as `mailto:` or `file://` are kept as typed.
+pub fn validate_url(input: &str) -> Result<String, BookmarkError> {
+    let input = input.trim();
+    let invalid = |reason: &str| BookmarkError::InvalidUrl {
+        url: input.to_string(),
+        reason: reason.to_string(),
+    };
+    if input.is_empty() {
+        return Err(BookmarkError::EmptyUrl);
+    }
+    if input.contains(char::is_whitespace) {
+        return Err(invalid("contains whitespace"));
+    }
+
+    let url = if has_scheme(input) {
+        input.to_string()
+    } else {
+        format!("https://{}", input)
+    };
+
+    let parsed = Url::parse(&url).map_err(|source| BookmarkError::MalformedUrl {
+        url: input.to_string(),
+        source,
+    })?;
+    if matches!(parsed.scheme(), "http" | "https") && parsed.host_str().is_none_or(str::is_empty) {
+        return Err(invalid("missing host"));
+    }
+    Ok(url)
+}
+
+/// Whether `text` is a whole URL with a scheme and host, like
+/// `https://example.com/page`, rather than arbitrary text.
+pub fn looks_like_url(text: &str) -> bool {
+    let text = text.trim();
+    !text.contains(char::is_whitespace)
+        && Url::parse(text).is_ok_and(|url| url.host_str().is_some_and(|host| !host.is_empty()))
+}
+
+/// Whether `input` starts with a scheme like `https://` or `mailto:`. A `host:port`
+/// prefix such as `localhost:8080` is not a scheme.
+fn has_scheme(input: &str) -> bool {
+    let Some((scheme, rest)) = input.split_once(':') else {
+        return false;
+    };
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid_scheme && (rest.starts_with("//") || !rest.starts_with(|c: char| c.is_ascii_digit()))
 }
 
 pub fn delete_bookmark(bookmarks: &mut Bookmarks, index: usize) {
@@ -71,17 +1133,2188 @@ pub fn delete_bookmark(bookmarks: &mut Bookmarks, index: usize) {
     }
 }
 
-pub fn open_bookmark(url: &str) -> Result<()> {
-    webbrowser::open(url).with_context(|| format!("Failed to open URL: {}", url))?;
+/// Remove the bookmarks at `indices` and return their names. Out-of-bounds
+/// indices are skipped, like [`delete_bookmark`].
+pub fn delete_bookmarks(bookmarks: &mut Bookmarks, indices: &BTreeSet<usize>) -> Vec<String> {
+    let mut removed = Vec::new();
+    let mut index = 0;
+    bookmarks.retain(|b| {
+        let keep = !indices.contains(&index);
+        if !keep {
+            removed.push(b.name.clone());
+        }
+        index += 1;
+        keep
+    });
+    removed
+}
+
+/// Move the bookmark at `from` to position `to`, shifting the ones in between.
+/// Out-of-bounds indices are a no-op, like [`delete_bookmark`].
+pub fn move_bookmark(bookmarks: &mut Bookmarks, from: usize, to: usize) {
+    if from < bookmarks.len() && to < bookmarks.len() {
+        let bookmark = bookmarks.remove(from);
+        bookmarks.insert(to, bookmark);
+    }
+}
+
+/// Remove every bookmark carrying `tag` (case-insensitively) and return their names.
+pub fn delete_by_tag(bookmarks: &mut Bookmarks, tag: &str) -> Vec<String> {
+    let mut removed = Vec::new();
+    bookmarks.retain(|b| {
+        let tagged = b.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()));
+        if tagged {
+            removed.push(b.name.clone());
+        }
+        !tagged
+    });
+    removed
+}
+
+/// Rename a bookmark in place, keeping its position in the list.
+pub fn rename_bookmark(
+    bookmarks: &mut Bookmarks,
+    old: &str,
+    new: &str,
+) -> Result<(), BookmarkError> {
+    let index =
+        find_bookmark(bookmarks, old).ok_or_else(|| BookmarkError::NotFound(old.to_string()))?;
+    // Only changing the case of its own name is allowed
+    if find_bookmark(bookmarks, new).is_some() {
+        return Err(BookmarkError::DuplicateName(new.to_string()));
+    }
+    check_name(bookmarks, Some(index), new)?;
+
+    bookmarks[index].name = new.to_string();
     Ok(())
 }
 
-pub fn get_all_tags(bookmarks: &Bookmarks) -> Vec<String> {
-    let mut tags: Vec<String> = bookmarks
+/// Index of the first bookmark with exactly this name, or failing that, this alias.
+pub fn find_bookmark(bookmarks: &Bookmarks, name: &str) -> Option<usize> {
+    bookmarks
         .iter()
-        .flat_map(|b| b.tags.iter().cloned())
-        .collect();
-    tags.sort();
-    tags.dedup();
-    tags
+        .position(|b| b.name == name)
+        .or_else(|| bookmarks.iter().position(|b| b.answers_to(name)))
+}
+
+/// Index of the bookmark a user means by `input`: an exact [`find_bookmark`]
+/// match, or failing that, a name or alias that differs only in case.
+pub fn resolve_name(bookmarks: &Bookmarks, input: &str) -> Option<usize> {
+    find_bookmark(bookmarks, input).or_else(|| {
+        let folded = input.to_lowercase();
+        let matches = |name: &String| name.to_lowercase() == folded;
+        bookmarks
+            .iter()
+            .position(|b| matches(&b.name))
+            .or_else(|| bookmarks.iter().position(|b| b.aliases.iter().any(matches)))
+    })
+}
+
+/// Name of the bookmark whose URL matches `url` after normalization.
+pub fn find_by_url<'a>(bookmarks: &'a Bookmarks, url: &str) -> Option<&'a String> {
+    let url = normalize_url(url);
+    bookmarks
+        .iter()
+        .find(|b| normalize_url(&b.url) == url)
+        .map(|b| &b.name)
+}
+
+/// Lowercase the scheme and host and strip trailing slashes, so that
+/// `https://GitHub.com/` and `https://github.com` compare equal.
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (format!("{}://", scheme.to_ascii_lowercase()), rest),
+        None => (String::new(), url),
+    };
+    let host_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    format!(
+        "{}{}{}",
+        scheme,
+        rest[..host_end].to_ascii_lowercase(),
+        &rest[host_end..]
+    )
+}
+
+/// Bump the visit counter and last-opened time of the bookmark at `index`.
+pub fn record_open(bookmarks: &mut Bookmarks, index: usize) {
+    if let Some(bookmark) = bookmarks.get_mut(index) {
+        bookmark.visits += 1;
+        bookmark.last_opened = Some(timestamp());
+    }
+}
+
+/// The current local time as stored in the bookmark timestamp fields.
+fn timestamp() -> String {
+    Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// Parse one of the bookmark timestamp fields, ignoring malformed values.
+fn parse_timestamp(ts: Option<&str>) -> Option<DateTime<FixedOffset>> {
+    ts.and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+}
+
+/// How long a short-lived process keeps serving the clipboard on Linux, where the
+/// contents disappear with the owning process unless a clipboard manager takes them over.
+#[cfg(target_os = "linux")]
+const CLIPBOARD_HOLD: std::time::Duration = std::time::Duration::from_secs(2);
+
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut clipboard = Clipboard::new().context("Clipboard unavailable")?;
+
+    #[cfg(target_os = "linux")]
+    let result = {
+        use arboard::SetExtLinux;
+        let deadline = std::time::Instant::now() + CLIPBOARD_HOLD;
+        clipboard.set().wait_until(deadline).text(text)
+    };
+    #[cfg(not(target_os = "linux"))]
+    let result = clipboard.set_text(text);
+
+    result.context("Failed to copy to clipboard")
+}
+
+/// How a URL gets launched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opener {
+    /// The web browser, for `http` and `https` links
+    Browser,
+    /// The operating system's default handler, for schemes like `mailto:` or `file://`
+    System,
+}
+
+pub fn opener_for(url: &str) -> Opener {
+    let scheme = url.split_once(':').map(|(scheme, _)| scheme).unwrap_or("");
+    if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") {
+        Opener::Browser
+    } else {
+        Opener::System
+    }
+}
+
+pub fn open_bookmark(url: &str) -> Result<()> {
+    open_bookmark_with(url, None)
+}
+
+/// Open `url`, using the named browser for web links when one is given.
+pub fn open_bookmark_with(url: &str, browser: Option<&str>) -> Result<()> {
+    match opener_for(url) {
+        Opener::Browser => {
+            let browser = browser.map(parse_browser).transpose()?;
+            match browser {
+                None | Some(Browser::Default) => webbrowser::open(url),
+                Some(browser) => open_in(browser, url),
+            }
+            .with_context(|| format!("Failed to open URL: {}", url))?
+        }
+        Opener::System => {
+            open::that(url).with_context(|| format!("Failed to open URL: {}", url))?
+        }
+    }
+    Ok(())
+}
+
+/// Browser names accepted by `--browser` and the `browser` bookmark field.
+pub const SUPPORTED_BROWSERS: &[&str] = &[
+    "default",
+    "firefox",
+    "chrome",
+    "safari",
+    "opera",
+    "ie",
+    "webpositive",
+];
+
+pub fn parse_browser(name: &str) -> Result<Browser> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "default" => Ok(Browser::Default),
+        "firefox" => Ok(Browser::Firefox),
+        "chrome" | "google-chrome" => Ok(Browser::Chrome),
+        "safari" => Ok(Browser::Safari),
+        "opera" => Ok(Browser::Opera),
+        "ie" | "internet-explorer" => Ok(Browser::InternetExplorer),
+        "webpositive" => Ok(Browser::WebPositive),
+        _ => bail!(
+            "Unknown browser '{}' (supported: {})",
+            name,
+            SUPPORTED_BROWSERS.join(", ")
+        ),
+    }
+}
+
+/// Executables tried, in order, when launching a browser directly.
+fn browser_commands(browser: Browser) -> &'static [&'static str] {
+    match browser {
+        Browser::Firefox => &["firefox"],
+        Browser::Chrome => &[
+            "google-chrome",
+            "google-chrome-stable",
+            "chromium",
+            "chromium-browser",
+        ],
+        Browser::Opera => &["opera"],
+        _ => &[],
+    }
+}
+
+/// Browsers that `open_private` knows how to launch in a private window.
+pub const PRIVATE_BROWSERS: &[&str] = &["firefox", "chrome", "opera"];
+
+fn private_flag(browser: Browser) -> Option<&'static str> {
+    match browser {
+        Browser::Firefox => Some("--private-window"),
+        Browser::Chrome => Some("--incognito"),
+        Browser::Opera => Some("--private"),
+        _ => None,
+    }
+}
+
+/// Open `url` in a private/incognito window of the named browser. Browsers without
+/// a known private-window flag, including the unnamed default, fall back to a
+/// normal open with a warning.
+pub fn open_private(url: &str, browser: Option<&str>) -> Result<()> {
+    if opener_for(url) == Opener::System {
+        return open_bookmark_with(url, None);
+    }
+
+    let parsed = browser.map(parse_browser).transpose()?;
+    let Some((browser, flag)) = parsed.and_then(|b| private_flag(b).map(|flag| (b, flag))) else {
+        eprintln!(
+            "Warning: private windows are supported for {} only (pass --browser); opening normally",
+            PRIVATE_BROWSERS.join(", ")
+        );
+        return open_bookmark_with(url, browser);
+    };
+
+    let result = if cfg!(target_os = "macos") {
+        let app = match browser {
+            Browser::Firefox => "Firefox",
+            Browser::Chrome => "Google Chrome",
+            _ => "Opera",
+        };
+        Command::new("open")
+            .args(["-na", app, "--args", flag, url])
+            .status()
+            .and_then(|status| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(std::io::Error::other(format!(
+                        "`open` exited with {}",
+                        status
+                    )))
+                }
+            })
+    } else {
+        spawn_browser(browser, &[flag, url])
+    };
+    result.with_context(|| format!("Failed to open URL in a private window: {}", url))
+}
+
+/// Launch `url` in `browser`. `webbrowser` only supports the default browser on
+/// Linux and the BSDs, so there the browser executable is started directly.
+fn open_in(browser: Browser, url: &str) -> std::io::Result<()> {
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        return webbrowser::open_browser(browser, url);
+    }
+    spawn_browser(browser, &[url])
+}
+
+fn spawn_browser(browser: Browser, args: &[&str]) -> std::io::Result<()> {
+    let candidates = browser_commands(browser);
+    for cmd in candidates {
+        let spawned = Command::new(cmd)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!(
+            "{} is not installed (tried: {})",
+            browser,
+            candidates.join(", ")
+        ),
+    ))
+}
+
+pub fn get_all_tags(bookmarks: &Bookmarks) -> Vec<String> {
+    let mut tags: Vec<String> = bookmarks
+        .iter()
+        .flat_map(|b| b.tags.iter().cloned())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Separator between a bookmark's group and the rest of its name, as in `work/jira`.
+pub const GROUP_SEPARATOR: char = '/';
+
+/// The group of a bookmark named like `work/jira`: the part before the first
+/// [`GROUP_SEPARATOR`], as long as neither side is empty.
+pub fn bookmark_group(name: &str) -> Option<&str> {
+    name.split_once(GROUP_SEPARATOR)
+        .filter(|(group, rest)| !group.is_empty() && !rest.is_empty())
+        .map(|(group, _)| group)
+}
+
+/// Number of bookmarks in each group, sorted by group name.
+pub fn get_group_counts(bookmarks: &Bookmarks) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for group in bookmarks.iter().filter_map(|b| bookmark_group(&b.name)) {
+        *counts.entry(group).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(group, count)| (group.to_string(), count))
+        .collect()
+}
+
+/// Number of bookmarks carrying each tag, most used first, then by name.
+pub fn get_tag_counts(bookmarks: &Bookmarks) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for bookmark in bookmarks {
+        let mut tags: Vec<&str> = bookmark.tags.iter().map(String::as_str).collect();
+        tags.sort();
+        tags.dedup();
+        for tag in tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+
+    let mut counts: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(tag, count)| (tag.to_string(), count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// How a set of tags is combined when filtering bookmarks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMatch {
+    /// The bookmark must carry every tag
+    #[default]
+    All,
+    /// The bookmark must carry at least one of the tags
+    Any,
+}
+
+/// Whether `bookmark` passes a filter on `tags`, compared case-insensitively.
+/// An empty filter matches everything.
+pub fn matches_tags<S: AsRef<str>>(bookmark: &Bookmark, tags: &[S], mode: TagMatch) -> bool {
+    if tags.is_empty() {
+        return true;
+    }
+    let has = |tag: &S| {
+        bookmark
+            .tags
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(tag.as_ref()))
+    };
+    match mode {
+        TagMatch::All => tags.iter().all(has),
+        TagMatch::Any => tags.iter().any(has),
+    }
+}
+
+/// Add a bookmark. Without a URL, the URL and any fields not given as flags are
+/// asked for on stdin.
+pub fn handle_add_command(path: &Path, bookmark: Bookmark, options: AddOptions) -> Result<()> {
+    let mut bookmarks = load_and_upgrade(path)?;
+    let bookmark = if bookmark.url.is_empty() {
+        prompt_bookmark_fields(
+            bookmark,
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout(),
+        )?
+    } else {
+        bookmark
+    };
+
+    #[cfg(feature = "network")]
+    let bookmark = if options.fetch_title && bookmark.desc.is_empty() {
+        let url = validate_url(&bookmark.url).unwrap_or_else(|_| bookmark.url.clone());
+        match crate::health::fetch_title(&url) {
+            Ok(title) => Bookmark {
+                desc: title,
+                ..bookmark
+            },
+            Err(e) => {
+                eprintln!("Warning: {:#}; leaving the description empty", e);
+                bookmark
+            }
+        }
+    } else {
+        bookmark
+    };
+
+    let name = bookmark.name.clone();
+    add_bookmark_with(&mut bookmarks, bookmark, options)?;
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!("Added '{}'", name));
+    Ok(())
+}
+
+/// Fill in an empty URL, description and tags by prompting on `output` and reading
+/// lines from `input`, like the TUI add form. The URL is asked for until it is non-empty.
+pub fn prompt_bookmark_fields(
+    mut bookmark: Bookmark,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<Bookmark> {
+    let mut read = |label: &str| -> Result<Option<String>> {
+        write!(output, "{}: ", label)?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim().to_string()))
+    };
+
+    while bookmark.url.is_empty() {
+        match read("URL")? {
+            Some(url) => bookmark.url = url,
+            None => bail!("A URL is required to add '{}'", bookmark.name),
+        }
+    }
+    if bookmark.desc.is_empty() {
+        bookmark.desc = read("Description (optional)")?.unwrap_or_default();
+    }
+    if bookmark.tags.is_empty() {
+        bookmark.tags = read("Tags, comma-separated (optional)")?
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+    }
+    Ok(bookmark)
+}
+
+/// Copy the named bookmark's URL to the clipboard, or print it when `print` is set.
+pub fn handle_copy_command(path: &Path, name: &str, print: bool) -> Result<()> {
+    let bookmarks = load_and_upgrade(path)?;
+    let index =
+        find_bookmark(&bookmarks, name).ok_or_else(|| BookmarkError::NotFound(name.to_string()))?;
+    let url = &bookmarks[index].url;
+
+    if print {
+        println!("{}", url);
+    } else {
+        copy_to_clipboard(url)?;
+        info(format!("Copied '{}' to clipboard", name));
+    }
+    Ok(())
+}
+
+/// Whether [`launch_bookmark`] opens a bookmark or only shows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenMode {
+    /// Open the URLs in the browser
+    #[default]
+    Launch,
+    /// Print the URLs, with templates expanded, to stdout
+    Print,
+    /// Fetch the URLs and print what they return to stdout; only text unless
+    /// `force` is set
+    #[cfg(feature = "network")]
+    Cat { force: bool },
+}
+
+/// How [`launch_bookmark`] opens a bookmark.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    /// Browser overriding the bookmark's own setting
+    pub browser: Option<String>,
+    /// Browser for bookmarks without their own, like the one in the config file
+    pub default_browser: Option<String>,
+    /// Open in a private/incognito window
+    pub private: bool,
+    /// Open the bookmark or print its URLs
+    pub mode: OpenMode,
+    /// Search terms substituted into a URL template, or a path appended to a
+    /// base URL (see [`is_base_url`])
+    pub query: Option<String>,
+    /// Least match quality for a fuzzy match; `None` means [`DEFAULT_MIN_SCORE`]
+    pub min_score: Option<i64>,
+}
+
+impl OpenOptions {
+    /// The browser to open `bookmark` in: [`OpenOptions::browser`], then the
+    /// bookmark's own, then [`OpenOptions::default_browser`]. `None` means the
+    /// system default.
+    pub fn browser_for<'a>(&'a self, bookmark: &'a Bookmark) -> Option<&'a str> {
+        self.browser
+            .as_deref()
+            .or(bookmark.browser.as_deref())
+            .or(self.default_browser.as_deref())
+    }
+}
+
+/// Placeholder in a bookmark URL that is replaced by search terms, turning
+/// the bookmark into a search shortcut.
+pub const QUERY_PLACEHOLDER: &str = "{query}";
+
+/// Characters left as-is when substituting a query: the URL-safe unreserved set.
+const QUERY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Whether a URL contains the [`QUERY_PLACEHOLDER`].
+pub fn is_url_template(url: &str) -> bool {
+    url.contains(QUERY_PLACEHOLDER)
+}
+
+/// Replace every [`QUERY_PLACEHOLDER`] in `url` with the percent-encoded `query`.
+pub fn expand_url_template(url: &str, query: &str) -> String {
+    let encoded = utf8_percent_encode(query, QUERY_ENCODE_SET).to_string();
+    url.replace(QUERY_PLACEHOLDER, &encoded)
+}
+
+/// Whether a URL is a base that launch arguments are appended to as a path:
+/// it ends with `/` and has no [`QUERY_PLACEHOLDER`], which takes precedence.
+pub fn is_base_url(url: &str) -> bool {
+    url.ends_with('/') && !is_url_template(url)
+}
+
+/// Append `path` to the base URL `url`. Its words and `/`-separated parts
+/// become percent-encoded path segments joined by single slashes.
+pub fn append_url_path(url: &str, path: &str) -> String {
+    let segments: Vec<String> = path
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| utf8_percent_encode(segment, QUERY_ENCODE_SET).to_string())
+        .collect();
+    format!("{}{}", url, segments.join("/"))
+}
+
+/// Resolve direct launch arguments to a bookmark and an optional query.
+///
+/// If the first argument resolves to a URL template, the remaining arguments
+/// are its query (`bmk gh rust cli`). If it is the name or an alias of a
+/// bookmark with a base URL, they are appended as a path (`bmk jira PROJ-123`).
+/// Otherwise all arguments together are looked up by name, then matched
+/// against the bookmarks. Fuzzy matches need at least `min_score` (see
+/// [`find_confident_match`]).
+pub fn resolve_launch_args(
+    bookmarks: &Bookmarks,
+    args: &[String],
+    min_score: i64,
+) -> Option<(usize, Option<String>)> {
+    if let [first, rest @ ..] = args
+        && !rest.is_empty()
+    {
+        let by_name = resolve_name(bookmarks, first);
+        let index = by_name.or_else(|| find_confident_match(bookmarks, first, min_score));
+        if let Some(index) = index.filter(|&i| is_url_template(&bookmarks[i].url)) {
+            return Some((index, Some(rest.join(" "))));
+        }
+        // Plenty of plain URLs end with `/`, so a base URL only takes a path
+        // when picked by name; a fuzzy first word stays part of the query
+        if let Some(index) = by_name.filter(|&i| is_base_url(&bookmarks[i].url)) {
+            return Some((index, Some(rest.join(" "))));
+        }
+    }
+
+    let query = args.join(" ");
+    resolve_name(bookmarks, &query)
+        .or_else(|| find_confident_match(bookmarks, &query, min_score))
+        .map(|index| (index, None))
+}
+
+/// With `-v`, say on stderr how `query` picked `bookmarks[index]`: by name, or
+/// by fuzzy match with its score and match quality. With `-vv`, also list the
+/// best fuzzy matches with their scores.
+pub fn debug_match(bookmarks: &Bookmarks, query: &str, index: usize) {
+    if verbosity() < Verbosity::Verbose {
+        return;
+    }
+    let name = &bookmarks[index].name;
+    if resolve_name(bookmarks, query) == Some(index) {
+        debug(
+            Verbosity::Verbose,
+            format!("'{}' is the name or an alias of '{}'", query, name),
+        );
+    } else {
+        let pattern: Vec<char> = query.chars().collect();
+        let bookmark = &bookmarks[index];
+        debug(
+            Verbosity::Verbose,
+            format!(
+                "'{}' matched '{}' with score {} (match quality {})",
+                query,
+                name,
+                ranked_score(&pattern, bookmark, SearchScope::Fields),
+                match_quality(&pattern, bookmark).unwrap_or(-1)
+            ),
+        );
+    }
+    for (i, score) in ranked_matches(bookmarks, query)
+        .into_iter()
+        .take(MAX_CHOICES)
+    {
+        debug(
+            Verbosity::VeryVerbose,
+            format!("  {:>6}  {}", score, bookmarks[i].name),
+        );
+    }
+}
+
+/// Check a direct launch of `query` that resolved to `best`. When `query` is
+/// not a bookmark name and its best fuzzy matches are nearly tied (see
+/// [`ambiguous_matches`]), ask which one to open if stdin is a terminal, and
+/// fail with the candidates otherwise.
+pub fn confirm_ambiguous_match(bookmarks: &Bookmarks, query: &str, best: usize) -> Result<usize> {
+    if resolve_name(bookmarks, query).is_some() {
+        return Ok(best);
+    }
+    let candidates = ambiguous_matches(bookmarks, query);
+    if candidates.is_empty() {
+        return Ok(best);
+    }
+
+    if std::io::stdin().is_terminal() {
+        return choose_bookmark(
+            bookmarks,
+            &candidates,
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout(),
+        );
+    }
+    let names: Vec<&str> = candidates
+        .iter()
+        .map(|&i| bookmarks[i].name.as_str())
+        .collect();
+    bail!(
+        "Several bookmarks match '{}': {}. Pass --first to open the best match",
+        query,
+        names.join(", ")
+    )
+}
+
+/// List `candidates` numbered from 1 on `output` and read the chosen number
+/// from `input`, asking again after an invalid answer. Fails on an empty
+/// answer or the end of input.
+pub fn choose_bookmark(
+    bookmarks: &Bookmarks,
+    candidates: &[usize],
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<usize> {
+    let width = candidates
+        .iter()
+        .map(|&i| bookmarks[i].name.width())
+        .max()
+        .unwrap_or(0);
+    for (n, &i) in candidates.iter().enumerate() {
+        let bookmark = &bookmarks[i];
+        writeln!(
+            output,
+            "{:>2}) {}  {}",
+            n + 1,
+            pad_to_width(&bookmark.name, width),
+            bookmark.url
+        )?;
+    }
+
+    loop {
+        write!(output, "Open which? [1-{}]: ", candidates.len())?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            bail!("No bookmark chosen");
+        }
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=candidates.len()).contains(&n) => return Ok(candidates[n - 1]),
+            _ => writeln!(output, "Enter a number from 1 to {}", candidates.len())?,
+        }
+    }
+}
+
+/// Delay between launches when opening several bookmarks, so the browser
+/// doesn't drop tabs that arrive at once.
+pub const OPEN_ALL_DELAY: Duration = Duration::from_millis(500);
+
+/// Open `bookmark` with the browser and window mode from `options`, or print
+/// its URLs in [`OpenMode::Print`].
+///
+/// Bookmarks with several URLs open all of them, [`OPEN_ALL_DELAY`] apart. A
+/// URL that fails to open doesn't stop the others; see [`batch_result`].
+pub fn launch_bookmark(bookmark: &Bookmark, options: &OpenOptions) -> Result<()> {
+    let browser = options.browser_for(bookmark);
+    let mut failures = Vec::new();
+    let mut opened = 0;
+    for (n, url) in bookmark.all_urls().enumerate() {
+        let url = match options.query.as_deref() {
+            _ if is_url_template(url) => {
+                expand_url_template(url, options.query.as_deref().unwrap_or_default())
+            }
+            Some(path) if is_base_url(url) => append_url_path(url, path),
+            _ => url.to_string(),
+        };
+
+        if options.mode == OpenMode::Print {
+            println!("{}", url);
+            continue;
+        }
+        #[cfg(feature = "network")]
+        if let OpenMode::Cat { force } = options.mode {
+            let result = crate::health::fetch_text(&url, force).and_then(|body| {
+                std::io::stdout()
+                    .write_all(&body)
+                    .context("Failed to write to stdout")
+            });
+            match result {
+                Ok(()) => opened += 1,
+                Err(err) => failures.push((url, err)),
+            }
+            continue;
+        }
+        if n > 0 {
+            std::thread::sleep(OPEN_ALL_DELAY);
+        }
+        let result = if options.private {
+            open_private(&url, browser)
+        } else {
+            open_bookmark_with(&url, browser)
+        };
+        match result {
+            Ok(()) => opened += 1,
+            Err(err) => failures.push((url, err)),
+        }
+    }
+    batch_result(opened, failures)
+}
+
+/// Outcome of opening several things one after another: `Ok` if none of them
+/// failed, the error itself if the only attempt failed, and otherwise an error
+/// summarizing each failure, like `Opened 5, failed 1: 'x' — <error>`.
+pub fn batch_result(opened: usize, mut failures: Vec<(String, anyhow::Error)>) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    if opened == 0 && failures.len() == 1 {
+        return Err(failures.remove(0).1);
+    }
+    let failed: Vec<String> = failures
+        .iter()
+        .map(|(what, err)| format!("'{}' — {:#}", what, err))
+        .collect();
+    bail!(
+        "Opened {}, failed {}: {}",
+        opened,
+        failures.len(),
+        failed.join("; ")
+    )
+}
+
+/// Open a bookmark by name (see [`resolve_name`]), falling back to the best fuzzy match.
+pub fn handle_open_command(path: &Path, query: &str, options: &OpenOptions) -> Result<()> {
+    let mut bookmarks = load_and_upgrade(path)?;
+
+    let index = match resolve_name(&bookmarks, query) {
+        Some(index) => index,
+        None => {
+            let min_score = options.min_score.unwrap_or(DEFAULT_MIN_SCORE);
+            let index = find_confident_match(&bookmarks, query, min_score)
+                .ok_or_else(|| BookmarkError::NotFound(query.to_string()))?;
+            note(format!(
+                "Resolved '{}' to '{}'",
+                query, bookmarks[index].name
+            ));
+            debug_match(&bookmarks, query, index);
+            index
+        }
+    };
+
+    if options.mode != OpenMode::Print {
+        record_open(&mut bookmarks, index);
+        save_visits_to(path, &bookmarks)?;
+    }
+    launch_bookmark(&bookmarks[index], options)
+}
+
+/// Open every bookmark carrying `tag`, one after another. Bookmarks that fail
+/// to open are reported together at the end (see [`batch_result`]).
+pub fn handle_open_all_command(path: &Path, tag: &str, options: &OpenOptions) -> Result<()> {
+    let mut bookmarks = load_and_upgrade(path)?;
+    let indices: Vec<usize> = (0..bookmarks.len())
+        .filter(|&i| matches_tags(&bookmarks[i], &[tag], TagMatch::All))
+        .collect();
+    if indices.is_empty() {
+        return Err(BookmarkError::NoneTagged(tag.to_string()).into());
+    }
+    if options.mode == OpenMode::Print {
+        for &index in &indices {
+            launch_bookmark(&bookmarks[index], options)?;
+        }
+        return Ok(());
+    }
+
+    for &index in &indices {
+        record_open(&mut bookmarks, index);
+    }
+    save_visits_to(path, &bookmarks)?;
+    let mut failures = Vec::new();
+    let mut opened = 0;
+    for (n, &index) in indices.iter().enumerate() {
+        if n > 0 {
+            std::thread::sleep(OPEN_ALL_DELAY);
+        }
+        let bookmark = &bookmarks[index];
+        info(format!("Opening '{}'", bookmark.name));
+        match launch_bookmark(bookmark, options) {
+            Ok(()) => opened += 1,
+            Err(err) => failures.push((bookmark.name.clone(), err)),
+        }
+    }
+    batch_result(opened, failures)
+}
+
+/// Ask a yes/no question on stdin; anything but `y`/`yes` means no.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// Delete a bookmark by name (see [`resolve_name`]).
+pub fn handle_delete_command(path: &Path, name: &str) -> Result<()> {
+    let mut store = BookmarkStore::open(path)?;
+    let deleted = store.delete(name)?;
+    store.save()?;
+    info(format!("Deleted '{}'", deleted.name));
+    Ok(())
+}
+
+/// Delete every bookmark carrying `tag`, asking first unless `yes` is set.
+pub fn handle_delete_by_tag_command(path: &Path, tag: &str, yes: bool) -> Result<()> {
+    let mut bookmarks = load_and_upgrade(path)?;
+    let tagged: Vec<&str> = bookmarks
+        .iter()
+        .filter(|b| matches_tags(b, &[tag], TagMatch::All))
+        .map(|b| b.name.as_str())
+        .collect();
+    if tagged.is_empty() {
+        return Err(BookmarkError::NoneTagged(tag.to_string()).into());
+    }
+
+    if !yes {
+        println!("Bookmarks tagged '{}':", tag);
+        for name in &tagged {
+            println!("  {}", name);
+        }
+        if !confirm(&format!("Delete {} bookmarks?", tagged.len()))? {
+            info("Aborted");
+            return Ok(());
+        }
+    }
+
+    let removed = delete_by_tag(&mut bookmarks, tag);
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!(
+        "Deleted {} bookmarks: {}",
+        removed.len(),
+        removed.join(", ")
+    ));
+    Ok(())
+}
+
+/// Names of the bookmarks never opened, in file order. With `since`, also
+/// those last opened before then; a bookmark never opened counts only if it
+/// was added before then too, so new bookmarks get a chance to be used.
+pub fn find_unused(bookmarks: &Bookmarks, since: Option<DateTime<FixedOffset>>) -> Vec<String> {
+    bookmarks
+        .iter()
+        .filter(|b| {
+            let never_opened = b.visits == 0 && b.last_opened.is_none();
+            let Some(since) = since else {
+                return never_opened;
+            };
+            let last_used = if never_opened {
+                &b.created_at
+            } else {
+                &b.last_opened
+            };
+            // Without a readable time, only a never opened bookmark is unused
+            parse_timestamp(last_used.as_deref()).map_or(never_opened, |at| at < since)
+        })
+        .map(|b| b.name.clone())
+        .collect()
+}
+
+/// Delete the bookmarks [`find_unused`] finds, counting those not opened in
+/// `days` days as unused too. Lists them and asks first unless `yes` is set;
+/// `dry_run` only lists them.
+pub fn handle_prune_command(
+    path: &Path,
+    days: Option<u32>,
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    let mut bookmarks = load_and_upgrade(path)?;
+    let since = days.map(|days| Local::now().fixed_offset() - TimeDelta::days(days.into()));
+    let unused = find_unused(&bookmarks, since);
+    if unused.is_empty() {
+        println!("No unused bookmarks");
+        return Ok(());
+    }
+
+    if dry_run || !yes {
+        println!("Unused bookmarks:");
+        for name in &unused {
+            println!("  {}", name);
+        }
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !yes && !confirm(&format!("Delete {} bookmarks?", unused.len()))? {
+        info("Aborted");
+        return Ok(());
+    }
+
+    for name in &unused {
+        if let Some(index) = bookmarks.iter().position(|b| &b.name == name) {
+            delete_bookmark(&mut bookmarks, index);
+        }
+    }
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!(
+        "Deleted {} bookmarks: {}",
+        unused.len(),
+        unused.join(", ")
+    ));
+    Ok(())
+}
+
+pub fn handle_rename_command(path: &Path, old: &str, new: &str) -> Result<()> {
+    let mut bookmarks = load_and_upgrade(path)?;
+    rename_bookmark(&mut bookmarks, old, new)?;
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!("Renamed '{}' to '{}'", old, new));
+    Ok(())
+}
+
+/// Replace tag `from` (matched case-insensitively) with `to` on every bookmark,
+/// returning how many bookmarks changed. A bookmark that already had `to` keeps
+/// a single copy.
+pub fn rename_tag(bookmarks: &mut Bookmarks, from: &str, to: &str) -> Result<usize> {
+    let Some(to) = normalize_tags(vec![to.to_string()]).pop() else {
+        bail!("Tag name must not be empty");
+    };
+    validate_tag(&to)?;
+    let from = from.trim();
+
+    let mut changed = 0;
+    for bookmark in bookmarks.iter_mut() {
+        if !bookmark.tags.iter().any(|t| t.eq_ignore_ascii_case(from)) {
+            continue;
+        }
+        let mut tags: Vec<String> = Vec::with_capacity(bookmark.tags.len());
+        for tag in bookmark.tags.drain(..) {
+            let tag = if tag.eq_ignore_ascii_case(from) {
+                to.clone()
+            } else {
+                tag
+            };
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                tags.push(tag);
+            }
+        }
+        bookmark.tags = tags;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+pub fn handle_tag_rename_command(path: &Path, from: &str, to: &str) -> Result<()> {
+    let mut bookmarks = load_and_upgrade(path)?;
+    let changed = rename_tag(&mut bookmarks, from, to)?;
+    if changed == 0 {
+        return Err(BookmarkError::NoneTagged(from.to_string()).into());
+    }
+
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!(
+        "Renamed tag '{}' to '{}' on {} bookmarks",
+        from, to, changed
+    ));
+    Ok(())
+}
+
+/// Number of tags shown in the `bmk stats` summary.
+pub const TOP_TAGS: usize = 5;
+
+/// Summary of a bookmark collection, as printed by `bmk stats`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stats {
+    pub total: usize,
+    pub unique_tags: usize,
+    /// Most used tags with their counts, at most [`TOP_TAGS`]
+    pub top_tags: Vec<(String, usize)>,
+    /// Sum of all visit counts
+    pub total_opens: u64,
+}
+
+pub fn compute_stats(bookmarks: &Bookmarks) -> Stats {
+    let mut tag_counts = get_tag_counts(bookmarks);
+    let unique_tags = tag_counts.len();
+    tag_counts.truncate(TOP_TAGS);
+    Stats {
+        total: bookmarks.len(),
+        unique_tags,
+        top_tags: tag_counts,
+        total_opens: bookmarks.iter().map(|b| b.visits).sum(),
+    }
+}
+
+/// Check the links of all bookmarks, or those with `tag`, and report the broken ones.
+#[cfg(feature = "network")]
+pub fn handle_check_command(path: &Path, tag: Option<&str>, timeout: Duration) -> Result<()> {
+    use crate::health::{LinkStatus, check_links};
+
+    let bookmarks = load_and_upgrade(path)?;
+    let tags: Vec<&str> = tag.into_iter().collect();
+    let selected: Vec<&Bookmark> = bookmarks
+        .iter()
+        .filter(|b| matches_tags(b, &tags, TagMatch::All))
+        .collect();
+
+    let results = check_links(&selected, timeout)?;
+    let checked = results
+        .iter()
+        .filter(|(_, status)| *status != LinkStatus::Skipped)
+        .count();
+    let mut broken = 0;
+    for (name, status) in &results {
+        match status {
+            LinkStatus::Broken(code) => println!("{}: HTTP {}", name, code),
+            LinkStatus::Unreachable(reason) => println!("{}: {}", name, reason),
+            LinkStatus::Ok(_) | LinkStatus::Skipped => continue,
+        }
+        broken += 1;
+    }
+
+    if broken > 0 {
+        bail!("{} of {} links are broken", broken, checked);
+    }
+    info(format!("All {} links are reachable", checked));
+    Ok(())
+}
+
+/// Write an encrypted copy of a plaintext bookmarks file, by default `<file>.age`.
+/// The plaintext file is left in place for the user to remove.
+#[cfg(feature = "encryption")]
+pub fn handle_encrypt_command(path: &Path, output: Option<&Path>) -> Result<()> {
+    if is_encrypted_path(path) {
+        bail!("'{}' is already encrypted", path.display());
+    }
+    let output = match output {
+        Some(output) if !is_encrypted_path(output) => {
+            bail!("Encrypted files must end in .{}", ENCRYPTED_EXTENSION)
+        }
+        Some(output) => output.to_path_buf(),
+        None => {
+            let mut name = path.as_os_str().to_owned();
+            name.push(".");
+            name.push(ENCRYPTED_EXTENSION);
+            PathBuf::from(name)
+        }
+    };
+    convert_file(path, &output)?;
+    info(format!(
+        "Encrypted '{}' to '{}'. Point BMK_PATH or --config at it, then delete the plaintext file and its backups.",
+        path.display(),
+        output.display()
+    ));
+    Ok(())
+}
+
+/// Write a plaintext copy of an encrypted bookmarks file, by default without the `.age` suffix.
+#[cfg(feature = "encryption")]
+pub fn handle_decrypt_command(path: &Path, output: Option<&Path>) -> Result<()> {
+    if !is_encrypted_path(path) {
+        bail!("'{}' is not encrypted", path.display());
+    }
+    let output = match output {
+        Some(output) if is_encrypted_path(output) => {
+            bail!("Decrypted files must not end in .{}", ENCRYPTED_EXTENSION)
+        }
+        Some(output) => output.to_path_buf(),
+        None => path.with_extension(""),
+    };
+    convert_file(path, &output)?;
+    info(format!(
+        "Decrypted '{}' to '{}'",
+        path.display(),
+        output.display()
+    ));
+    Ok(())
+}
+
+#[cfg(feature = "encryption")]
+fn convert_file(from: &Path, to: &Path) -> Result<()> {
+    if !from.is_file() {
+        bail!("'{}' does not exist", from.display());
+    }
+    if to.exists() {
+        bail!("'{}' already exists", to.display());
+    }
+    let bookmarks = load_bookmarks_from(from)?;
+    save_bookmarks_to(to, &bookmarks)
+}
+
+/// Check the bookmarks file at `path` with [`validate_bookmarks`], printing
+/// each problem found and failing if there are any.
+pub fn handle_validate_command(path: &Path) -> Result<()> {
+    if !path.exists() {
+        bail!("No bookmarks file at '{}'", path.display());
+    }
+    let format = FileFormat::from_path(path)?;
+    let issues = validate_bookmarks(&read_bookmarks_file(path)?, format);
+    if issues.is_empty() {
+        info(format!("No problems found in '{}'", path.display()));
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("{}: {}", path.display(), issue);
+    }
+    bail!("Found {} problems in '{}'", issues.len(), path.display())
+}
+
+pub fn handle_stats_command(path: &Path) -> Result<()> {
+    let stats = compute_stats(&load_and_upgrade(path)?);
+    println!("Bookmarks:   {}", stats.total);
+    println!("Tags:        {}", stats.unique_tags);
+    println!("Total opens: {}", stats.total_opens);
+    if !stats.top_tags.is_empty() {
+        println!("Top tags:");
+        let width = stats
+            .top_tags
+            .iter()
+            .map(|(tag, _)| tag.width())
+            .max()
+            .unwrap_or(0);
+        for (tag, count) in &stats.top_tags {
+            println!("  {}  {}", pad_to_width(tag, width), count);
+        }
+    }
+    Ok(())
+}
+
+/// Opened bookmarks, most recently opened first, at most `limit` of them.
+/// Bookmarks never opened, or with an unreadable `last_opened`, are left out.
+pub fn recent_bookmarks(bookmarks: &Bookmarks, limit: usize) -> Vec<&Bookmark> {
+    let mut opened: Vec<(DateTime<FixedOffset>, &Bookmark)> = bookmarks
+        .iter()
+        .filter_map(|b| Some((parse_timestamp(b.last_opened.as_deref())?, b)))
+        .collect();
+    opened.sort_by(|(a, _), (b, _)| b.cmp(a));
+    opened.into_iter().take(limit).map(|(_, b)| b).collect()
+}
+
+/// A short description of how long ago something happened, like `5m ago` or
+/// `3d ago`, from the number of seconds since. Uses the largest whole unit.
+pub fn humanize_elapsed(seconds: i64) -> String {
+    const UNITS: [(i64, &str); 6] = [
+        (365 * 24 * 3600, "y"),
+        (30 * 24 * 3600, "mo"),
+        (7 * 24 * 3600, "w"),
+        (24 * 3600, "d"),
+        (3600, "h"),
+        (60, "m"),
+    ];
+    UNITS
+        .iter()
+        .find(|&&(size, _)| seconds >= size)
+        .map_or_else(
+            || "just now".to_string(),
+            |&(size, unit)| format!("{}{} ago", seconds / size, unit),
+        )
+}
+
+pub fn handle_recent_command(path: &Path, limit: usize) -> Result<()> {
+    let bookmarks = load_and_upgrade(path)?;
+    let recent = recent_bookmarks(&bookmarks, limit);
+    if recent.is_empty() {
+        println!("No bookmarks opened yet");
+        return Ok(());
+    }
+
+    let now = Local::now();
+    let rows: Vec<(&Bookmark, String)> = recent
+        .into_iter()
+        .map(|b| {
+            let opened = parse_timestamp(b.last_opened.as_deref()).unwrap_or(now.into());
+            (
+                b,
+                humanize_elapsed((now.fixed_offset() - opened).num_seconds()),
+            )
+        })
+        .collect();
+    let name_width = rows.iter().map(|(b, _)| b.name.width()).max().unwrap_or(0);
+    let ago_width = rows.iter().map(|(_, ago)| ago.width()).max().unwrap_or(0);
+    for (bookmark, ago) in rows {
+        println!(
+            "{}  {}  {}",
+            pad_to_width(&bookmark.name, name_width),
+            pad_to_width(&ago, ago_width),
+            bookmark.url
+        );
+    }
+    Ok(())
+}
+
+/// `text` followed by spaces up to `width` terminal columns. Unlike `{:<width$}`,
+/// which counts characters, this lines up columns when wide characters such as
+/// CJK or emoji take two columns each.
+pub fn pad_to_width(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.width());
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// `text` cut to at most `width` terminal columns, ending with `…` when
+/// something was cut. Cuts between characters, so wide and multibyte text is
+/// shortened by the columns it takes rather than split inside a character.
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let Some(room) = width.checked_sub(1) else {
+        return String::new();
+    };
+    let mut used = 0;
+    let mut truncated: String = text
+        .chars()
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= room
+        })
+        .collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Shown in place of a missing icon so names still line up.
+pub const ICON_PLACEHOLDER: &str = "·";
+
+/// Columns taken by the widest icon, or 0 when no bookmark has one.
+pub fn icon_width(bookmarks: &[Bookmark]) -> usize {
+    bookmarks.iter().map(|b| b.icon.width()).max().unwrap_or(0)
+}
+
+/// The icon column for `bookmark`, padded to `width` and followed by a space.
+/// Empty when `width` is 0, so lists without icons look as before.
+pub fn icon_label(bookmark: &Bookmark, width: usize) -> String {
+    if width == 0 {
+        return String::new();
+    }
+    let icon = if bookmark.icon.is_empty() {
+        ICON_PLACEHOLDER
+    } else {
+        &bookmark.icon
+    };
+    format!("{} ", pad_to_width(icon, width))
+}
+
+pub fn handle_tags_command(path: &Path) -> Result<()> {
+    let bookmarks = load_and_upgrade(path)?;
+    let counts = get_tag_counts(&bookmarks);
+
+    let width = counts.iter().map(|(tag, _)| tag.width()).max().unwrap_or(0);
+    for (tag, count) in counts {
+        println!("{}  {}", pad_to_width(&tag, width), count);
+    }
+    Ok(())
+}
+
+/// List backups, or restore the `index`-th newest one (starting at 1).
+pub fn handle_restore_command(path: &Path, index: Option<usize>) -> Result<()> {
+    let backups = list_backups(path)?;
+    if backups.is_empty() {
+        bail!("No backups found for '{}'", path.display());
+    }
+
+    let Some(index) = index else {
+        for (i, backup) in backups.iter().enumerate() {
+            println!("{:>2}  {}", i + 1, backup.display());
+        }
+        println!("Run `bmk restore <number>` to restore one");
+        return Ok(());
+    };
+
+    let backup = index
+        .checked_sub(1)
+        .and_then(|i| backups.get(i))
+        .with_context(|| format!("No backup number {} (1-{})", index, backups.len()))?;
+    restore_backup(path, backup)?;
+    info(format!("Restored '{}'", backup.display()));
+    Ok(())
+}
+
+/// The editor for `bmk edit-file`: `$EDITOR`, or `vi` (`notepad` on Windows).
+pub fn editor_command() -> String {
+    std::env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string())
+}
+
+/// Copy of `path` edited by [`handle_edit_file_command`], e.g.
+/// `bookmarks.edit.yaml`. Keeps the extension so editors highlight it.
+pub fn edit_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.edit.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.edit", stem),
+    };
+    path.with_file_name(name)
+}
+
+/// Open a copy of the bookmarks file in `editor` and, once it exits, replace
+/// the file with the copy if it still parses. `editor` may include arguments,
+/// e.g. `code --wait`. The bookmarks file is left alone when the editor fails
+/// or the copy is invalid; an invalid copy is kept so the edits aren't lost.
+pub fn handle_edit_file_command(path: &Path, editor: &str) -> Result<()> {
+    if is_encrypted_path(path) {
+        bail!(
+            "Cannot edit encrypted file '{}' directly; decrypt it first",
+            path.display()
+        );
+    }
+    let format = FileFormat::from_path(path)?;
+    let original = if path.exists() {
+        fs::read(path).with_context(|| format!("Failed to read '{}'", path.display()))?
+    } else {
+        Vec::new()
+    };
+
+    let copy = edit_path(path);
+    fs::write(&copy, &original).with_context(|| format!("Failed to write '{}'", copy.display()))?;
+
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("No editor set")?;
+    let status = Command::new(program).args(words).arg(&copy).status();
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            let _ = fs::remove_file(&copy);
+            return Err(e).with_context(|| format!("Failed to start editor '{}'", editor));
+        }
+    };
+    if !status.success() {
+        let _ = fs::remove_file(&copy);
+        bail!(
+            "Editor exited with {}; '{}' was not changed",
+            status,
+            path.display()
+        );
+    }
+
+    let edited = fs::read(&copy).with_context(|| format!("Failed to read '{}'", copy.display()))?;
+    if edited == original {
+        let _ = fs::remove_file(&copy);
+        info("No changes");
+        return Ok(());
+    }
+
+    let bookmarks = load_bookmarks_from_reader(edited.as_slice(), format).with_context(|| {
+        format!(
+            "'{}' was not changed; your edits are in '{}'",
+            path.display(),
+            copy.display()
+        )
+    })?;
+
+    if path.is_file() {
+        backup_file(path)?;
+    }
+    write_atomically(path, &edited)
+        .with_context(|| format!("Failed to write bookmarks to '{}'", path.display()))?;
+    let _ = fs::remove_file(&copy);
+    info(format!(
+        "Updated '{}' ({} bookmarks)",
+        path.display(),
+        bookmarks.len()
+    ));
+    Ok(())
+}
+
+/// Print bookmarks matching `query`, best first, without opening any.
+/// Fails when nothing matches, like launching with a query does.
+pub fn handle_search_command(path: &Path, query: &str, limit: Option<usize>) -> Result<()> {
+    let bookmarks = load_and_upgrade(path)?;
+    let ranked = rank_bookmarks(&bookmarks, query);
+    if ranked.is_empty() {
+        return Err(BookmarkError::NoMatch(query.to_string()).into());
+    }
+
+    let matches: Vec<&Bookmark> = ranked
+        .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|i| &bookmarks[i])
+        .collect();
+    let width = matches.iter().map(|b| b.name.width()).max().unwrap_or(0);
+    for bookmark in matches {
+        println!("{}  {}", pad_to_width(&bookmark.name, width), bookmark.url);
+    }
+    Ok(())
+}
+
+/// Output and filtering for [`handle_list_command`].
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Print only bookmark names, one per line
+    pub names_only: bool,
+    /// Print a JSON array instead of the table
+    pub json: bool,
+    /// Only list bookmarks with these tags
+    pub tags: Vec<String>,
+    pub tag_match: TagMatch,
+    /// List at most this many bookmarks
+    pub limit: Option<usize>,
+    /// Skip this many bookmarks first
+    pub offset: usize,
+    pub sort: ListSort,
+    /// Print grouped bookmarks (see [`bookmark_group`]) indented under their group
+    pub tree: bool,
+    /// End with a `#` line counting the bookmarks, or with `json`, wrap the
+    /// array in an object with the counts
+    pub count: bool,
+}
+
+/// Order of the bookmarks printed by `bmk list`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSort {
+    /// Order of the bookmarks file
+    #[default]
+    File,
+    /// Most recently added first; bookmarks without `created_at` come last
+    Recent,
+}
+
+/// Shape of each entry in `bmk list --json`. Unlike the bookmarks file, every
+/// field is always present so scripts don't have to handle missing keys.
+#[derive(Serialize)]
+struct ListEntry<'a> {
+    name: &'a str,
+    url: &'a str,
+    desc: &'a str,
+    tags: &'a [String],
+}
+
+/// `bmk list --json --count`: the listed entries with how many bookmarks
+/// passed the tag filter and how many there are in total.
+#[derive(Serialize)]
+struct CountedList<'a> {
+    count: usize,
+    total: usize,
+    bookmarks: Vec<ListEntry<'a>>,
+}
+
+/// The `--count` summary, e.g. `# 312 bookmarks (24 matching tag 'dev')`.
+/// `matching` is only mentioned when a tag filter is active.
+pub fn count_summary(total: usize, matching: usize, tags: &[String], mode: TagMatch) -> String {
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    let mut summary = format!("# {} bookmark{}", total, plural(total));
+    if !tags.is_empty() {
+        let quoted: Vec<String> = tags.iter().map(|t| format!("'{}'", t)).collect();
+        let joiner = match mode {
+            TagMatch::All => " and ",
+            TagMatch::Any => " or ",
+        };
+        summary.push_str(&format!(
+            " ({} matching tag{} {})",
+            matching,
+            plural(tags.len()),
+            quoted.join(joiner)
+        ));
+    }
+    summary
+}
+
+pub fn handle_list_command(path: &Path, options: ListOptions) -> Result<()> {
+    let bookmarks = load_and_upgrade(path)?;
+    write_bookmark_list(&bookmarks, &options, &mut std::io::stdout())
+}
+
+/// Write the bookmarks passing the tag filter in the format chosen by `options`.
+pub fn write_bookmark_list(
+    bookmarks: &Bookmarks,
+    options: &ListOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut matching: Vec<&Bookmark> = bookmarks
+        .iter()
+        .filter(|b| matches_tags(b, &options.tags, options.tag_match))
+        .collect();
+    if options.sort == ListSort::Recent {
+        matching.sort_by_key(|b| std::cmp::Reverse(parse_timestamp(b.created_at.as_deref())));
+    }
+    let all = bookmarks.len();
+    let total = matching.len();
+    let summary = count_summary(all, total, &options.tags, options.tag_match);
+    let bookmarks: Vec<&Bookmark> = matching
+        .into_iter()
+        .skip(options.offset)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    if options.json {
+        let entries: Vec<ListEntry> = bookmarks
+            .iter()
+            .map(|b| ListEntry {
+                name: &b.name,
+                url: &b.url,
+                desc: &b.desc,
+                tags: &b.tags,
+            })
+            .collect();
+        if options.count {
+            let counted = CountedList {
+                count: total,
+                total: all,
+                bookmarks: entries,
+            };
+            serde_json::to_writer_pretty(&mut *out, &counted)
+        } else {
+            serde_json::to_writer_pretty(&mut *out, &entries)
+        }
+        .context("Failed to serialize bookmarks as JSON")?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    if options.names_only {
+        for bookmark in &bookmarks {
+            writeln!(out, "{}", bookmark.name)?;
+        }
+        if options.count {
+            writeln!(out, "{}", summary)?;
+        }
+        return Ok(());
+    }
+
+    // Rows of the table: a name column, which for --tree is indented under a
+    // group heading row that has no bookmark, and the bookmark itself
+    let mut rows: Vec<(String, Option<&Bookmark>)> = Vec::with_capacity(bookmarks.len());
+    if options.tree {
+        let mut groups: Vec<&str> = Vec::new();
+        for bookmark in &bookmarks {
+            match bookmark_group(&bookmark.name) {
+                Some(group) if !groups.contains(&group) => groups.push(group),
+                Some(_) => {}
+                None => rows.push((bookmark.name.clone(), Some(bookmark))),
+            }
+        }
+        for group in groups {
+            rows.push((format!("{}{}", group, GROUP_SEPARATOR), None));
+            for bookmark in bookmarks
+                .iter()
+                .filter(|b| bookmark_group(&b.name) == Some(group))
+            {
+                let rest = &bookmark.name[group.len() + GROUP_SEPARATOR.len_utf8()..];
+                rows.push((format!("  {}", rest), Some(bookmark)));
+            }
+        }
+    } else {
+        rows.extend(bookmarks.iter().map(|b| (b.name.clone(), Some(*b))));
+    }
+
+    let width = rows
+        .iter()
+        .filter(|(_, bookmark)| bookmark.is_some())
+        .map(|(name, _)| name.width())
+        .max()
+        .unwrap_or(0);
+    for (name, bookmark) in &rows {
+        let Some(bookmark) = bookmark else {
+            writeln!(out, "{}", name)?;
+            continue;
+        };
+        let tags = if bookmark.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", bookmark.tags.join(", "))
+        };
+        writeln!(
+            out,
+            "{}  {}{}",
+            pad_to_width(name, width),
+            bookmark.url,
+            tags
+        )?;
+    }
+
+    // Footer for the human-readable table only, so --names and --json stay parseable
+    if options.limit.is_some() || options.offset > 0 {
+        if bookmarks.is_empty() {
+            writeln!(out, "showing 0 of {}", total)?;
+        } else {
+            writeln!(
+                out,
+                "showing {}-{} of {}",
+                options.offset + 1,
+                options.offset + bookmarks.len(),
+                total
+            )?;
+        }
+    }
+    if options.count {
+        writeln!(out, "{}", summary)?;
+    }
+    Ok(())
+}
+
+/// Changes made by [`handle_edit_command`]. Fields left as `None` keep their
+/// current value.
+#[derive(Debug, Clone, Default)]
+pub struct EditOptions {
+    pub url: Option<String>,
+    pub desc: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub aliases: Option<Vec<String>>,
+    pub notes: Option<String>,
+    pub icon: Option<String>,
+}
+
+/// Update only the given fields of the named bookmark, leaving the rest untouched.
+pub fn handle_edit_command(path: &Path, name: &str, edit: EditOptions) -> Result<()> {
+    let EditOptions {
+        url,
+        desc,
+        tags,
+        aliases,
+        notes,
+        icon,
+    } = edit;
+    let mut bookmarks = load_and_upgrade(path)?;
+    let index =
+        resolve_name(&bookmarks, name).ok_or_else(|| BookmarkError::NotFound(name.to_string()))?;
+
+    let mut bookmark = bookmarks[index].clone();
+    if let Some(url) = url {
+        bookmark.url = url;
+    }
+    if let Some(desc) = desc {
+        bookmark.desc = desc;
+    }
+    if let Some(tags) = tags {
+        bookmark.tags = tags;
+    }
+    if let Some(aliases) = aliases {
+        bookmark.aliases = aliases;
+    }
+    if let Some(notes) = notes {
+        bookmark.notes = notes;
+    }
+    if let Some(icon) = icon {
+        bookmark.icon = icon;
+    }
+
+    let name = bookmark.name.clone();
+    update_bookmark(&mut bookmarks, index, bookmark)?;
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!("Updated '{}'", name));
+    Ok(())
+}
+
+/// Source formats understood by `bmk import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// Netscape bookmark HTML, as exported by Firefox, Chrome and Safari
+    Netscape,
+}
+
+/// Counts of what happened to each bookmark during [`import_bookmarks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportReport {
+    pub added: usize,
+    /// Not added because the URL is already bookmarked (see [`find_by_url`])
+    pub skipped_duplicate: usize,
+    /// Added under a suffixed name because the name was taken
+    pub renamed: usize,
+    /// Not added because the bookmark is invalid, e.g. a tag with a newline
+    pub skipped_invalid: usize,
+}
+
+/// Add imported bookmarks to `into`, under a suffixed name (`GitHub-2`) when
+/// the name is taken. Unless `allow_duplicates` is set, bookmarks whose URL is
+/// already bookmarked, or came earlier in the import, are skipped, so
+/// importing the same export twice adds nothing the second time. Invalid
+/// bookmarks are skipped with a warning on stderr.
+pub fn import_bookmarks(
+    into: &mut Bookmarks,
+    from: Bookmarks,
+    allow_duplicates: bool,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+    for mut bookmark in from {
+        if !allow_duplicates && find_by_url(into, &bookmark.url).is_some() {
+            report.skipped_duplicate += 1;
+            continue;
+        }
+        let name = unique_name(into, &bookmark.name);
+        let renamed = name != bookmark.name;
+        bookmark.name = name;
+        // Browser exports hold absolute URLs, including schemes like `place:`
+        let options = AddOptions {
+            allow_duplicate_url: true,
+            skip_url_validation: true,
+            ..Default::default()
+        };
+        let name = bookmark.name.clone();
+        match add_bookmark_with(into, bookmark, options) {
+            Ok(()) if renamed => report.renamed += 1,
+            Ok(()) => report.added += 1,
+            Err(e) => {
+                eprintln!("Warning: skipping '{}': {}", name, e);
+                report.skipped_invalid += 1;
+            }
+        }
+    }
+    report
+}
+
+pub fn handle_import_command(
+    path: &Path,
+    format: ImportFormat,
+    file: &Path,
+    allow_duplicates: bool,
+) -> Result<()> {
+    let imported = match format {
+        ImportFormat::Netscape => import_netscape_html(file)?,
+    };
+
+    let mut bookmarks = load_and_upgrade(path)?;
+    let report = import_bookmarks(&mut bookmarks, imported, allow_duplicates);
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!(
+        "Imported '{}': {} added, {} renamed, {} skipped as duplicates, {} skipped as invalid",
+        file.display(),
+        report.added,
+        report.renamed,
+        report.skipped_duplicate,
+        report.skipped_invalid
+    ));
+    Ok(())
+}
+
+/// What [`merge_bookmarks`] does with a bookmark whose name is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MergeStrategy {
+    /// Keep the existing bookmark and skip the incoming one
+    #[default]
+    Keep,
+    /// Replace the existing bookmark with the incoming one
+    Overwrite,
+    /// Add the incoming bookmark under a suffixed name (`GitHub-2`)
+    Rename,
+}
+
+/// Counts of what happened to each bookmark during [`merge_bookmarks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MergeReport {
+    pub added: usize,
+    pub skipped: usize,
+    /// Not merged because the URL is already bookmarked under another name
+    pub skipped_duplicate: usize,
+    pub overwritten: usize,
+    pub renamed: usize,
+}
+
+/// Merge `from` into `into`. Bookmarks with new names are added; name
+/// conflicts are resolved by `strategy`. Unless `allow_duplicates` is set,
+/// bookmarks whose URL is already bookmarked, other than by the bookmark they
+/// overwrite, are skipped. Incoming aliases that are already taken in `into`
+/// are dropped.
+pub fn merge_bookmarks(
+    into: &mut Bookmarks,
+    from: Bookmarks,
+    strategy: MergeStrategy,
+    allow_duplicates: bool,
+) -> MergeReport {
+    let mut report = MergeReport::default();
+    for mut bookmark in from {
+        let existing = into
+            .iter()
+            .position(|b| b.name.to_lowercase() == bookmark.name.to_lowercase());
+        let index = match (existing, strategy) {
+            (Some(_), MergeStrategy::Keep) => {
+                report.skipped += 1;
+                continue;
+            }
+            (Some(index), MergeStrategy::Overwrite) => Some(index),
+            _ => None,
+        };
+
+        let url = normalize_url(&bookmark.url);
+        let duplicate = into
+            .iter()
+            .enumerate()
+            .any(|(i, b)| Some(i) != index && normalize_url(&b.url) == url);
+        if duplicate && !allow_duplicates {
+            report.skipped_duplicate += 1;
+            continue;
+        }
+        match (existing, index) {
+            (Some(_), Some(_)) => report.overwritten += 1,
+            // Another name in a different case, or an alias, is taken as well
+            _ if name_taken(into, None, &bookmark.name).is_some() => {
+                bookmark.name = unique_name(into, &bookmark.name);
+                report.renamed += 1;
+            }
+            _ => report.added += 1,
+        }
+
+        bookmark
+            .aliases
+            .retain(|alias| find_bookmark(into, alias).is_none_or(|i| Some(i) == index));
+        match index {
+            Some(index) => into[index] = bookmark,
+            None => into.push(bookmark),
+        }
+    }
+    report
+}
+
+/// Merge the bookmarks from `other` into the bookmarks file.
+pub fn handle_merge_command(
+    path: &Path,
+    other: &Path,
+    strategy: MergeStrategy,
+    allow_duplicates: bool,
+) -> Result<()> {
+    // Loading would take a missing file for an empty one
+    if !other.is_file() {
+        bail!("'{}' does not exist", other.display());
+    }
+    let mut bookmarks = load_and_upgrade(path)?;
+    let incoming = load_bookmarks_from(other)?;
+
+    let report = merge_bookmarks(&mut bookmarks, incoming, strategy, allow_duplicates);
+    save_bookmarks_to(path, &bookmarks)?;
+    info(format!(
+        "Merged '{}': {} added, {} skipped, {} overwritten, {} renamed, {} skipped as duplicates",
+        other.display(),
+        report.added,
+        report.skipped,
+        report.overwritten,
+        report.renamed,
+        report.skipped_duplicate
+    ));
+    Ok(())
+}
+
+/// Target formats for `bmk export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Yaml,
+    Json,
+    /// Netscape bookmark HTML, which browsers can import
+    Netscape,
+    /// A Markdown list of links, for pasting into a chat or README
+    Markdown,
+}
+
+/// The bookmarks `bmk export` writes: all of them when `names` and `tags`
+/// are empty, otherwise those named in `names` plus those with every tag in
+/// `tags`, in file order. Fails on an unknown name or an empty selection.
+pub fn select_bookmarks(
+    bookmarks: &Bookmarks,
+    names: &[String],
+    tags: &[String],
+) -> Result<Bookmarks> {
+    if names.is_empty() && tags.is_empty() {
+        return Ok(bookmarks.clone());
+    }
+
+    let mut selected = vec![false; bookmarks.len()];
+    for name in names {
+        let index =
+            resolve_name(bookmarks, name).ok_or_else(|| BookmarkError::NotFound(name.clone()))?;
+        selected[index] = true;
+    }
+    if !tags.is_empty() {
+        for (i, bookmark) in bookmarks.iter().enumerate() {
+            selected[i] |= matches_tags(bookmark, tags, TagMatch::All);
+        }
+    }
+
+    let chosen: Bookmarks = bookmarks
+        .iter()
+        .zip(selected)
+        .filter(|(_, selected)| *selected)
+        .map(|(bookmark, _)| bookmark.clone())
+        .collect();
+    if chosen.is_empty() {
+        return Err(BookmarkError::NoneTagged(tags.join(", ")).into());
+    }
+    Ok(chosen)
+}
+
+/// One `- [label](url)` line per bookmark, labelled with the description or,
+/// without one, the name.
+pub fn to_markdown(bookmarks: &[Bookmark]) -> String {
+    bookmarks
+        .iter()
+        .map(|bookmark| {
+            let label = if bookmark.desc.is_empty() {
+                &bookmark.name
+            } else {
+                &bookmark.desc
+            };
+            let label = label.replace('[', "\\[").replace(']', "\\]");
+            // Parentheses and spaces would end the link target early
+            let url = bookmark
+                .url
+                .replace(' ', "%20")
+                .replace('(', "%28")
+                .replace(')', "%29");
+            format!("- [{}]({})\n", label, url)
+        })
+        .collect()
+}
+
+/// Write `bookmarks` to `out` in the given format.
+pub fn export_bookmarks(
+    bookmarks: &Bookmarks,
+    format: ExportFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        ExportFormat::Yaml => serde_yaml::to_writer(&mut *out, bookmarks)?,
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, bookmarks)?;
+            writeln!(out)?;
+        }
+        ExportFormat::Netscape => write_netscape_html(bookmarks, out)?,
+        ExportFormat::Markdown => out.write_all(to_markdown(bookmarks).as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Export to `output`, or stdout when it is `None`.
+pub fn handle_export_command(
+    path: &Path,
+    format: ExportFormat,
+    output: Option<&Path>,
+    names: &[String],
+    tags: &[String],
+) -> Result<()> {
+    let bookmarks = select_bookmarks(&load_and_upgrade(path)?, names, tags)?;
+    let Some(output) = output else {
+        return export_bookmarks(&bookmarks, format, &mut std::io::stdout().lock());
+    };
+
+    let mut contents = Vec::new();
+    export_bookmarks(&bookmarks, format, &mut contents)?;
+    write_atomically(output, &contents)
+        .with_context(|| format!("Failed to write export to '{}'", output.display()))?;
+    info(format!(
+        "Exported {} bookmarks to '{}'",
+        bookmarks.len(),
+        output.display()
+    ));
+    Ok(())
+}
+
+/// Write a flat Netscape bookmark file, the format browsers import and export.
+pub fn write_netscape_html(bookmarks: &Bookmarks, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "<!DOCTYPE NETSCAPE-Bookmark-file-1>")?;
+    writeln!(
+        out,
+        r#"<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">"#
+    )?;
+    writeln!(out, "<TITLE>Bookmarks</TITLE>")?;
+    writeln!(out, "<H1>Bookmarks</H1>")?;
+    writeln!(out, "<DL><p>")?;
+    for bookmark in bookmarks {
+        write!(
+            out,
+            r#"    <DT><A HREF="{}""#,
+            encode_entities(&bookmark.url)
+        )?;
+        if !bookmark.tags.is_empty() {
+            write!(
+                out,
+                r#" TAGS="{}""#,
+                encode_entities(&bookmark.tags.join(","))
+            )?;
+        }
+        writeln!(out, ">{}</A>", encode_entities(&bookmark.name))?;
+        if !bookmark.desc.is_empty() {
+            writeln!(out, "    <DD>{}", encode_entities(&bookmark.desc))?;
+        }
+    }
+    writeln!(out, "</DL><p>")
+}
+
+/// Return `name`, or `name-2`, `name-3`, ... if a bookmark with that name already exists.
+pub fn unique_name(bookmarks: &Bookmarks, name: &str) -> String {
+    let taken = |candidate: &str| name_taken(bookmarks, None, candidate).is_some();
+    if !taken(name) {
+        return name.to_string();
+    }
+
+    (2..)
+        .map(|n| format!("{}-{}", name, n))
+        .find(|candidate| !taken(candidate))
+        .expect("an unused suffix always exists")
+}
+
+/// Turn a title into a name that is easy to type: lowercase, with every run
+/// of characters other than letters and digits replaced by a single `-`.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(slug.trim_end_matches('-').len());
+    slug
+}
+
+pub fn import_netscape_html(path: &Path) -> Result<Bookmarks> {
+    let html = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read bookmarks HTML from '{}'", path.display()))?;
+    Ok(parse_netscape_html(&html))
+}
+
+/// Parse the `<DT><A HREF=...>title</A>` entries of a Netscape bookmark export,
+/// named after the [`slugify`]d title, or the URL when there is none.
+/// Malformed entries are skipped with a warning on stderr.
+pub fn parse_netscape_html(html: &str) -> Bookmarks {
+    let lower = html.to_ascii_lowercase();
+    let mut bookmarks = Bookmarks::new();
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find("<a") {
+        let start = pos + offset;
+        pos = start + 2;
+        if !lower[pos..].starts_with(|c: char| c == '>' || c.is_ascii_whitespace()) {
+            continue;
+        }
+
+        let Some(tag_len) = lower[start..].find('>') else {
+            eprintln!("Warning: skipping unterminated <A> tag at byte {}", start);
+            break;
+        };
+        let attrs = &html[start + 2..start + tag_len];
+        let text_start = start + tag_len + 1;
+        let Some(text_len) = lower[text_start..].find("</a>") else {
+            eprintln!("Warning: skipping <A> tag without </A> at byte {}", start);
+            break;
+        };
+        pos = text_start + text_len + 4;
+
+        let Some(url) = html_attribute(attrs, "href").filter(|u| !u.is_empty()) else {
+            eprintln!("Warning: skipping bookmark without HREF at byte {}", start);
+            continue;
+        };
+
+        let title = decode_entities(html[text_start..text_start + text_len].trim());
+        let name = match slugify(&title) {
+            slug if slug.is_empty() => slugify(&url),
+            slug => slug,
+        };
+        let tags = html_attribute(attrs, "tags")
+            .map(|t| {
+                t.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // A <DD> directly after the link holds its description
+        let rest = html[pos..].trim_start();
+        // Compare bytes, since text after the link can start with a multibyte character
+        let desc = if rest
+            .as_bytes()
+            .get(..4)
+            .is_some_and(|b| b.eq_ignore_ascii_case(b"<dd>"))
+        {
+            let body = &rest[4..];
+            decode_entities(body[..body.find('<').unwrap_or(body.len())].trim())
+        } else {
+            String::new()
+        };
+
+        bookmarks.push(Bookmark {
+            name: unique_name(&bookmarks, &name),
+            url,
+            desc,
+            tags,
+            ..Default::default()
+        });
+    }
+
+    bookmarks
+}
+
+fn html_attribute(attrs: &str, name: &str) -> Option<String> {
+    let lower = attrs.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(offset) = lower[search..].find(name) {
+        let start = search + offset;
+        search = start + name.len();
+
+        let preceded_by_space = start == 0 || lower.as_bytes()[start - 1].is_ascii_whitespace();
+        let rest = lower[search..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = attrs.len() - rest.len() + 1;
+        let value = attrs[value_start..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &value[1..];
+                &inner[..inner.find(quote).unwrap_or(inner.len())]
+            }
+            _ => &value[..value.find(char::is_whitespace).unwrap_or(value.len())],
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+fn encode_entities(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub(crate) fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
 }
@@ -0,0 +1,456 @@
+use crate::bookmarks::{
+    BOOKMARKS_PATH_ENV, ExportFormat, ImportFormat, ListSort, MergeStrategy, get_bookmarks_path,
+};
+use crate::config::Config;
+use crate::fuzzy::DEFAULT_MIN_SCORE;
+#[cfg(feature = "skim")]
+use crate::fuzzy::Matcher;
+use crate::theme::ThemeName;
+use anyhow::Result;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// A terminal-based bookmark manager.
+///
+/// Run without arguments to launch the TUI, or pass a query to open the
+/// best matching bookmark directly.
+///
+/// Exits with 0 on success, 2 when no bookmark matches the query, name or
+/// tag given, and 1 on any other error, including invalid arguments.
+#[derive(Debug, Parser)]
+#[command(name = "bmk", version, about)]
+pub struct Cli {
+    /// Bookmarks file to use (takes precedence over BMK_PATH and the default location)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+
+    /// Print only the output asked for, without confirmations like "Added 'gh'"
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Explain which files are used and why a bookmark matched; -vv also lists
+    /// the runner-up matches with their scores
+    #[arg(long, short, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Browser to open the bookmark in (overrides the bookmark's own setting and
+    /// the config file's `browser`)
+    #[arg(long, value_name = "NAME")]
+    pub browser: Option<String>,
+
+    /// Open in a private/incognito window (Firefox, Chrome and Opera)
+    #[arg(long)]
+    pub private: bool,
+
+    /// Print the URL of the best match instead of opening it
+    #[arg(long, visible_alias = "print")]
+    pub dry_run: bool,
+
+    /// Match the query against YAML or JSON bookmarks read from stdin instead
+    /// of the bookmarks file, which is left untouched
+    #[arg(long, requires = "query")]
+    pub stdin: bool,
+
+    /// Fetch the best match's URL and print its body instead of opening it; text only
+    #[cfg(feature = "network")]
+    #[arg(long, conflicts_with = "dry_run")]
+    pub cat: bool,
+
+    /// With --cat, print the body even if it isn't text
+    #[cfg(feature = "network")]
+    #[arg(long, requires = "cat")]
+    pub force: bool,
+
+    /// Open the best match without asking when several match about equally well
+    #[arg(long)]
+    pub first: bool,
+
+    /// Least match quality for opening a fuzzy match; 0 accepts any match
+    #[arg(long, global = true, value_name = "N", default_value_t = DEFAULT_MIN_SCORE)]
+    pub min_score: i64,
+
+    /// Choose from a numbered list on stdin instead of the TUI; implied by TERM=dumb
+    #[arg(long, visible_alias = "no-tui")]
+    pub plain: bool,
+
+    /// Start the TUI searching for TERM, to refine interactively instead of
+    /// opening the best match
+    #[arg(long, short, value_name = "TERM", conflicts_with = "query")]
+    pub search: Option<String>,
+
+    /// Start the TUI filtered by the search the previous session ended with
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Fuzzy matching algorithm
+    #[cfg(feature = "skim")]
+    #[arg(long, global = true, value_enum, default_value_t = Matcher::Builtin)]
+    pub matcher: Matcher,
+
+    /// Color theme for the TUI (overrides BMK_THEME)
+    #[arg(long, value_enum, value_name = "THEME")]
+    pub theme: Option<ThemeName>,
+
+    /// Fuzzy query for the bookmark to open
+    pub query: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Add a new bookmark
+    Add {
+        /// Display name of the bookmark
+        name: String,
+        /// URL to open; when omitted, the URL, description and tags are prompted for
+        url: Option<String>,
+        /// Optional description
+        #[arg(long, default_value = "")]
+        desc: String,
+        /// Comma-separated tags; can be repeated
+        #[arg(long, value_delimiter = ',')]
+        tags: Vec<String>,
+        /// A single tag, easier to quote than a --tags list; can be repeated.
+        /// Added after the tags from --tags. Tags must not contain commas
+        #[arg(long = "tag", value_name = "TAG")]
+        tag: Vec<String>,
+        /// Longer free-form notes
+        #[arg(long, default_value = "")]
+        notes: String,
+        /// Emoji or short glyph shown before the name in the TUI
+        #[arg(long, default_value = "")]
+        icon: String,
+        /// Comma-separated other names for the bookmark
+        #[arg(long, value_delimiter = ',')]
+        aliases: Vec<String>,
+        /// Add even if the URL is already bookmarked under another name
+        #[arg(long)]
+        force: bool,
+        /// Store the URL as given, e.g. for custom schemes the validator rejects
+        #[arg(long)]
+        no_validate: bool,
+        /// Use the page title as the description when --desc is not given
+        #[cfg(feature = "network")]
+        #[arg(long)]
+        fetch_title: bool,
+    },
+    /// Report bookmarks whose links are broken or unreachable
+    #[cfg(feature = "network")]
+    Check {
+        /// Only check bookmarks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Seconds to wait for each server
+        #[arg(long, default_value_t = crate::health::DEFAULT_TIMEOUT.as_secs())]
+        timeout: u64,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell,
+    },
+    /// Copy a bookmark's URL to the clipboard
+    Copy {
+        /// Name of the bookmark to copy
+        name: String,
+        /// Print the URL to stdout instead, for environments without a clipboard
+        #[arg(long)]
+        print: bool,
+    },
+    /// Delete a bookmark by name, or every bookmark with a tag
+    Delete {
+        /// Name of the bookmark to delete
+        #[arg(required_unless_present = "tag", conflicts_with = "tag")]
+        name: Option<String>,
+        /// Delete every bookmark with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Don't ask for confirmation when deleting by tag
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Write a plaintext copy of an encrypted (.age) bookmarks file
+    #[cfg(feature = "encryption")]
+    Decrypt {
+        /// File to write, by default the bookmarks file without .age
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Change fields of an existing bookmark; omitted fields are kept
+    Edit {
+        /// Name of the bookmark to edit
+        name: String,
+        /// New URL
+        #[arg(long)]
+        url: Option<String>,
+        /// New description
+        #[arg(long)]
+        desc: Option<String>,
+        /// New comma-separated tags, replacing the existing ones
+        #[arg(long, value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+        /// New comma-separated aliases, replacing the existing ones
+        #[arg(long, value_delimiter = ',')]
+        aliases: Option<Vec<String>>,
+        /// New notes; an empty string removes them
+        #[arg(long)]
+        notes: Option<String>,
+        /// New icon; an empty string removes it
+        #[arg(long)]
+        icon: Option<String>,
+    },
+    /// Open the bookmarks file in $EDITOR, keeping the changes only if it still parses
+    EditFile,
+    /// Write a passphrase-encrypted copy of the bookmarks file
+    #[cfg(feature = "encryption")]
+    Encrypt {
+        /// File to write, ending in .age; by default the bookmarks file with .age appended
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Export bookmarks to stdout or a file
+    Export {
+        /// Format to write
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+        /// File to write instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Export this bookmark; can be repeated
+        #[arg(long = "name", value_name = "NAME")]
+        names: Vec<String>,
+        /// Export bookmarks with these comma-separated tags
+        #[arg(long, value_delimiter = ',')]
+        tag: Vec<String>,
+    },
+    /// Import bookmarks exported from a browser
+    Import {
+        /// Format of the file being imported
+        #[arg(long, value_enum, default_value_t = ImportFormat::Netscape)]
+        format: ImportFormat,
+        /// File to import
+        file: PathBuf,
+        /// Import bookmarks whose URL is already bookmarked instead of skipping them
+        #[arg(long)]
+        allow_duplicates: bool,
+    },
+    /// List all bookmarks
+    List {
+        /// Print only bookmark names, one per line
+        #[arg(long)]
+        names: bool,
+        /// Print a JSON array of bookmarks, for scripting
+        #[arg(long, conflicts_with = "names")]
+        json: bool,
+        /// Only list bookmarks with these comma-separated tags
+        #[arg(long, value_delimiter = ',')]
+        tag: Vec<String>,
+        /// Require every tag given with --tag (the default)
+        #[arg(long, conflicts_with = "any_tags")]
+        all_tags: bool,
+        /// Require at least one of the tags given with --tag
+        #[arg(long)]
+        any_tags: bool,
+        /// List at most this many bookmarks
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Skip this many bookmarks first
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Order to list bookmarks in [default: file, or `default_sort` from the config file]
+        #[arg(long, value_enum)]
+        sort: Option<ListSort>,
+        /// List bookmarks named like group/name under a heading for their group
+        #[arg(long, conflicts_with_all = ["names", "json"])]
+        tree: bool,
+        /// End with a `# N bookmarks` line; with --json, wrap the list in {count, total, bookmarks}
+        #[arg(long)]
+        count: bool,
+    },
+    /// Merge the bookmarks from another file into this one
+    Merge {
+        /// Bookmarks file to merge in
+        file: PathBuf,
+        /// What to do when a bookmark name exists in both files
+        #[arg(long, value_enum, default_value_t = MergeStrategy::Keep)]
+        strategy: MergeStrategy,
+        /// Merge bookmarks whose URL is already bookmarked instead of skipping them
+        #[arg(long)]
+        allow_duplicates: bool,
+    },
+    /// Open a bookmark by name, or the best fuzzy match if there is none
+    Open {
+        /// Name of the bookmark to open
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        name: Vec<String>,
+        /// Open every bookmark with the tag given by --tag
+        #[arg(long, requires = "tag")]
+        all: bool,
+        /// Tag of the bookmarks to open with --all
+        #[arg(long, requires = "all")]
+        tag: Option<String>,
+        /// Print the URLs that would be opened without opening them
+        #[arg(long, visible_alias = "print")]
+        dry_run: bool,
+        /// Browser to open the bookmark in (overrides the bookmark's own setting)
+        #[arg(long, value_name = "NAME")]
+        browser: Option<String>,
+        /// Open in a private/incognito window (Firefox, Chrome and Opera)
+        #[arg(long)]
+        private: bool,
+        /// Fetch the URL and print its body instead of opening it; text only
+        #[cfg(feature = "network")]
+        #[arg(long, conflicts_with_all = ["all", "dry_run"])]
+        cat: bool,
+        /// With --cat, print the body even if it isn't text
+        #[cfg(feature = "network")]
+        #[arg(long, requires = "cat")]
+        force: bool,
+    },
+    /// Delete bookmarks that are never opened, after listing them
+    Prune {
+        /// Select bookmarks that were never opened
+        #[arg(long, required = true)]
+        unused: bool,
+        /// Also count bookmarks not opened in this many days as unused
+        #[arg(long, value_name = "N")]
+        days: Option<u32>,
+        /// List the bookmarks that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask for confirmation
+        #[arg(long, short, conflicts_with = "dry_run")]
+        yes: bool,
+    },
+    /// List the most recently opened bookmarks with how long ago each was opened
+    Recent {
+        /// Print at most this many bookmarks
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Rename a bookmark
+    Rename {
+        /// Current name
+        old: String,
+        /// New name
+        new: String,
+    },
+    /// List backups of the bookmarks file, or restore one
+    Restore {
+        /// Backup to restore, as numbered in the listing (1 is the newest)
+        number: Option<usize>,
+    },
+    /// Print bookmarks matching a fuzzy query, best first, without opening any
+    Search {
+        /// Fuzzy query
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Print at most this many matches
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Show bookmark and tag counts and total opens
+    Stats,
+    /// List tags with the number of bookmarks using each, or manage them
+    #[command(alias = "tag")]
+    Tags {
+        #[command(subcommand)]
+        action: Option<TagsCommand>,
+    },
+    /// Check a bookmarks file for mistakes, listing every problem found
+    Validate {
+        /// File to check, by default the bookmarks file
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TagsCommand {
+    /// Rename a tag on every bookmark that has it
+    Rename {
+        /// Current tag, matched case-insensitively
+        from: String,
+        /// New tag
+        to: String,
+    },
+}
+
+impl Cli {
+    /// Resolve the bookmarks file: `--config` > `BMK_PATH` > the config
+    /// file's `bookmarks_path` > the default location.
+    pub fn bookmarks_path(&self, config: &Config) -> Result<PathBuf> {
+        if let Some(path) = &self.config {
+            return Ok(path.clone());
+        }
+        let env_set = std::env::var_os(BOOKMARKS_PATH_ENV).is_some_and(|p| !p.is_empty());
+        match &config.bookmarks_path {
+            Some(path) if !env_set => Ok(path.clone()),
+            _ => get_bookmarks_path(),
+        }
+    }
+}
+
+// Completes bookmark names for the top-level query, for the commands that
+// take a name (`open`, `copy`, `edit`, `delete`, `rename`) and for
+// `export --name`, on top of the static completions generated by clap.
+const BASH_NAMES: &str = r#"
+_bmk_names() {
+    local IFS=$'\n'
+    COMPREPLY+=($(compgen -W "$(bmk list --names 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}"))
+}
+
+_bmk_with_names() {
+    _bmk "$@"
+    case "${COMP_WORDS[1]}" in
+        open|copy|edit|delete|rename) [[ ${COMP_CWORD} -eq 2 ]] && _bmk_names ;;
+        export) [[ ${COMP_WORDS[COMP_CWORD-1]} == --name ]] && _bmk_names ;;
+        *) [[ ${COMP_CWORD} -eq 1 ]] && _bmk_names ;;
+    esac
+}
+
+complete -F _bmk_with_names -o nosort -o bashdefault -o default bmk
+"#;
+
+const ZSH_NAMES: &str = r#"
+_bmk_names() {
+    local -a names
+    names=("${(@f)$(bmk list --names 2>/dev/null)}")
+    compadd -a names
+}
+
+_bmk_with_names() {
+    if (( CURRENT == 2 )) \
+        || { (( CURRENT == 3 )) && [[ $words[2] == (open|copy|edit|delete|rename) ]] } \
+        || { [[ $words[2] == export && $words[CURRENT-1] == --name ]] }; then
+        _bmk_names
+    fi
+    _bmk "$@"
+}
+
+compdef _bmk_with_names bmk
+"#;
+
+const FISH_NAMES: &str = r#"
+complete -c bmk -n "__fish_use_subcommand" -f -a "(bmk list --names 2>/dev/null)"
+complete -c bmk -n "__fish_seen_subcommand_from open copy edit delete rename; and not __fish_seen_subcommand_from tags" -f -a "(bmk list --names 2>/dev/null)"
+complete -c bmk -n "__fish_seen_subcommand_from export" -l name -x -a "(bmk list --names 2>/dev/null)"
+"#;
+
+/// Write the completion script for `shell`, including bookmark name completion where supported.
+pub fn generate_completions(shell: Shell, out: &mut dyn Write) -> Result<()> {
+    let mut cmd = Cli::command();
+    clap_complete::generate(shell, &mut cmd, "bmk", out);
+
+    let names = match shell {
+        Shell::Bash => BASH_NAMES,
+        Shell::Zsh => ZSH_NAMES,
+        Shell::Fish => FISH_NAMES,
+        _ => "",
+    };
+    out.write_all(names.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use crate::bookmarks::{ListSort, config_dir};
+use crate::output::{Verbosity, debug};
+use crate::theme::ThemeName;
+use anyhow::{Context, Result};
+use home::home_dir;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File in the config directory with defaults for flags and environment variables.
+pub const CONFIG_FILE: &str = "config.yaml";
+
+/// Defaults read from `~/.config/bmk/config.yaml`. Flags and environment
+/// variables take precedence over them; anything left out keeps the built-in
+/// default.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Bookmarks file used when neither `--config` nor `BMK_PATH` is given
+    pub bookmarks_path: Option<PathBuf>,
+    /// Browser used when `--browser` isn't given
+    pub browser: Option<String>,
+    /// TUI theme used when neither `--theme`, `NO_COLOR` nor `BMK_THEME` is set
+    pub theme: Option<ThemeName>,
+    /// Order of `bmk list` when `--sort` isn't given
+    pub default_sort: Option<ListSort>,
+    /// Ask before deleting in the TUI, with `bmk delete --tag` and `bmk prune`
+    pub confirm_delete: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bookmarks_path: None,
+            browser: None,
+            theme: None,
+            default_sort: None,
+            confirm_delete: true,
+        }
+    }
+}
+
+/// Load the config file from the config directory, or the defaults if there is none.
+pub fn load_config() -> Result<Config> {
+    // Without a home directory there is nowhere to look, same as no file
+    match config_dir() {
+        Ok(dir) => load_config_from(&dir.join(CONFIG_FILE)),
+        Err(_) => Ok(Config::default()),
+    }
+}
+
+/// Load the config file at `path`, or the defaults if it doesn't exist. A
+/// leading `~/` in `bookmarks_path` is expanded to the home directory.
+pub fn load_config_from(path: &Path) -> Result<Config> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    debug(
+        Verbosity::Verbose,
+        format!("Reading config file '{}'", path.display()),
+    );
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    // An empty file is valid YAML for "nothing set", but not for serde_yaml
+    if content.trim().is_empty() {
+        return Ok(Config::default());
+    }
+    let mut config: Config = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    if let Some(rest) = config
+        .bookmarks_path
+        .as_deref()
+        .and_then(|p| p.strip_prefix("~").ok())
+    {
+        let home = home_dir().context("Failed to find the home directory")?;
+        config.bookmarks_path = Some(home.join(rest));
+    }
+    Ok(config)
+}
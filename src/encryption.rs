@@ -0,0 +1,76 @@
+use age::secrecy::SecretString;
+use age::{DecryptError, Decryptor, Encryptor};
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::io::{Read, Write};
+use std::iter;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Environment variable holding the passphrase, for scripts. Prompted for when unset.
+pub const PASSPHRASE_ENV: &str = "BMK_PASSPHRASE";
+
+// Passphrase entered at the prompt, reused for the rest of the run
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Passphrase for `path`, from `BMK_PASSPHRASE`, an earlier prompt in this run, or a
+/// new prompt. `confirm` asks twice, for files that are about to be created.
+pub fn passphrase(path: &Path, confirm: bool) -> Result<String> {
+    if let Some(passphrase) = env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()) {
+        return Ok(passphrase);
+    }
+
+    let mut cached = PASSPHRASE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+
+    let passphrase = rpassword::prompt_password(format!("Passphrase for '{}': ", path.display()))
+        .context("Failed to read passphrase")?;
+    if passphrase.is_empty() {
+        bail!("Passphrase must not be empty");
+    }
+    if confirm {
+        let again = rpassword::prompt_password("Confirm passphrase: ")
+            .context("Failed to read passphrase")?;
+        if again != passphrase {
+            bail!("Passphrases do not match");
+        }
+    }
+
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+fn forget_passphrase() {
+    *PASSPHRASE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let encryptor = Encryptor::with_user_passphrase(SecretString::from(passphrase.to_owned()));
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut encrypted)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?;
+    Ok(encrypted)
+}
+
+/// Decrypt `ciphertext`, reporting a wrong passphrase as such rather than as corrupt data.
+pub fn decrypt(ciphertext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let decryptor = Decryptor::new(ciphertext).context("Not an age-encrypted file")?;
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_owned()));
+    let mut reader = match decryptor.decrypt(iter::once(&identity as &dyn age::Identity)) {
+        Ok(reader) => reader,
+        Err(DecryptError::DecryptionFailed | DecryptError::NoMatchingKeys) => {
+            forget_passphrase();
+            bail!("Wrong passphrase");
+        }
+        Err(e) => return Err(e).context("Failed to decrypt"),
+    };
+
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .context("Failed to decrypt: the file is corrupted")?;
+    Ok(plaintext)
+}
@@ -0,0 +1,392 @@
+use crate::bookmarks::{Bookmark, Bookmarks};
+#[cfg(feature = "skim")]
+use clap::ValueEnum;
+#[cfg(feature = "skim")]
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use std::cmp::Ordering;
+#[cfg(feature = "skim")]
+use std::sync::LazyLock;
+#[cfg(feature = "skim")]
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+/// Weight of the usage bonus added to fuzzy scores. The bonus grows with the
+/// logarithm of the visit count so it breaks ties between similar matches
+/// without letting a popular bookmark outrank a clearly better match.
+pub const VISIT_WEIGHT: f64 = 10.0;
+
+/// Bonus for a field that also matches with the case of the query as typed,
+/// so `API` prefers `API Docs` over `api-client`. Matching itself ignores case.
+pub const EXACT_CASE_BONUS: i64 = 15;
+
+/// Bonus for a pattern made of the first letters of the text's words, like
+/// `gc` for `google-calendar`, since that is how people abbreviate.
+pub const ACRONYM_BONUS: i64 = 50;
+
+/// Default for the least [`match_quality`] a bookmark needs to be opened from
+/// a query. Each matched character earns 10 points plus bonuses for starting
+/// a word, following the previous match, or being near the start, so letters
+/// scattered through a long URL or description stay below this.
+pub const DEFAULT_MIN_SCORE: i64 = 20;
+
+/// Score gap within which the best matches for a direct launch count as a
+/// tie, so `bmk` asks which one to open.
+pub const AMBIGUITY_DELTA: i64 = 10;
+
+/// Most bookmarks offered to choose from when a query is ambiguous.
+pub const MAX_CHOICES: usize = 5;
+
+/// Algorithm that scores a query against one field of a bookmark.
+#[cfg(feature = "skim")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Matcher {
+    /// bmk's own matcher, tuned for short names and acronyms
+    #[default]
+    Builtin,
+    /// SkimMatcherV2 from the `fuzzy-matcher` crate, which ranks like fzf and skim
+    Skim,
+}
+
+#[cfg(feature = "skim")]
+static USE_SKIM: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "skim")]
+static SKIM: LazyLock<SkimMatcherV2> = LazyLock::new(|| SkimMatcherV2::default().respect_case());
+
+/// Choose the matcher [`fuzzy_match`] uses for the rest of the process.
+/// Called once from `main` with the `--matcher` flag.
+#[cfg(feature = "skim")]
+pub fn set_matcher(matcher: Matcher) {
+    USE_SKIM.store(matcher == Matcher::Skim, AtomicOrdering::Relaxed);
+}
+
+/// The matcher chosen with [`set_matcher`], [`Matcher::Builtin`] until then.
+#[cfg(feature = "skim")]
+pub fn current_matcher() -> Matcher {
+    if USE_SKIM.load(AtomicOrdering::Relaxed) {
+        Matcher::Skim
+    } else {
+        Matcher::Builtin
+    }
+}
+
+/// Which parts of a bookmark a query is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchScope {
+    /// Name and aliases, URL, description and tags, in that order of
+    /// importance: a match in the name outranks any match in the URL
+    #[default]
+    Fields,
+    /// Every field joined together, including extra URLs and notes, for
+    /// when it's unclear where a term appears. A match may span fields
+    All,
+}
+
+fn is_word_separator(c: char) -> bool {
+    matches!(c, '/' | '.' | '-' | '_' | ' ')
+}
+
+/// Fuzzy score plus a bonus for frequently opened bookmarks, or -1 if there is no match.
+pub fn ranked_score(pattern: &[char], bookmark: &Bookmark, scope: SearchScope) -> i64 {
+    let score = fuzzy_score(pattern, bookmark, scope);
+    if score < 0 {
+        return score;
+    }
+    score + usage_bonus(bookmark)
+}
+
+fn usage_bonus(bookmark: &Bookmark) -> i64 {
+    (VISIT_WEIGHT * (bookmark.visits as f64).ln_1p()) as i64
+}
+
+/// Score of the best matching field of `bookmark` within `scope`, or -1 if
+/// none matches. `pattern` is the query as typed; matching ignores case.
+pub fn fuzzy_score(pattern: &[char], bookmark: &Bookmark, scope: SearchScope) -> i64 {
+    match scope {
+        SearchScope::Fields => {
+            best_field(pattern, bookmark).map_or(-1, |(score, offset)| score + offset)
+        }
+        SearchScope::All => {
+            let lower: Vec<char> = pattern.iter().flat_map(|c| c.to_lowercase()).collect();
+            field_score(pattern, &lower, &all_fields(bookmark))
+        }
+    }
+}
+
+/// Every text field of `bookmark`, most important first, separated by spaces.
+fn all_fields(bookmark: &Bookmark) -> String {
+    std::iter::once(&bookmark.name)
+        .chain(&bookmark.aliases)
+        .chain(std::iter::once(&bookmark.url))
+        .chain(&bookmark.urls)
+        .chain([&bookmark.desc, &bookmark.notes])
+        .chain(&bookmark.tags)
+        .filter(|field| !field.is_empty())
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Average points per query character in the best matching field of
+/// `bookmark`, or `None` if nothing matches. Unlike [`fuzzy_score`] this
+/// doesn't depend on which field matched, so it measures how good the match is.
+pub fn match_quality(pattern: &[char], bookmark: &Bookmark) -> Option<i64> {
+    let (score, _) = best_field(pattern, bookmark)?;
+    Some(score / pattern.len().max(1) as i64)
+}
+
+/// Score of the first matching field, in order of importance, and the offset
+/// that ranks it above matches in less important fields.
+fn best_field(pattern: &[char], bookmark: &Bookmark) -> Option<(i64, i64)> {
+    let lower: Vec<char> = pattern.iter().flat_map(|c| c.to_lowercase()).collect();
+    let score = |text: &str| field_score(pattern, &lower, text);
+
+    // Aliases count as names, so `tickets` finds a bookmark named `jira`
+    let name_score = || {
+        std::iter::once(&bookmark.name)
+            .chain(&bookmark.aliases)
+            .map(|n| score(n))
+            .max()
+            .unwrap_or(-1)
+    };
+    let url_score = || score(&bookmark.url);
+    let desc_score = || score(&bookmark.desc);
+    let tag_score = || bookmark.tags.iter().map(|t| score(t)).max().unwrap_or(-1);
+
+    // Fields after the first match don't count, so they aren't scored at all
+    let fields: [(&dyn Fn() -> i64, i64); 4] = [
+        (&name_score, 1000),
+        (&url_score, 500),
+        (&desc_score, 100),
+        (&tag_score, 0),
+    ];
+    fields
+        .into_iter()
+        .map(|(score, offset)| (score(), offset))
+        .find(|&(score, _)| score >= 0)
+}
+
+/// Case-insensitive [`fuzzy_match`] of `lower` against `text`, plus
+/// [`EXACT_CASE_BONUS`] if `pattern` also matches with its original case.
+fn field_score(pattern: &[char], lower: &[char], text: &str) -> i64 {
+    let score = fuzzy_match(lower, &text.to_lowercase());
+    if score >= 0 && fuzzy_match(pattern, text) >= 0 {
+        score + EXACT_CASE_BONUS
+    } else {
+        score
+    }
+}
+
+/// Score of `pattern` against `text`, or -1 if it doesn't match. Case
+/// matters; callers lowercase both sides to ignore it. Uses the matcher
+/// chosen with `--matcher` when built with the `skim` feature.
+pub fn fuzzy_match(pattern: &[char], text: &str) -> i64 {
+    #[cfg(feature = "skim")]
+    return fuzzy_match_with(current_matcher(), pattern, text);
+    #[cfg(not(feature = "skim"))]
+    builtin_match(pattern, text)
+}
+
+/// [`fuzzy_match`] with an explicit matcher rather than the process-wide one.
+#[cfg(feature = "skim")]
+pub fn fuzzy_match_with(matcher: Matcher, pattern: &[char], text: &str) -> i64 {
+    match matcher {
+        Matcher::Builtin => builtin_match(pattern, text),
+        Matcher::Skim if pattern.is_empty() => 0,
+        Matcher::Skim => SKIM
+            .fuzzy_match(text, &pattern.iter().collect::<String>())
+            .unwrap_or(-1),
+    }
+}
+
+fn builtin_match(pattern: &[char], text: &str) -> i64 {
+    if pattern.is_empty() {
+        return 0;
+    }
+
+    let text_chars: Vec<char> = text.chars().collect();
+    let mut pattern_idx = 0;
+    let mut score: i64 = 0;
+    let mut last_match: Option<usize> = None;
+    let mut consecutive = 0i64;
+
+    for (i, &c) in text_chars.iter().enumerate() {
+        if pattern_idx < pattern.len() && c == pattern[pattern_idx] {
+            if let Some(last) = last_match {
+                if i == last + 1 {
+                    consecutive += 10;
+                } else {
+                    consecutive = 0;
+                }
+            }
+
+            let boundary_bonus = if i == 0
+                || text_chars
+                    .get(i.wrapping_sub(1))
+                    .is_some_and(|&c| is_word_separator(c))
+            {
+                20
+            } else {
+                0
+            };
+
+            let position_bonus = 10 - (i.min(10) as i64);
+            score += 10 + consecutive + boundary_bonus + position_bonus;
+            last_match = Some(i);
+            pattern_idx += 1;
+        }
+    }
+
+    if pattern_idx < pattern.len() {
+        -1
+    } else if is_acronym(pattern, text) {
+        score + ACRONYM_BONUS
+    } else {
+        score
+    }
+}
+
+/// Whether every character of `pattern` (at least two) is, in order, the first
+/// letter of a word of `text`, with words separated like the boundary bonus.
+/// Patterns found verbatim in the text, like `gh` in `gh-home`, don't count.
+fn is_acronym(pattern: &[char], text: &str) -> bool {
+    if pattern.len() < 2 || text.contains(&pattern.iter().collect::<String>()) {
+        return false;
+    }
+    let mut initials = text
+        .split(is_word_separator)
+        .filter_map(|word| word.chars().next());
+    pattern.iter().all(|&p| initials.any(|c| c == p))
+}
+
+/// Character positions in `text` that [`fuzzy_match`] matches against `pattern`,
+/// or `None` if it doesn't match. Used to highlight why a result matched.
+pub fn fuzzy_match_indices(pattern: &[char], text: &str) -> Option<Vec<usize>> {
+    let mut indices = Vec::with_capacity(pattern.len());
+    for (i, c) in text.chars().enumerate() {
+        if indices.len() < pattern.len() && c == pattern[indices.len()] {
+            indices.push(i);
+        }
+    }
+    (indices.len() == pattern.len()).then_some(indices)
+}
+
+/// Indices and [`ranked_score`]s of the bookmarks accepted by `keep` that
+/// match `query`, best first. An empty query matches every kept bookmark with
+/// a score of 0. `tie_break` orders equal scores, and the sort is stable, so
+/// returning [`Ordering::Equal`] keeps file order.
+///
+/// This is the one place search results are filtered, scored and sorted; the
+/// TUI list and the CLI lookups below all go through it.
+pub fn compute_visible_items(
+    bookmarks: &[Bookmark],
+    query: &str,
+    scope: SearchScope,
+    keep: impl Fn(&Bookmark) -> bool,
+    tie_break: impl Fn(&Bookmark, &Bookmark) -> Ordering,
+) -> Vec<(usize, i64)> {
+    let kept = bookmarks
+        .iter()
+        .enumerate()
+        .filter(|(_, bm)| keep(bm))
+        .map(|(i, _)| i);
+    rank_candidates(bookmarks, kept, query, scope, tie_break)
+}
+
+/// The scoring and sorting half of [`compute_visible_items`], for only the
+/// bookmarks at `candidates`, which must be in file order for ties to come
+/// out the same. A query's matches are always among the matches of any prefix
+/// of it, so while a query is being typed, the previous results are enough.
+pub fn rank_candidates(
+    bookmarks: &[Bookmark],
+    candidates: impl IntoIterator<Item = usize>,
+    query: &str,
+    scope: SearchScope,
+    tie_break: impl Fn(&Bookmark, &Bookmark) -> Ordering,
+) -> Vec<(usize, i64)> {
+    let query_chars: Vec<char> = query.chars().collect();
+
+    let mut scored: Vec<(usize, i64)> = candidates
+        .into_iter()
+        .filter_map(|i| {
+            if query_chars.is_empty() {
+                return Some((i, 0));
+            }
+            let score = ranked_score(&query_chars, &bookmarks[i], scope);
+            (score >= 0).then_some((i, score))
+        })
+        .collect();
+    scored.sort_by(|&(a, a_score), &(b, b_score)| {
+        b_score
+            .cmp(&a_score)
+            .then_with(|| tie_break(&bookmarks[a], &bookmarks[b]))
+    });
+    scored
+}
+
+/// Indices of all bookmarks matching `query` with a score above zero, best match first.
+pub fn rank_bookmarks(bookmarks: &Bookmarks, query: &str) -> Vec<usize> {
+    ranked_matches(bookmarks, query)
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Like [`rank_bookmarks`], with the score of each match.
+pub fn ranked_matches(bookmarks: &Bookmarks, query: &str) -> Vec<(usize, i64)> {
+    let mut scored = compute_visible_items(
+        bookmarks,
+        query,
+        SearchScope::Fields,
+        |_| true,
+        |_, _| Ordering::Equal,
+    );
+    scored.retain(|&(_, score)| score > 0);
+    scored
+}
+
+/// The best matches for `query`, if the runner-up scores within
+/// [`AMBIGUITY_DELTA`] of the best: every match that close, at most
+/// [`MAX_CHOICES`]. Empty when one bookmark clearly wins.
+pub fn ambiguous_matches(bookmarks: &Bookmarks, query: &str) -> Vec<usize> {
+    let ranked = ranked_matches(bookmarks, query);
+    let Some(&(_, best)) = ranked.first() else {
+        return Vec::new();
+    };
+
+    let close: Vec<usize> = ranked
+        .into_iter()
+        .take_while(|&(_, score)| best - score <= AMBIGUITY_DELTA)
+        .take(MAX_CHOICES)
+        .map(|(i, _)| i)
+        .collect();
+    if close.len() > 1 { close } else { Vec::new() }
+}
+
+/// Find the best matching bookmark for a given query, searching names,
+/// aliases, URLs, descriptions and tags like the TUI does.
+/// Returns the name and URL of the best match, None if nothing matches well
+/// enough. A URL template is returned as is, see [`expand_url_template`].
+///
+/// [`expand_url_template`]: crate::bookmarks::expand_url_template
+pub fn find_best_match(bookmarks: &Bookmarks, query: &str) -> Option<(String, String)> {
+    find_best_match_index(bookmarks, query).map(|i| {
+        let bookmark = &bookmarks[i];
+        (bookmark.name.clone(), bookmark.url.clone())
+    })
+}
+
+/// Like [`find_best_match`], but returns the index of the matching bookmark.
+pub fn find_best_match_index(bookmarks: &Bookmarks, query: &str) -> Option<usize> {
+    find_confident_match(bookmarks, query, DEFAULT_MIN_SCORE)
+}
+
+/// Index of the best match for `query`, unless its [`match_quality`] is below
+/// `min_score`. A `min_score` of 0 accepts any match.
+pub fn find_confident_match(bookmarks: &Bookmarks, query: &str, min_score: i64) -> Option<usize> {
+    let query_chars: Vec<char> = query.chars().collect();
+
+    rank_bookmarks(bookmarks, query)
+        .first()
+        .copied()
+        .filter(|&i| match_quality(&query_chars, &bookmarks[i]).is_some_and(|q| q >= min_score))
+}
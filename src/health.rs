@@ -0,0 +1,217 @@
+use crate::bookmarks::{Bookmark, Opener, decode_entities, opener_for};
+use anyhow::{Context, Result, bail};
+use reqwest::StatusCode;
+use reqwest::blocking::Client;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Maximum number of links checked at the same time.
+pub const MAX_CONCURRENT_CHECKS: usize = 8;
+
+/// Default per-request timeout for `bmk check`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of checking a single bookmark's URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// The server answered with a 2xx or 3xx status
+    Ok(u16),
+    /// The server answered with a 4xx or 5xx status
+    Broken(u16),
+    /// The request failed, e.g. DNS failure, refused connection or timeout
+    Unreachable(String),
+    /// Not an http(s) URL, so not checked
+    Skipped,
+}
+
+impl LinkStatus {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, LinkStatus::Ok(_) | LinkStatus::Skipped)
+    }
+}
+
+/// HTTP client used for network features, following redirects with a timeout.
+pub fn http_client(timeout: Duration) -> Result<Client> {
+    Ok(Client::builder()
+        .timeout(timeout)
+        .user_agent(concat!("bmk/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+/// Check every bookmark's URL with a HEAD request, at most
+/// [`MAX_CONCURRENT_CHECKS`] at a time. Results keep the order of `bookmarks`.
+pub fn check_links(
+    bookmarks: &[&Bookmark],
+    timeout: Duration,
+) -> Result<Vec<(String, LinkStatus)>> {
+    let client = http_client(timeout)?;
+    let results: Mutex<Vec<Option<LinkStatus>>> = Mutex::new(vec![None; bookmarks.len()]);
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..MAX_CONCURRENT_CHECKS.min(bookmarks.len()) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(bookmark) = bookmarks.get(i) else {
+                        break;
+                    };
+                    let status = check_link(&client, &bookmark.url);
+                    results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(status);
+                }
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    Ok(bookmarks
+        .iter()
+        .zip(results)
+        .map(|(bookmark, status)| {
+            let status = status.unwrap_or_else(|| LinkStatus::Unreachable("not checked".into()));
+            (bookmark.name.clone(), status)
+        })
+        .collect())
+}
+
+fn check_link(client: &Client, url: &str) -> LinkStatus {
+    if opener_for(url) != Opener::Browser {
+        return LinkStatus::Skipped;
+    }
+
+    // Some servers don't implement HEAD; retry those with GET
+    let response = match client.head(url).send() {
+        Ok(response)
+            if matches!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            ) =>
+        {
+            client.get(url).send()
+        }
+        other => other,
+    };
+
+    match response {
+        Ok(response) => {
+            let status = response.status();
+            if status.is_success() || status.is_redirection() {
+                LinkStatus::Ok(status.as_u16())
+            } else {
+                LinkStatus::Broken(status.as_u16())
+            }
+        }
+        Err(e) => LinkStatus::Unreachable(describe_error(&e)),
+    }
+}
+
+/// Fetch `url` and return its page title, following redirects.
+pub fn fetch_title(url: &str) -> Result<String> {
+    let response = http_client(DEFAULT_TIMEOUT)?
+        .get(url)
+        .send()
+        .with_context(|| format!("Failed to fetch '{}'", url))?;
+    if !response.status().is_success() {
+        bail!(
+            "Fetching '{}' returned HTTP {}",
+            url,
+            response.status().as_u16()
+        );
+    }
+    let html = response
+        .text()
+        .with_context(|| format!("Failed to read '{}'", url))?;
+    extract_title(&html).with_context(|| format!("No <title> found at '{}'", url))
+}
+
+/// Body of `url`, for `bmk open --cat`. Unless `force` is set, fails when the
+/// response is evidently not text (see [`is_text`]), rather than dumping
+/// binary data into the terminal.
+pub fn fetch_text(url: &str, force: bool) -> Result<Vec<u8>> {
+    if opener_for(url) != Opener::Browser {
+        bail!("Can't fetch '{}': only http(s) URLs can be printed", url);
+    }
+    let response = http_client(DEFAULT_TIMEOUT)?
+        .get(url)
+        .send()
+        .with_context(|| format!("Failed to fetch '{}'", url))?;
+    if !response.status().is_success() {
+        bail!(
+            "Fetching '{}' returned HTTP {}",
+            url,
+            response.status().as_u16()
+        );
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = response
+        .bytes()
+        .with_context(|| format!("Failed to read '{}'", url))?;
+    if !force && !is_text(content_type.as_deref(), &body) {
+        bail!(
+            "'{}' is not text ({}); use --force to print it anyway",
+            url,
+            content_type.as_deref().unwrap_or("binary data")
+        );
+    }
+    Ok(body.to_vec())
+}
+
+/// Whether a response is text worth printing: by its `Content-Type` when the
+/// server sends one, and otherwise by the body having no NUL bytes.
+pub fn is_text(content_type: Option<&str>, body: &[u8]) -> bool {
+    let Some(content_type) = content_type else {
+        return !body.contains(&0);
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let Some((kind, subtype)) = mime.split_once('/') else {
+        return !body.contains(&0);
+    };
+    kind == "text"
+        || subtype.ends_with("+json")
+        || subtype.ends_with("+xml")
+        || (kind == "application"
+            && matches!(
+                subtype,
+                "json"
+                    | "xml"
+                    | "javascript"
+                    | "ecmascript"
+                    | "yaml"
+                    | "x-yaml"
+                    | "toml"
+                    | "x-sh"
+                    | "x-www-form-urlencoded"
+            ))
+}
+
+/// Text of the first `<title>` element, with entities decoded and whitespace collapsed.
+pub fn extract_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = decode_entities(&html[start..end]);
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+fn describe_error(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        "timed out".to_string()
+    } else if error.is_connect() {
+        "connection failed".to_string()
+    } else {
+        error.to_string()
+    }
+}
@@ -1,2 +1,12 @@
 pub mod bookmarks;
+pub mod cli;
+pub mod config;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod fuzzy;
+#[cfg(feature = "network")]
+pub mod health;
+pub mod output;
+pub mod store;
+pub mod theme;
 pub mod tui;
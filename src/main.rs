@@ -1,26 +1,327 @@
 use anyhow::Result;
-use bmk::bookmarks::{load_bookmarks, open_bookmark};
-use bmk::tui::{find_best_match, run_tui_and_open};
-use std::env;
+use bmk::bookmarks::{
+    AddOptions, Bookmark, EXIT_NO_MATCH, EditOptions, ListOptions, OpenMode, OpenOptions, TagMatch,
+    confirm_ambiguous_match, debug_match, editor_command, exit_code, handle_add_command,
+    handle_copy_command, handle_delete_by_tag_command, handle_delete_command, handle_edit_command,
+    handle_edit_file_command, handle_export_command, handle_import_command, handle_list_command,
+    handle_merge_command, handle_open_all_command, handle_open_command, handle_prune_command,
+    handle_recent_command, handle_rename_command, handle_restore_command, handle_search_command,
+    handle_stats_command, handle_tag_rename_command, handle_tags_command, handle_validate_command,
+    launch_bookmark, load_bookmarks_or_recover, load_piped_bookmarks, record_open,
+    resolve_launch_args, save_visits_to,
+};
+use bmk::cli::{Cli, Commands, TagsCommand, generate_completions};
+use bmk::config::load_config;
+use bmk::fuzzy::find_confident_match;
+use bmk::output::{Verbosity, debug, set_verbosity};
+use bmk::tui::{Selection, is_dumb_terminal, run_tui_and_open};
+use clap::Parser;
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+fn main() -> ExitCode {
+    let cli = Cli::try_parse().unwrap_or_else(|err| {
+        // clap exits with 2 on bad arguments, which is reserved for "no match"
+        if err.use_stderr() {
+            let _ = err.print();
+            std::process::exit(1);
+        }
+        err.exit()
+    });
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
 
-    // If a query argument is provided, try to open the best matching bookmark directly
-    if args.len() > 1 {
-        let query = args[1..].join(" ");
-        let bookmarks = load_bookmarks()?;
+fn run(cli: Cli) -> Result<()> {
+    set_verbosity(Verbosity::from_flags(cli.quiet, cli.verbose));
+    #[cfg(feature = "skim")]
+    bmk::fuzzy::set_matcher(cli.matcher);
+    let config = load_config()?;
+    let path = cli.bookmarks_path(&config)?;
+    debug(
+        Verbosity::Verbose,
+        format!("Bookmarks file '{}'", path.display()),
+    );
+    #[cfg(feature = "skim")]
+    debug(Verbosity::Verbose, format!("Matcher {:?}", cli.matcher));
+    let browser = cli.browser.clone();
 
-        if let Some(url) = find_best_match(&bookmarks, &query) {
-            open_bookmark(&url)?;
-        } else {
-            eprintln!("No bookmark found matching: {}", query);
-            std::process::exit(1);
+    match cli.command {
+        Some(Commands::Add {
+            name,
+            url,
+            desc,
+            tags,
+            tag,
+            notes,
+            icon,
+            aliases,
+            force,
+            no_validate,
+            #[cfg(feature = "network")]
+            fetch_title,
+        }) => {
+            let bookmark = Bookmark {
+                name,
+                url: url.unwrap_or_default(),
+                desc,
+                tags: tags.into_iter().chain(tag).collect(),
+                notes,
+                icon,
+                aliases,
+                ..Default::default()
+            };
+            let options = AddOptions {
+                allow_duplicate_url: force,
+                skip_url_validation: no_validate,
+                ..Default::default()
+            };
+            #[cfg(feature = "network")]
+            let options = AddOptions {
+                fetch_title,
+                ..options
+            };
+            handle_add_command(&path, bookmark, options)?
+        }
+        #[cfg(feature = "network")]
+        Some(Commands::Check { tag, timeout }) => bmk::bookmarks::handle_check_command(
+            &path,
+            tag.as_deref(),
+            std::time::Duration::from_secs(timeout),
+        )?,
+        Some(Commands::Completions { shell }) => {
+            generate_completions(shell, &mut std::io::stdout())?
+        }
+        Some(Commands::Copy { name, print }) => handle_copy_command(&path, &name, print)?,
+        Some(Commands::Delete { name, tag, yes }) => match (name, tag) {
+            (_, Some(tag)) => {
+                handle_delete_by_tag_command(&path, &tag, yes || !config.confirm_delete)?
+            }
+            (Some(name), None) => handle_delete_command(&path, &name)?,
+            (None, None) => unreachable!("clap requires a name or --tag"),
+        },
+        #[cfg(feature = "encryption")]
+        Some(Commands::Decrypt { output }) => {
+            bmk::bookmarks::handle_decrypt_command(&path, output.as_deref())?
+        }
+        Some(Commands::Edit {
+            name,
+            url,
+            desc,
+            tags,
+            aliases,
+            notes,
+            icon,
+        }) => handle_edit_command(
+            &path,
+            &name,
+            EditOptions {
+                url,
+                desc,
+                tags,
+                aliases,
+                notes,
+                icon,
+            },
+        )?,
+        #[cfg(feature = "encryption")]
+        Some(Commands::Encrypt { output }) => {
+            bmk::bookmarks::handle_encrypt_command(&path, output.as_deref())?
+        }
+        Some(Commands::Export {
+            format,
+            output,
+            names,
+            tag,
+        }) => handle_export_command(&path, format, output.as_deref(), &names, &tag)?,
+        Some(Commands::Import {
+            format,
+            file,
+            allow_duplicates,
+        }) => handle_import_command(&path, format, &file, allow_duplicates)?,
+        Some(Commands::List {
+            names,
+            json,
+            tag,
+            all_tags: _,
+            any_tags,
+            limit,
+            offset,
+            sort,
+            tree,
+            count,
+        }) => {
+            let options = ListOptions {
+                names_only: names,
+                json,
+                tags: tag,
+                tag_match: if any_tags {
+                    TagMatch::Any
+                } else {
+                    TagMatch::All
+                },
+                limit,
+                offset,
+                sort: sort.or(config.default_sort).unwrap_or_default(),
+                tree,
+                count,
+            };
+            handle_list_command(&path, options)?
+        }
+        Some(Commands::Merge {
+            file,
+            strategy,
+            allow_duplicates,
+        }) => handle_merge_command(&path, &file, strategy, allow_duplicates)?,
+        Some(Commands::Open {
+            name,
+            all: _,
+            tag,
+            dry_run,
+            browser,
+            private,
+            #[cfg(feature = "network")]
+            cat,
+            #[cfg(feature = "network")]
+            force,
+        }) => {
+            let options = OpenOptions {
+                browser: browser.or(cli.browser),
+                default_browser: config.browser,
+                private: private || cli.private,
+                mode: if dry_run {
+                    OpenMode::Print
+                } else {
+                    OpenMode::Launch
+                },
+                min_score: Some(cli.min_score),
+                ..Default::default()
+            };
+            #[cfg(feature = "network")]
+            let options = OpenOptions {
+                mode: if cat {
+                    OpenMode::Cat { force }
+                } else {
+                    options.mode
+                },
+                ..options
+            };
+            match tag {
+                Some(tag) => handle_open_all_command(&path, &tag, &options)?,
+                None => handle_open_command(&path, &name.join(" "), &options)?,
+            }
+        }
+        Some(Commands::Rename { old, new }) => handle_rename_command(&path, &old, &new)?,
+        Some(Commands::EditFile) => handle_edit_file_command(&path, &editor_command())?,
+        Some(Commands::Prune {
+            unused: _,
+            days,
+            dry_run,
+            yes,
+        }) => handle_prune_command(&path, days, dry_run, yes || !config.confirm_delete)?,
+        Some(Commands::Recent { limit }) => handle_recent_command(&path, limit)?,
+        Some(Commands::Restore { number }) => handle_restore_command(&path, number)?,
+        Some(Commands::Search { query, limit }) => {
+            handle_search_command(&path, &query.join(" "), limit)?
+        }
+        Some(Commands::Stats) => handle_stats_command(&path)?,
+        Some(Commands::Tags { action }) => match action {
+            Some(TagsCommand::Rename { from, to }) => handle_tag_rename_command(&path, &from, &to)?,
+            None => handle_tags_command(&path)?,
+        },
+        Some(Commands::Validate { file }) => {
+            handle_validate_command(file.as_deref().unwrap_or(&path))?
+        }
+        // If a query argument is provided, try to open the best matching bookmark directly
+        None if !cli.query.is_empty() => {
+            let mut bookmarks = if cli.stdin {
+                load_piped_bookmarks(std::io::stdin().lock())?
+            } else {
+                load_bookmarks_or_recover(&path)?
+            };
+
+            if let Some((index, query)) = resolve_launch_args(&bookmarks, &cli.query, cli.min_score)
+            {
+                // Template and base URL queries picked their bookmark by the first
+                // word, so only plain queries can tie
+                let index = if cli.first || query.is_some() {
+                    index
+                } else {
+                    confirm_ambiguous_match(&bookmarks, &cli.query.join(" "), index)?
+                };
+                // A template or base URL bookmark was picked by the first word alone
+                let matched = match query {
+                    Some(_) => cli.query[0].clone(),
+                    None => cli.query.join(" "),
+                };
+                debug_match(&bookmarks, &matched, index);
+                let mode = if cli.dry_run {
+                    OpenMode::Print
+                } else {
+                    // Piped bookmarks have no file to record the visit in
+                    if !cli.stdin {
+                        record_open(&mut bookmarks, index);
+                        save_visits_to(&path, &bookmarks)?;
+                    }
+                    OpenMode::Launch
+                };
+                #[cfg(feature = "network")]
+                let mode = if cli.cat {
+                    OpenMode::Cat { force: cli.force }
+                } else {
+                    mode
+                };
+                let options = OpenOptions {
+                    browser,
+                    default_browser: config.browser,
+                    private: cli.private,
+                    mode,
+                    query,
+                    ..Default::default()
+                };
+                launch_bookmark(&bookmarks[index], &options)?;
+            } else {
+                let query = cli.query.join(" ");
+                match find_confident_match(&bookmarks, &query, 0) {
+                    Some(index) => eprintln!(
+                        "No confident match for: {} (closest is '{}'; lower --min-score to open it)",
+                        query, bookmarks[index].name
+                    ),
+                    None => eprintln!("No bookmark found matching: {}", query),
+                }
+                std::process::exit(EXIT_NO_MATCH.into());
+            }
         }
-    } else {
         // No arguments: launch the TUI
-        if let Some(url) = run_tui_and_open()? {
-            open_bookmark(&url)?;
+        None => {
+            let open_options = OpenOptions {
+                browser: browser.clone(),
+                default_browser: config.browser.clone(),
+                private: cli.private,
+                ..Default::default()
+            };
+            let plain = cli.plain || is_dumb_terminal();
+            if let Some(Selection { bookmark, private }) = run_tui_and_open(
+                &path,
+                cli.theme,
+                &config,
+                open_options,
+                cli.resume,
+                plain,
+                cli.search,
+            )? {
+                let options = OpenOptions {
+                    browser,
+                    default_browser: config.browser.clone(),
+                    private: cli.private || private,
+                    ..Default::default()
+                };
+                launch_bookmark(&bookmark, &options)?;
+            }
         }
     }
 
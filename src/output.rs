@@ -0,0 +1,68 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much `bmk` prints besides the output asked for, like lists and URLs.
+/// Warnings and errors are printed at every level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// No confirmations like "Added 'gh'", for scripts (`--quiet`)
+    Quiet,
+    #[default]
+    Normal,
+    /// Also which files are used and why a bookmark matched (`-v`)
+    Verbose,
+    /// Also the runner-up matches with their scores (`-vv`)
+    VeryVerbose,
+}
+
+impl Verbosity {
+    /// Level for the `--quiet` flag and the number of `-v` flags.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::VeryVerbose,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Set the level for the rest of the process. Called once from `main` with
+/// the `--quiet` and `-v` flags.
+pub fn set_verbosity(level: Verbosity) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// The level chosen with [`set_verbosity`], [`Verbosity::Normal`] until then.
+pub fn verbosity() -> Verbosity {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        2 => Verbosity::Verbose,
+        _ => Verbosity::VeryVerbose,
+    }
+}
+
+/// Print a confirmation to stdout, unless `--quiet` was given.
+pub fn info(message: impl Display) {
+    if verbosity() > Verbosity::Quiet {
+        println!("{}", message);
+    }
+}
+
+/// Print a note to stderr, where it can't mix with output like `--print`'s
+/// URLs, unless `--quiet` was given.
+pub fn note(message: impl Display) {
+    if verbosity() > Verbosity::Quiet {
+        eprintln!("{}", message);
+    }
+}
+
+/// Print detail to stderr when at least `level` was asked for with `-v`.
+pub fn debug(level: Verbosity, message: impl Display) {
+    if verbosity() >= level {
+        eprintln!("bmk: {}", message);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::bookmarks::{
+    Bookmark, BookmarkError, Bookmarks, add_bookmark, delete_bookmark, get_all_tags,
+    load_and_upgrade, record_open, resolve_name, save_bookmarks_to, save_visits_to,
+    update_bookmark,
+};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// A bookmarks file loaded into memory, with the operations the CLI and TUI
+/// perform on it. Changes stay in memory until [`BookmarkStore::save`],
+/// unless autosave is on (see [`BookmarkStore::with_autosave`]).
+///
+/// Bookmarks are looked up the way the commands do (see [`resolve_name`]).
+#[derive(Debug, Clone)]
+pub struct BookmarkStore {
+    path: PathBuf,
+    bookmarks: Bookmarks,
+    autosave: bool,
+}
+
+impl BookmarkStore {
+    /// Load the bookmarks at `path`; a missing file is an empty store.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let bookmarks = load_and_upgrade(&path)?;
+        Ok(Self::new(path, bookmarks))
+    }
+
+    /// A store for `bookmarks` already loaded from `path`.
+    pub fn new(path: impl Into<PathBuf>, bookmarks: Bookmarks) -> Self {
+        Self {
+            path: path.into(),
+            bookmarks,
+            autosave: false,
+        }
+    }
+
+    /// Save after every successful change, so none can be lost by forgetting
+    /// to call [`BookmarkStore::save`]. Off by default, for callers that make
+    /// several changes and save once.
+    pub fn with_autosave(mut self, autosave: bool) -> Self {
+        self.autosave = autosave;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All bookmarks, in file order.
+    pub fn all(&self) -> &Bookmarks {
+        &self.bookmarks
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bookmark> {
+        resolve_name(&self.bookmarks, name).map(|index| &self.bookmarks[index])
+    }
+
+    /// Add a bookmark, validating it like [`add_bookmark`].
+    pub fn add(&mut self, bookmark: Bookmark) -> Result<()> {
+        self.modify(|bookmarks| Ok(add_bookmark(bookmarks, bookmark)?))
+    }
+
+    /// Replace the bookmark called `name`, validating it like [`update_bookmark`].
+    pub fn update(&mut self, name: &str, bookmark: Bookmark) -> Result<()> {
+        let index = self.index_of(name)?;
+        self.modify(|bookmarks| Ok(update_bookmark(bookmarks, index, bookmark)?))
+    }
+
+    /// Remove the bookmark called `name` and return it.
+    pub fn delete(&mut self, name: &str) -> Result<Bookmark> {
+        let index = self.index_of(name)?;
+        self.modify(|bookmarks| {
+            let bookmark = bookmarks[index].clone();
+            delete_bookmark(bookmarks, index);
+            Ok(bookmark)
+        })
+    }
+
+    /// Make any other change to the bookmarks, such as several at once. With
+    /// autosave on, the result is saved unless `change` fails. If either
+    /// fails, the bookmarks are left as they were.
+    pub fn modify<T>(&mut self, change: impl FnOnce(&mut Bookmarks) -> Result<T>) -> Result<T> {
+        self.modify_with(change, save_bookmarks_to)
+    }
+
+    /// Count a visit to the bookmark at `index`. With autosave on, it is saved
+    /// without a backup (see [`save_visits_to`]).
+    pub fn record_open(&mut self, index: usize) -> Result<()> {
+        self.modify_with(
+            |bookmarks| {
+                record_open(bookmarks, index);
+                Ok(())
+            },
+            save_visits_to,
+        )
+    }
+
+    /// Replace the bookmarks with what is in the file now, e.g. after it was
+    /// edited elsewhere.
+    pub fn reload(&mut self) -> Result<()> {
+        self.bookmarks = load_and_upgrade(&self.path)?;
+        Ok(())
+    }
+
+    /// Every tag in use, sorted and deduplicated.
+    pub fn all_tags(&self) -> Vec<String> {
+        get_all_tags(&self.bookmarks)
+    }
+
+    /// Write the bookmarks back to the file they were loaded from.
+    pub fn save(&self) -> Result<()> {
+        save_bookmarks_to(&self.path, &self.bookmarks)
+    }
+
+    fn modify_with<T>(
+        &mut self,
+        change: impl FnOnce(&mut Bookmarks) -> Result<T>,
+        save: fn(&Path, &Bookmarks) -> Result<()>,
+    ) -> Result<T> {
+        let before = self.bookmarks.clone();
+        let result = change(&mut self.bookmarks).and_then(|value| {
+            if self.autosave {
+                save(&self.path, &self.bookmarks)?;
+            }
+            Ok(value)
+        });
+        if result.is_err() {
+            self.bookmarks = before;
+        }
+        result
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize, BookmarkError> {
+        resolve_name(&self.bookmarks, name).ok_or_else(|| BookmarkError::NotFound(name.to_string()))
+    }
+}
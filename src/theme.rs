@@ -0,0 +1,187 @@
+use crate::bookmarks::config_dir;
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use ratatui::style::Color;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Environment variable selecting the built-in theme when `--theme` isn't given.
+pub const THEME_ENV: &str = "BMK_THEME";
+
+/// Environment variable that, when set to anything but an empty string, turns
+/// colors off unless `--theme` is given. See <https://no-color.org>.
+pub const NO_COLOR_ENV: &str = "NO_COLOR";
+
+/// File in the config directory whose colors override the selected theme.
+pub const THEME_FILE: &str = "theme.yaml";
+
+/// Built-in color themes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    /// Catppuccin Mocha
+    #[default]
+    #[value(alias = "mocha")]
+    #[serde(alias = "mocha")]
+    Dark,
+    /// Catppuccin Latte, for light terminals
+    #[value(alias = "latte")]
+    #[serde(alias = "latte")]
+    Light,
+}
+
+/// Colors used by the TUI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub base: Color,
+    pub surface0: Color,
+    pub surface1: Color,
+    pub text: Color,
+    pub subtext0: Color,
+    pub lavender: Color,
+    pub mauve: Color,
+    pub red: Color,
+    pub green: Color,
+}
+
+/// Colors from the theme file, as `#rrggbb` strings. Missing entries keep the
+/// built-in value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeOverrides {
+    base: Option<String>,
+    surface0: Option<String>,
+    surface1: Option<String>,
+    text: Option<String>,
+    subtext0: Option<String>,
+    lavender: Option<String>,
+    mauve: Option<String>,
+    red: Option<String>,
+    green: Option<String>,
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Theme {
+                base: Color::Rgb(30, 30, 46),
+                surface0: Color::Rgb(49, 50, 68),
+                surface1: Color::Rgb(69, 71, 90),
+                text: Color::Rgb(205, 214, 244),
+                subtext0: Color::Rgb(166, 173, 200),
+                lavender: Color::Rgb(180, 190, 254),
+                mauve: Color::Rgb(203, 166, 247),
+                red: Color::Rgb(243, 139, 168),
+                green: Color::Rgb(166, 227, 161),
+            },
+            ThemeName::Light => Theme {
+                base: Color::Rgb(239, 241, 245),
+                surface0: Color::Rgb(204, 208, 218),
+                surface1: Color::Rgb(188, 192, 204),
+                text: Color::Rgb(76, 79, 105),
+                subtext0: Color::Rgb(108, 111, 133),
+                lavender: Color::Rgb(114, 135, 253),
+                mauve: Color::Rgb(136, 57, 239),
+                red: Color::Rgb(210, 15, 57),
+                green: Color::Rgb(64, 160, 43),
+            },
+        }
+    }
+
+    /// The terminal's own foreground and background everywhere. The selected
+    /// row stays visible through its marker and bold text.
+    pub fn no_color() -> Self {
+        Theme {
+            base: Color::Reset,
+            surface0: Color::Reset,
+            surface1: Color::Reset,
+            text: Color::Reset,
+            subtext0: Color::Reset,
+            lavender: Color::Reset,
+            mauve: Color::Reset,
+            red: Color::Reset,
+            green: Color::Reset,
+        }
+    }
+
+    /// Resolve the theme for the TUI: `flag` > `NO_COLOR` > `BMK_THEME` >
+    /// `configured` (from the config file) > dark, with colors from
+    /// `~/.config/bmk/theme.yaml` applied on top if that file exists and colors
+    /// are on.
+    pub fn load(flag: Option<ThemeName>, configured: Option<ThemeName>) -> Result<Self> {
+        if flag.is_none() && no_color_requested() {
+            return Ok(Theme::no_color());
+        }
+        let theme = Theme::builtin(resolve_theme_name(flag, configured)?);
+        // Without a home directory there is no theme file to apply
+        match config_dir().map(|dir| dir.join(THEME_FILE)) {
+            Ok(path) if path.exists() => theme.with_overrides(&path),
+            _ => Ok(theme),
+        }
+    }
+
+    /// Replace colors with those set in the YAML file at `path`.
+    pub fn with_overrides(mut self, path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme file: {}", path.display()))?;
+        let overrides: ThemeOverrides = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse theme file: {}", path.display()))?;
+
+        let slots = [
+            (&mut self.base, overrides.base),
+            (&mut self.surface0, overrides.surface0),
+            (&mut self.surface1, overrides.surface1),
+            (&mut self.text, overrides.text),
+            (&mut self.subtext0, overrides.subtext0),
+            (&mut self.lavender, overrides.lavender),
+            (&mut self.mauve, overrides.mauve),
+            (&mut self.red, overrides.red),
+            (&mut self.green, overrides.green),
+        ];
+        for (slot, value) in slots {
+            if let Some(value) = value {
+                *slot = parse_hex_color(&value)
+                    .with_context(|| format!("Invalid color in theme file: {}", path.display()))?;
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Whether `NO_COLOR` asks for output without colors.
+pub fn no_color_requested() -> bool {
+    env::var_os(NO_COLOR_ENV).is_some_and(|value| !value.is_empty())
+}
+
+/// Pick the theme name from the flag, falling back to `BMK_THEME`, the config
+/// file's `theme` and then the default.
+pub fn resolve_theme_name(
+    flag: Option<ThemeName>,
+    configured: Option<ThemeName>,
+) -> Result<ThemeName> {
+    if let Some(name) = flag {
+        return Ok(name);
+    }
+    match env::var(THEME_ENV) {
+        Ok(value) if !value.is_empty() => ThemeName::from_str(&value, true).map_err(|_| {
+            anyhow!(
+                "Unknown theme '{}' in {} (expected dark or light)",
+                value,
+                THEME_ENV
+            )
+        }),
+        _ => Ok(configured.unwrap_or_default()),
+    }
+}
+
+/// Parse a `#rrggbb` color.
+pub fn parse_hex_color(value: &str) -> Result<Color> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Expected a color like '#1e1e2e', got '{}'", value);
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or_default();
+    Ok(Color::Rgb(channel(0), channel(2), channel(4)))
+}
@@ -1,10 +1,20 @@
 use crate::bookmarks::{
-    Bookmark, Bookmarks, add_bookmark, delete_bookmark, get_all_tags, load_bookmarks,
-    save_bookmarks, update_bookmark,
+    Bookmark, Bookmarks, OpenOptions, TagMatch, add_bookmark, bookmark_group, choose_bookmark,
+    config_dir, delete_bookmark, delete_bookmarks, get_group_counts, get_tag_counts, icon_label,
+    icon_width, launch_bookmark, load_bookmarks_or_recover, looks_like_url, matches_tags,
+    move_bookmark, resolve_name, truncate_to_width, update_bookmark,
 };
-use anyhow::Result;
+use crate::config::Config;
+use crate::fuzzy::{SearchScope, compute_visible_items, fuzzy_match_indices, rank_candidates};
+use crate::store::BookmarkStore;
+use crate::theme::{Theme, ThemeName};
+use anyhow::{Context, Result};
+use arboard::Clipboard;
+use chrono::{DateTime, FixedOffset};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -12,26 +22,19 @@ use ratatui::{
     Frame, Terminal,
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Modifier, Style, Stylize},
+    style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
-use std::io;
-
-// Catppuccin Mocha colors
-mod colors {
-    use ratatui::style::Color;
-
-    pub const BASE: Color = Color::Rgb(30, 30, 46);
-    pub const SURFACE0: Color = Color::Rgb(49, 50, 68);
-    pub const SURFACE1: Color = Color::Rgb(69, 71, 90);
-    pub const TEXT: Color = Color::Rgb(205, 214, 244);
-    pub const SUBTEXT0: Color = Color::Rgb(166, 173, 200);
-    pub const LAVENDER: Color = Color::Rgb(180, 190, 254);
-    pub const MAUVE: Color = Color::Rgb(203, 166, 247);
-    pub const RED: Color = Color::Rgb(243, 139, 168);
-    pub const GREEN: Color = Color::Rgb(166, 227, 161);
-}
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use unicode_width::UnicodeWidthStr;
+use url::Url;
 
 #[derive(PartialEq, Clone)]
 enum Mode {
@@ -41,105 +44,271 @@ enum Mode {
     Edit(AddField),
     Delete,
     TagFilter,
+    GroupFilter,
+}
+
+/// How often the TUI checks the bookmarks file for changes made elsewhere.
+/// A change is picked up once the file is unchanged for one more interval.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Idle time after which type-ahead starts over with a fresh prefix.
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Time after which a confirmation like "Added 'gh'" leaves the status bar.
+/// Errors stay until the next keypress so they aren't missed.
+const STATUS_TIMEOUT: Duration = Duration::from_millis(2500);
+
+/// Characters with their own action in the list, which can't start a type-ahead.
+const NORMAL_MODE_KEYS: &str = "qjkJKgGpoy /aeDdtfcsu";
+
+/// Order of the list among equally scored bookmarks, cycled with `s`.
+#[derive(PartialEq, Clone, Copy)]
+enum SortMode {
+    /// Order of the bookmarks file
+    File,
+    Name,
+    Host,
+    /// Most visited first
+    Visits,
+    /// Most recently opened first
+    Recent,
+}
+
+impl SortMode {
+    fn next(self) -> Self {
+        match self {
+            SortMode::File => SortMode::Name,
+            SortMode::Name => SortMode::Host,
+            SortMode::Host => SortMode::Visits,
+            SortMode::Visits => SortMode::Recent,
+            SortMode::Recent => SortMode::File,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortMode::File => "file",
+            SortMode::Name => "name",
+            SortMode::Host => "host",
+            SortMode::Visits => "visits",
+            SortMode::Recent => "recent",
+        }
+    }
+
+    fn compare(self, a: &Bookmark, b: &Bookmark) -> Ordering {
+        match self {
+            SortMode::File => Ordering::Equal,
+            SortMode::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortMode::Host => host(&a.url)
+                .cmp(&host(&b.url))
+                .then_with(|| a.url.cmp(&b.url)),
+            SortMode::Visits => b.visits.cmp(&a.visits),
+            // Never-opened bookmarks (None) sort last
+            SortMode::Recent => last_opened(b).cmp(&last_opened(a)),
+        }
+    }
+}
+
+fn host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            u.host_str()
+                .map(|h| h.trim_start_matches("www.").to_lowercase())
+        })
+        .unwrap_or_default()
+}
+
+fn last_opened(bookmark: &Bookmark) -> Option<DateTime<FixedOffset>> {
+    bookmark
+        .last_opened
+        .as_deref()
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+}
+
+/// The bookmark chosen in the TUI and how to open it.
+#[derive(Debug)]
+pub struct Selection {
+    pub bookmark: Bookmark,
+    /// Open in a private/incognito window
+    pub private: bool,
+}
+
+/// A one-line message shown in place of the help bar until the next keypress,
+/// or until [`STATUS_TIMEOUT`] passes for anything but an error.
+struct Status {
+    text: String,
+    is_error: bool,
+    shown_at: Instant,
 }
 
 #[derive(PartialEq, Clone)]
 enum AddField {
     Name,
+    Icon,
     Url,
     Desc,
+    Notes,
     Tags,
 }
 
-struct App {
+/// Bookmarks as they were before the last change, restored with `u`.
+struct Undo {
     bookmarks: Bookmarks,
+    /// What the change was, e.g. "delete of 'GitHub'"
+    action: String,
+}
+
+struct App {
+    theme: Theme,
+    // Browser and window mode for bookmarks opened without quitting
+    open_options: OpenOptions,
+    // Saves on every change
+    store: BookmarkStore,
     filtered_indices: Vec<usize>,
+    // Query `filtered_indices` was computed for, so typing can narrow them
+    filtered_query: String,
     list_state: ListState,
     mode: Mode,
     search_query: String,
-    tag_filter: Option<String>,
+    search_scope: SearchScope,
+    // Tags selected in the tag filter, and how they combine
+    tag_filter: BTreeSet<String>,
+    tag_match: TagMatch,
+    // Tags toggled in the tag dialog, applied on Enter
+    tag_selection: BTreeSet<String>,
     tag_list_state: ListState,
+    // Group (the part of `group/name` names before the slash) the list is
+    // narrowed to, and the position in the group dialog
+    group_filter: Option<String>,
+    group_list_state: ListState,
+    sort_mode: SortMode,
+    // Ask before deleting; off with `confirm_delete: false` in the config file
+    confirm_delete: bool,
     // Form fields for add/edit
     form_name: String,
     form_url: String,
+    form_icon: String,
     form_desc: String,
+    form_notes: String,
     form_tags: String,
+    form_error: Option<String>,
     edit_index: Option<usize>,
+    // Positions in the file of bookmarks marked for bulk actions
+    marked: BTreeSet<usize>,
+    // Modification time of the file when last loaded or saved, and a newer one
+    // seen at the previous check, to notice edits made outside the TUI
+    file_modified: Option<SystemTime>,
+    pending_modified: Option<SystemTime>,
+    status: Option<Status>,
+    undo: Option<Undo>,
+    // First `g` of a `gg` jump seen
+    pending_g: bool,
+    // Name prefix typed in the list to jump to, and when it was last extended
+    type_ahead: String,
+    type_ahead_at: Option<Instant>,
+    // Kept alive for the whole session so copied text stays available
+    clipboard: Option<Clipboard>,
     should_quit: bool,
-    url_to_open: Option<String>,
+    to_open: Option<Selection>,
 }
 
 impl App {
-    fn new(bookmarks: Bookmarks) -> Self {
-        let filtered_indices: Vec<usize> = (0..bookmarks.len()).collect();
+    fn new(store: BookmarkStore, theme: Theme, open_options: OpenOptions) -> Self {
+        let file_modified = modified_time(store.path());
+        let filtered_indices: Vec<usize> = (0..store.all().len()).collect();
         let mut list_state = ListState::default();
         if !filtered_indices.is_empty() {
             list_state.select(Some(0));
         }
 
         Self {
-            bookmarks,
+            theme,
+            open_options,
+            store,
             filtered_indices,
+            filtered_query: String::new(),
             list_state,
             mode: Mode::Normal,
             search_query: String::new(),
-            tag_filter: None,
+            search_scope: SearchScope::Fields,
+            tag_filter: BTreeSet::new(),
+            tag_match: TagMatch::All,
+            tag_selection: BTreeSet::new(),
             tag_list_state: ListState::default(),
+            group_filter: None,
+            group_list_state: ListState::default(),
+            sort_mode: SortMode::File,
+            confirm_delete: true,
             form_name: String::new(),
             form_url: String::new(),
+            form_icon: String::new(),
             form_desc: String::new(),
+            form_notes: String::new(),
             form_tags: String::new(),
+            form_error: None,
             edit_index: None,
+            marked: BTreeSet::new(),
+            file_modified,
+            pending_modified: None,
+            status: None,
+            undo: None,
+            pending_g: false,
+            type_ahead: String::new(),
+            type_ahead_at: None,
+            clipboard: None,
             should_quit: false,
-            url_to_open: None,
+            to_open: None,
         }
     }
 
     fn update_filter(&mut self) {
-        let query = self.search_query.to_lowercase();
-        let query_chars: Vec<char> = query.chars().collect();
-
-        self.filtered_indices = self
-            .bookmarks
-            .iter()
-            .enumerate()
-            .filter_map(|(i, bm)| {
-                // Tag filter
-                if let Some(ref tag) = self.tag_filter
-                    && !bm.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
-                {
-                    return None;
-                }
-
-                // Fuzzy search
-                if query.is_empty() {
-                    return Some((i, 0i64));
-                }
+        let tags: Vec<&String> = self.tag_filter.iter().collect();
+        let group = self.group_filter.as_deref();
+        // The sort mode orders ties, which is every entry without a query
+        self.filtered_indices = compute_visible_items(
+            self.store.all(),
+            &self.search_query,
+            self.search_scope,
+            |bm| {
+                matches_tags(bm, &tags, self.tag_match)
+                    && group.is_none_or(|g| bookmark_group(&bm.name) == Some(g))
+            },
+            |a, b| self.sort_mode.compare(a, b),
+        )
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect();
+        self.filtered_query = self.search_query.clone();
+        self.reset_selection();
+    }
 
-                let score = fuzzy_score(&query_chars, bm);
-                if score >= 0 { Some((i, score)) } else { None }
-            })
-            .collect::<Vec<_>>()
-            .into_iter()
-            .collect::<Vec<_>>()
-            .into_iter()
-            .fold(Vec::new(), |mut acc, (i, score)| {
-                acc.push((i, score));
-                acc
-            })
-            .into_iter()
-            .collect::<Vec<_>>()
-            .into_iter()
-            .fold(Vec::new(), |mut acc, x| {
-                acc.push(x);
-                acc.sort_by(|a, b| b.1.cmp(&a.1));
-                acc
-            })
-            .into_iter()
-            .map(|(i, _)| i)
-            .collect();
+    /// Like `update_filter` after a character is typed, but only rescores the
+    /// bookmarks that matched the query before it, since nothing else can
+    /// match the longer one
+    fn narrow_filter(&mut self) {
+        if !self.search_query.starts_with(&self.filtered_query) {
+            self.update_filter();
+            return;
+        }
+        // Back in file order, so ties sort the same as in `update_filter`
+        let mut candidates = std::mem::take(&mut self.filtered_indices);
+        candidates.sort_unstable();
+        self.filtered_indices = rank_candidates(
+            self.store.all(),
+            candidates,
+            &self.search_query,
+            self.search_scope,
+            |a, b| self.sort_mode.compare(a, b),
+        )
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect();
+        self.filtered_query = self.search_query.clone();
+        self.reset_selection();
+    }
 
-        // Reset selection
+    fn reset_selection(&mut self) {
         if self.filtered_indices.is_empty() {
             self.list_state.select(None);
         } else {
@@ -151,7 +320,7 @@ impl App {
         self.list_state
             .selected()
             .and_then(|i| self.filtered_indices.get(i))
-            .and_then(|&idx| self.bookmarks.get(idx))
+            .and_then(|&idx| self.store.all().get(idx))
     }
 
     fn selected_index(&self) -> Option<usize> {
@@ -188,16 +357,42 @@ impl App {
         self.list_state.select(Some(i));
     }
 
+    fn first(&mut self) {
+        if !self.filtered_indices.is_empty() {
+            self.list_state.select(Some(0));
+        }
+    }
+
+    fn last(&mut self) {
+        if !self.filtered_indices.is_empty() {
+            self.list_state
+                .select(Some(self.filtered_indices.len() - 1));
+        }
+    }
+
     fn clear_form(&mut self) {
         self.form_name.clear();
         self.form_url.clear();
+        self.form_icon.clear();
         self.form_desc.clear();
+        self.form_notes.clear();
         self.form_tags.clear();
+        self.form_error = None;
         self.edit_index = None;
     }
 
+    /// Open the add form, with the URL filled in from the clipboard when it
+    /// holds one.
     fn start_add(&mut self) {
         self.clear_form();
+        if let Some(url) = self
+            .clipboard()
+            .ok()
+            .and_then(|clipboard| clipboard.get_text().ok())
+            .filter(|text| looks_like_url(text))
+        {
+            self.form_url = url.trim().to_string();
+        }
         self.mode = Mode::Add(AddField::Name);
     }
 
@@ -206,12 +401,27 @@ impl App {
             self.edit_index = self.selected_index();
             self.form_name = bm.name;
             self.form_url = bm.url;
+            self.form_icon = bm.icon;
             self.form_desc = bm.desc;
+            self.form_notes = bm.notes;
             self.form_tags = bm.tags.join(", ");
             self.mode = Mode::Edit(AddField::Name);
         }
     }
 
+    /// Open the add form filled in from the selected bookmark, all but the
+    /// name, to add a similar one.
+    fn start_duplicate(&mut self) {
+        if let Some(bm) = self.selected_bookmark().cloned() {
+            self.clear_form();
+            self.form_url = bm.url;
+            self.form_icon = bm.icon;
+            self.form_desc = bm.desc;
+            self.form_tags = bm.tags.join(", ");
+            self.mode = Mode::Add(AddField::Name);
+        }
+    }
+
     fn save_bookmark(&mut self) {
         let tags: Vec<String> = self
             .form_tags
@@ -220,133 +430,470 @@ impl App {
             .filter(|s| !s.is_empty())
             .collect();
 
-        let bookmark = Bookmark {
-            name: self.form_name.clone(),
-            url: self.form_url.clone(),
-            desc: self.form_desc.clone(),
-            tags,
+        // Start from the existing entry when editing so usage stats are kept
+        let mut bookmark = self
+            .edit_index
+            .and_then(|idx| self.store.all().get(idx).cloned())
+            .unwrap_or_default();
+        bookmark.name = self.form_name.clone();
+        bookmark.url = self.form_url.clone();
+        bookmark.icon = self.form_icon.trim().to_string();
+        bookmark.desc = self.form_desc.clone();
+        bookmark.notes = self.form_notes.trim_end().to_string();
+        bookmark.tags = tags;
+
+        let before = self.store.all().clone();
+        let name = bookmark.name.clone();
+        let (result, action, done) = if let Some(idx) = self.edit_index {
+            let action = format!("edit of '{}'", before[idx].name);
+            (
+                self.change(|bookmarks| Ok(update_bookmark(bookmarks, idx, bookmark)?)),
+                action,
+                format!("Updated '{}'", name),
+            )
+        } else {
+            let action = format!("add of '{}'", bookmark.name);
+            (
+                self.change(|bookmarks| Ok(add_bookmark(bookmarks, bookmark)?)),
+                action,
+                format!("Added '{}'", name),
+            )
         };
 
-        if let Some(idx) = self.edit_index {
-            update_bookmark(&mut self.bookmarks, idx, bookmark);
-        } else {
-            add_bookmark(&mut self.bookmarks, bookmark);
+        // Keep the form open so the user can fix the input
+        if let Err(e) = result {
+            self.form_error = Some(e.to_string());
+            return;
         }
 
-        let _ = save_bookmarks(&self.bookmarks);
+        self.remember(before, action);
         self.clear_form();
         self.mode = Mode::Normal;
         self.update_filter();
+        self.select_name(&name);
+        self.confirm(done);
     }
 
+    fn toggle_mark(&mut self) {
+        if let Some(idx) = self.selected_index()
+            && !self.marked.remove(&idx)
+        {
+            self.marked.insert(idx);
+        }
+    }
+
+    /// Delete the marked bookmarks, or the selected one if none are marked.
     fn delete_selected(&mut self) {
-        if let Some(idx) = self.selected_index() {
-            delete_bookmark(&mut self.bookmarks, idx);
-            let _ = save_bookmarks(&self.bookmarks);
-            self.update_filter();
+        let before = self.store.all().clone();
+        if !self.marked.is_empty() {
+            let marked = self.marked.clone();
+            let action = format!("delete of {} bookmarks", marked.len());
+            let done = format!("Deleted {} bookmarks", marked.len());
+            if self.apply(|bookmarks| {
+                delete_bookmarks(bookmarks, &marked);
+            }) {
+                self.marked.clear();
+                self.remember(before, action);
+                self.update_filter();
+                self.confirm(done);
+            }
+        } else if let Some(idx) = self.selected_index() {
+            let action = format!("delete of '{}'", before[idx].name);
+            let done = format!("Deleted '{}'", before[idx].name);
+            if self.apply(|bookmarks| delete_bookmark(bookmarks, idx)) {
+                self.remember(before, action);
+                self.update_filter();
+                self.confirm(done);
+            }
         }
         self.mode = Mode::Normal;
     }
 
-    fn open_selected(&mut self) {
-        if let Some(bm) = self.selected_bookmark() {
-            self.url_to_open = Some(bm.url.clone());
-            self.should_quit = true;
+    /// Move the selected bookmark one place up (`-1`) or down (`1`) in the file.
+    /// Only possible while the list shows the whole file in file order.
+    fn move_selected(&mut self, offset: isize) {
+        if !self.search_query.is_empty()
+            || !self.tag_filter.is_empty()
+            || self.group_filter.is_some()
+            || self.sort_mode != SortMode::File
+        {
+            self.set_status(
+                "Clear the search, filters and sort order to reorder".to_string(),
+                true,
+            );
+            return;
         }
+        let Some(from) = self.selected_index() else {
+            return;
+        };
+        let Some(to) = from
+            .checked_add_signed(offset)
+            .filter(|&to| to < self.store.all().len())
+        else {
+            return;
+        };
+
+        let action = format!("move of '{}'", self.store.all()[from].name);
+        let before = self.store.all().clone();
+        if !self.apply(|bookmarks| move_bookmark(bookmarks, from, to)) {
+            return;
+        }
+        self.remember(before, action);
+        // Marks follow the two bookmarks that swapped places
+        let (from_marked, to_marked) = (self.marked.remove(&from), self.marked.remove(&to));
+        if from_marked {
+            self.marked.insert(to);
+        }
+        if to_marked {
+            self.marked.insert(from);
+        }
+        self.update_filter();
+        self.list_state.select(Some(to));
     }
-}
 
-pub fn fuzzy_score(pattern: &[char], bookmark: &Bookmark) -> i64 {
-    let name_score = fuzzy_match(pattern, &bookmark.name.to_lowercase());
-    let url_score = fuzzy_match(pattern, &bookmark.url.to_lowercase());
-    let desc_score = fuzzy_match(pattern, &bookmark.desc.to_lowercase());
-    let tag_score = bookmark
-        .tags
-        .iter()
-        .map(|t| fuzzy_match(pattern, &t.to_lowercase()))
-        .max()
-        .unwrap_or(-1);
-
-    if name_score >= 0 {
-        name_score + 1000
-    } else if url_score >= 0 {
-        url_score + 500
-    } else if desc_score >= 0 {
-        desc_score + 100
-    } else if tag_score >= 0 {
-        tag_score
-    } else {
-        -1
+    fn marked_names(&self) -> HashSet<String> {
+        self.marked
+            .iter()
+            .filter_map(|&idx| self.store.all().get(idx))
+            .map(|bm| bm.name.clone())
+            .collect()
     }
-}
 
-pub fn fuzzy_match(pattern: &[char], text: &str) -> i64 {
-    if pattern.is_empty() {
-        return 0;
+    /// Mark the bookmarks named in `names`, for when the whole list was
+    /// replaced and the marked positions no longer mean anything.
+    fn mark_names(&mut self, names: &HashSet<String>) {
+        self.marked = self
+            .store
+            .all()
+            .iter()
+            .enumerate()
+            .filter(|(_, bm)| names.contains(&bm.name))
+            .map(|(i, _)| i)
+            .collect();
     }
 
-    let text_chars: Vec<char> = text.chars().collect();
-    let mut pattern_idx = 0;
-    let mut score: i64 = 0;
-    let mut last_match: Option<usize> = None;
-    let mut consecutive = 0i64;
+    /// Keep `before` so the change described by `action` can be undone.
+    /// Only the most recent change is kept.
+    fn remember(&mut self, before: Bookmarks, action: String) {
+        self.undo = Some(Undo {
+            bookmarks: before,
+            action,
+        });
+    }
 
-    for (i, &c) in text_chars.iter().enumerate() {
-        if pattern_idx < pattern.len() && c == pattern[pattern_idx] {
-            if let Some(last) = last_match {
-                if i == last + 1 {
-                    consecutive += 10;
-                } else {
-                    consecutive = 0;
+    fn undo(&mut self) {
+        let Some(undo) = self.undo.take() else {
+            self.set_status("Nothing to undo".to_string(), true);
+            return;
+        };
+
+        let marked = self.marked_names();
+        if !self.apply(|bookmarks| *bookmarks = undo.bookmarks.clone()) {
+            // Still there to try again
+            self.undo = Some(undo);
+            return;
+        }
+        self.mark_names(&marked);
+        self.update_filter();
+        self.set_status(format!("Undid {}", undo.action), false);
+    }
+
+    /// Change the bookmarks through the store, which saves them, noting the
+    /// new modification time so the save isn't taken for an outside edit.
+    fn change<T>(&mut self, change: impl FnOnce(&mut Bookmarks) -> Result<T>) -> Result<T> {
+        self.with_store(|store| store.modify(change))
+    }
+
+    /// Count a visit to the bookmark at `idx`, reporting a failed save in
+    /// the status bar.
+    fn record_visit(&mut self, idx: usize) {
+        if let Err(e) = self.with_store(|store| store.record_open(idx)) {
+            self.set_status(format!("Failed to save bookmarks: {:#}", e), true);
+        }
+    }
+
+    fn with_store<T>(&mut self, f: impl FnOnce(&mut BookmarkStore) -> Result<T>) -> Result<T> {
+        let result = f(&mut self.store);
+        self.file_modified = modified_time(self.store.path());
+        self.pending_modified = None;
+        result
+    }
+
+    /// Like [`App::change`] for changes that can't fail, reporting a failed
+    /// save in the status bar. Returns whether the change was kept; a change
+    /// that couldn't be saved is rolled back.
+    fn apply(&mut self, change: impl FnOnce(&mut Bookmarks)) -> bool {
+        match self.change(|bookmarks| {
+            change(bookmarks);
+            Ok(())
+        }) {
+            Ok(()) => true,
+            Err(e) => {
+                self.set_status(format!("Failed to save bookmarks: {:#}", e), true);
+                false
+            }
+        }
+    }
+
+    /// Reload the bookmarks if the file was changed by someone else and has
+    /// stopped changing since the last check. Reloads wait while a form or
+    /// dialog is open, since those hold on to positions in the list.
+    fn check_reload(&mut self) {
+        let modified = modified_time(self.store.path());
+        if modified == self.file_modified {
+            self.pending_modified = None;
+            return;
+        }
+        if modified != self.pending_modified {
+            self.pending_modified = modified;
+            return;
+        }
+        if !matches!(self.mode, Mode::Normal | Mode::Search) {
+            return;
+        }
+
+        // Remember the attempt even if it fails, so a broken file is reported once
+        self.file_modified = modified;
+        self.pending_modified = None;
+        let selected = self.selected_bookmark().map(|bm| bm.name.clone());
+        let marked = self.marked_names();
+        match self.store.reload() {
+            Ok(()) => {
+                // Undoing now would throw away the changes just loaded
+                self.undo = None;
+                self.mark_names(&marked);
+                self.update_filter();
+                if let Some(name) = selected {
+                    self.select_name(&name);
                 }
+                self.set_status("Reloaded bookmarks changed on disk".to_string(), false);
             }
+            Err(e) => self.set_status(format!("Failed to reload bookmarks: {:#}", e), true),
+        }
+    }
 
-            let boundary_bonus = if i == 0
-                || text_chars
-                    .get(i.wrapping_sub(1))
-                    .is_some_and(|&c| matches!(c, '/' | '.' | '-' | '_' | ' '))
-            {
-                20
-            } else {
-                0
-            };
+    /// Extend the type-ahead prefix with `c` and select the first listed
+    /// bookmark whose name starts with it. A prefix can only start with a
+    /// character that has no action of its own; once started, every character
+    /// extends it until [`TYPE_AHEAD_TIMEOUT`] passes without typing.
+    /// Returns whether `c` was taken.
+    fn type_ahead(&mut self, c: char) -> bool {
+        let active = self
+            .type_ahead_at
+            .is_some_and(|at| at.elapsed() < TYPE_AHEAD_TIMEOUT);
+        if !active {
+            self.type_ahead.clear();
+            if NORMAL_MODE_KEYS.contains(c) {
+                self.type_ahead_at = None;
+                return false;
+            }
+        }
+        self.type_ahead.push(c);
+        self.type_ahead_at = Some(Instant::now());
 
-            let position_bonus = 10 - (i.min(10) as i64);
-            score += 10 + consecutive + boundary_bonus + position_bonus;
-            last_match = Some(i);
-            pattern_idx += 1;
+        let prefix = self.type_ahead.to_lowercase();
+        let found = self
+            .filtered_indices
+            .iter()
+            .position(|&i| self.store.all()[i].name.to_lowercase().starts_with(&prefix));
+        match found {
+            Some(pos) => {
+                self.list_state.select(Some(pos));
+                self.set_status(format!("Jump to: {}", self.type_ahead), false);
+            }
+            None => self.set_status(
+                format!("No bookmark starts with '{}'", self.type_ahead),
+                true,
+            ),
         }
+        true
     }
 
-    if pattern_idx == pattern.len() {
-        score
-    } else {
-        -1
+    /// Switch between searching the main fields by importance and searching
+    /// everything, notes included.
+    fn toggle_search_scope(&mut self) {
+        let (scope, text) = match self.search_scope {
+            SearchScope::Fields => (SearchScope::All, "Searching all fields, notes included"),
+            SearchScope::All => (
+                SearchScope::Fields,
+                "Searching names, URLs, descriptions and tags",
+            ),
+        };
+        self.search_scope = scope;
+        self.update_filter();
+        self.set_status(text.to_string(), false);
+    }
+
+    /// Select the bookmark called `name` if the list shows it.
+    fn select_name(&mut self, name: &str) {
+        if let Some(pos) = self
+            .filtered_indices
+            .iter()
+            .position(|&i| self.store.all()[i].name == name)
+        {
+            self.list_state.select(Some(pos));
+        }
     }
-}
 
-/// Find the best matching bookmark for a given query.
-/// Returns the URL of the best match if score > 0, None otherwise.
-pub fn find_best_match(bookmarks: &Bookmarks, query: &str) -> Option<String> {
-    let query = query.to_lowercase();
-    let query_chars: Vec<char> = query.chars().collect();
+    fn set_status(&mut self, text: String, is_error: bool) {
+        self.status = Some(Status {
+            text,
+            is_error,
+            shown_at: Instant::now(),
+        });
+    }
 
-    bookmarks
-        .iter()
-        .filter_map(|bm| {
-            let score = fuzzy_score(&query_chars, bm);
-            if score > 0 {
-                Some((bm.url.clone(), score))
-            } else {
-                None
+    /// Confirm a change in the status bar, unless saving it failed and the
+    /// error is shown already.
+    fn confirm(&mut self, text: String) {
+        if self.status.is_none() {
+            self.set_status(text, false);
+        }
+    }
+
+    /// Clear a confirmation that has been shown for [`STATUS_TIMEOUT`].
+    fn expire_status(&mut self) {
+        if self
+            .status
+            .as_ref()
+            .is_some_and(|status| !status.is_error && status.shown_at.elapsed() >= STATUS_TIMEOUT)
+        {
+            self.status = None;
+        }
+    }
+
+    /// The system clipboard, connected to on first use.
+    fn clipboard(&mut self) -> Result<&mut Clipboard, arboard::Error> {
+        let clipboard = match self.clipboard.take() {
+            Some(clipboard) => clipboard,
+            None => Clipboard::new()?,
+        };
+        Ok(self.clipboard.insert(clipboard))
+    }
+
+    fn copy_selected(&mut self) {
+        let Some(bm) = self.selected_bookmark().cloned() else {
+            return;
+        };
+
+        let clipboard = match self.clipboard() {
+            Ok(clipboard) => clipboard,
+            Err(e) => {
+                self.set_status(format!("Clipboard unavailable: {}", e), true);
+                return;
             }
-        })
-        .max_by_key(|(_, score)| *score)
-        .map(|(url, _)| url)
+        };
+        match clipboard.set_text(bm.url) {
+            Ok(()) => self.set_status(format!("Copied '{}' to clipboard", bm.name), false),
+            Err(e) => self.set_status(format!("Failed to copy to clipboard: {}", e), true),
+        }
+    }
+
+    /// Open the selected bookmark and stay in the TUI, so several can be
+    /// opened in a row.
+    fn open_selected_in_place(&mut self) {
+        let Some(idx) = self.selected_index() else {
+            return;
+        };
+        let bookmark = self.store.all()[idx].clone();
+        if let Err(e) = launch_bookmark(&bookmark, &self.open_options) {
+            self.set_status(format!("Failed to open '{}': {:#}", bookmark.name, e), true);
+            return;
+        }
+
+        self.record_visit(idx);
+        // The visit count and last-opened time may change the sort order
+        self.update_filter();
+        self.select_name(&bookmark.name);
+        if self.status.is_none() {
+            self.set_status(format!("Opened '{}'", bookmark.name), false);
+        }
+    }
+
+    fn open_selected(&mut self, private: bool) {
+        if let Some(idx) = self.selected_index() {
+            self.record_visit(idx);
+            self.to_open = Some(Selection {
+                bookmark: self.store.all()[idx].clone(),
+                private,
+            });
+            self.should_quit = true;
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
 }
 
-pub fn run_tui_and_open() -> Result<Option<String>> {
-    let bookmarks = load_bookmarks()?;
+/// Name of the file in the config directory that keeps TUI state between
+/// sessions, apart from the bookmarks.
+pub const STATE_FILE: &str = "state.yaml";
+
+/// TUI state kept between sessions.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TuiState {
+    /// The search query the last session ended with, if it had one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_search: Option<String>,
+}
+
+impl TuiState {
+    /// Read the state saved at `path`; a missing file is the default state.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create '{}'", parent.display()))?;
+        }
+        fs::write(path, serde_yaml::to_string(self)?)
+            .with_context(|| format!("Failed to write '{}'", path.display()))
+    }
+}
+
+/// Where [`TuiState`] is kept: [`STATE_FILE`] in the config directory.
+pub fn state_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(STATE_FILE))
+}
+
+/// Whether `TERM` says the terminal can't draw the TUI, as on `TERM=dumb`.
+pub fn is_dumb_terminal() -> bool {
+    std::env::var("TERM").is_ok_and(|term| term == "dumb")
+}
+
+/// Show the TUI and return the bookmark chosen in it, or with `plain`, ask
+/// for one with [`run_plain_picker`] on stdin instead. With `search`, the TUI
+/// starts in search mode with that query typed; otherwise `resume` restores
+/// the previous session's search.
+pub fn run_tui_and_open(
+    path: &Path,
+    theme: Option<ThemeName>,
+    config: &Config,
+    open_options: OpenOptions,
+    resume: bool,
+    plain: bool,
+    search: Option<String>,
+) -> Result<Option<Selection>> {
+    if plain {
+        return run_plain_picker(path, &mut io::stdin().lock(), &mut io::stderr());
+    }
+    let bookmarks = load_bookmarks_or_recover(path)?;
+    let theme = Theme::load(theme, config.theme)?;
+    // Saved state is a convenience, so a missing or broken state file is ignored
+    let state_path = state_path().ok();
+    let mut state = state_path
+        .as_deref()
+        .and_then(|path| TuiState::load(path).ok())
+        .unwrap_or_default();
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -354,7 +901,22 @@ pub fn run_tui_and_open() -> Result<Option<String>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(bookmarks);
+    let store = BookmarkStore::new(path, bookmarks).with_autosave(true);
+    let mut app = App::new(store, theme, open_options);
+    app.confirm_delete = config.confirm_delete;
+    if let Some(query) = search {
+        app.search_query = query;
+        app.mode = Mode::Search;
+        app.update_filter();
+        // An exact name is selected, but the other matches stay listed
+        if let Some(index) = resolve_name(app.store.all(), &app.search_query) {
+            let name = app.store.all()[index].name.clone();
+            app.select_name(&name);
+        }
+    } else if resume && let Some(query) = state.last_search.clone() {
+        app.search_query = query;
+        app.update_filter();
+    }
     let result = run_app(&mut terminal, &mut app);
 
     disable_raw_mode()?;
@@ -365,47 +927,135 @@ pub fn run_tui_and_open() -> Result<Option<String>> {
     )?;
     terminal.show_cursor()?;
 
+    if !app.search_query.is_empty()
+        && let Some(path) = state_path
+    {
+        state.last_search = Some(app.search_query.clone());
+        let _ = state.save(&path);
+    }
+
     result?;
-    Ok(app.url_to_open)
+    Ok(app.to_open)
+}
+
+/// Pick a bookmark by number from a list written to `output`, reading the
+/// answer from `input`. For scripts and terminals where the TUI doesn't work.
+/// Records the visit like opening from the TUI does.
+pub fn run_plain_picker(
+    path: &Path,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<Option<Selection>> {
+    let mut store = BookmarkStore::open(path)?.with_autosave(true);
+    if store.all().is_empty() {
+        writeln!(output, "No bookmarks. Add one with `bmk add`.")?;
+        return Ok(None);
+    }
+
+    let candidates: Vec<usize> = (0..store.all().len()).collect();
+    let index = choose_bookmark(store.all(), &candidates, input, output)?;
+    store.record_open(index)?;
+    Ok(Some(Selection {
+        bookmark: store.all()[index].clone(),
+        private: false,
+    }))
 }
 
 fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
     loop {
         terminal.draw(|f| ui(f, app))?;
 
+        if !event::poll(RELOAD_CHECK_INTERVAL)? {
+            app.expire_status();
+            app.check_reload();
+            continue;
+        }
         if let Event::Key(key) = event::read()? {
             if key.kind != KeyEventKind::Press {
                 continue;
             }
+            app.status = None;
+            let pending_g = std::mem::take(&mut app.pending_g);
+            if matches!(app.mode, Mode::Normal) {
+                if let KeyCode::Char(c) = key.code
+                    && !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && app.type_ahead(c)
+                {
+                    continue;
+                }
+                app.type_ahead_at = None;
+            }
 
             match &app.mode {
                 Mode::Normal => match key.code {
+                    KeyCode::Esc if !app.marked.is_empty() => app.marked.clear(),
                     KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
                     KeyCode::Char('j') | KeyCode::Down => app.next(),
                     KeyCode::Char('k') | KeyCode::Up => app.previous(),
-                    KeyCode::Enter => app.open_selected(),
+                    KeyCode::Char('J') => app.move_selected(1),
+                    KeyCode::Char('K') => app.move_selected(-1),
+                    KeyCode::Char('g') if pending_g => app.first(),
+                    KeyCode::Char('g') => app.pending_g = true,
+                    KeyCode::Char('G') | KeyCode::End => app.last(),
+                    KeyCode::Home => app.first(),
+                    KeyCode::Enter => app.open_selected(false),
+                    KeyCode::Char('p') => app.open_selected(true),
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.toggle_search_scope()
+                    }
+                    KeyCode::Char('o') => app.open_selected_in_place(),
+                    KeyCode::Char('y') => app.copy_selected(),
+                    KeyCode::Char(' ') => app.toggle_mark(),
                     KeyCode::Char('/') => {
                         app.mode = Mode::Search;
                         app.search_query.clear();
                     }
                     KeyCode::Char('a') => app.start_add(),
                     KeyCode::Char('e') => app.start_edit(),
-                    KeyCode::Char('d') => {
-                        if app.selected_bookmark().is_some() {
+                    KeyCode::Char('D') => app.start_duplicate(),
+                    KeyCode::Char('d')
+                        if !app.marked.is_empty() || app.selected_bookmark().is_some() =>
+                    {
+                        if app.confirm_delete {
                             app.mode = Mode::Delete;
+                        } else {
+                            app.delete_selected();
                         }
                     }
                     KeyCode::Char('t') => {
-                        let tags = get_all_tags(&app.bookmarks);
+                        let tags = app.store.all_tags();
                         if !tags.is_empty() {
+                            app.tag_selection = app.tag_filter.clone();
                             app.tag_list_state.select(Some(0));
                             app.mode = Mode::TagFilter;
                         }
                     }
+                    KeyCode::Char('f') => {
+                        let groups = get_group_counts(app.store.all());
+                        if groups.is_empty() {
+                            app.set_status(
+                                "No groups; name bookmarks like group/name to add some".to_string(),
+                                true,
+                            );
+                        } else {
+                            // Start on the current group, after the "all" entry
+                            let current = app.group_filter.as_ref().and_then(|group| {
+                                groups.iter().position(|(g, _)| g == group).map(|i| i + 1)
+                            });
+                            app.group_list_state.select(Some(current.unwrap_or(0)));
+                            app.mode = Mode::GroupFilter;
+                        }
+                    }
                     KeyCode::Char('c') => {
-                        app.tag_filter = None;
+                        app.tag_filter.clear();
+                        app.group_filter = None;
+                        app.update_filter();
+                    }
+                    KeyCode::Char('s') => {
+                        app.sort_mode = app.sort_mode.next();
                         app.update_filter();
                     }
+                    KeyCode::Char('u') => app.undo(),
                     _ => {}
                 },
                 Mode::Search => match key.code {
@@ -421,9 +1071,12 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                         app.search_query.pop();
                         app.update_filter();
                     }
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.toggle_search_scope()
+                    }
                     KeyCode::Char(c) => {
                         app.search_query.push(c);
-                        app.update_filter();
+                        app.narrow_filter();
                     }
                     KeyCode::Down => app.next(),
                     KeyCode::Up => app.previous(),
@@ -431,16 +1084,23 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                 },
                 Mode::Add(field) | Mode::Edit(field) => {
                     let is_edit = matches!(app.mode, Mode::Edit(_));
+                    app.form_error = None;
                     match key.code {
                         KeyCode::Esc => {
                             app.clear_form();
                             app.mode = Mode::Normal;
                         }
+                        // Notes span several lines, so Enter there starts a new one
+                        KeyCode::Enter if matches!(field, AddField::Notes) => {
+                            app.form_notes.push('\n');
+                        }
                         KeyCode::Tab | KeyCode::Enter => {
                             let next = match field {
-                                AddField::Name => AddField::Url,
+                                AddField::Name => AddField::Icon,
+                                AddField::Icon => AddField::Url,
                                 AddField::Url => AddField::Desc,
-                                AddField::Desc => AddField::Tags,
+                                AddField::Desc => AddField::Notes,
+                                AddField::Notes => AddField::Tags,
                                 AddField::Tags => {
                                     if !app.form_name.is_empty() && !app.form_url.is_empty() {
                                         app.save_bookmark();
@@ -458,9 +1118,11 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                         KeyCode::BackTab => {
                             let prev = match field {
                                 AddField::Name => AddField::Name,
-                                AddField::Url => AddField::Name,
+                                AddField::Icon => AddField::Name,
+                                AddField::Url => AddField::Icon,
                                 AddField::Desc => AddField::Url,
-                                AddField::Tags => AddField::Desc,
+                                AddField::Notes => AddField::Desc,
+                                AddField::Tags => AddField::Notes,
                             };
                             app.mode = if is_edit {
                                 Mode::Edit(prev)
@@ -471,8 +1133,10 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                         KeyCode::Backspace => {
                             let field_ref = match field {
                                 AddField::Name => &mut app.form_name,
+                                AddField::Icon => &mut app.form_icon,
                                 AddField::Url => &mut app.form_url,
                                 AddField::Desc => &mut app.form_desc,
+                                AddField::Notes => &mut app.form_notes,
                                 AddField::Tags => &mut app.form_tags,
                             };
                             field_ref.pop();
@@ -480,8 +1144,10 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                         KeyCode::Char(c) => {
                             let field_ref = match field {
                                 AddField::Name => &mut app.form_name,
+                                AddField::Icon => &mut app.form_icon,
                                 AddField::Url => &mut app.form_url,
                                 AddField::Desc => &mut app.form_desc,
+                                AddField::Notes => &mut app.form_notes,
                                 AddField::Tags => &mut app.form_tags,
                             };
                             field_ref.push(c);
@@ -496,26 +1162,47 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                 },
                 Mode::TagFilter => match key.code {
                     KeyCode::Esc => app.mode = Mode::Normal,
-                    KeyCode::Enter => {
-                        let tags = get_all_tags(&app.bookmarks);
-                        if let Some(i) = app.tag_list_state.selected() {
-                            if i == 0 {
-                                app.tag_filter = None;
-                            } else {
-                                app.tag_filter = tags.get(i - 1).cloned();
+                    KeyCode::Char(' ') => {
+                        let tags = app.store.all_tags();
+                        match app.tag_list_state.selected() {
+                            Some(0) => app.tag_selection.clear(),
+                            Some(i) => {
+                                if let Some(tag) = tags.get(i - 1)
+                                    && !app.tag_selection.remove(tag)
+                                {
+                                    app.tag_selection.insert(tag.clone());
+                                }
                             }
+                            None => {}
+                        }
+                    }
+                    KeyCode::Char('m') => {
+                        app.tag_match = match app.tag_match {
+                            TagMatch::All => TagMatch::Any,
+                            TagMatch::Any => TagMatch::All,
+                        };
+                    }
+                    KeyCode::Enter => {
+                        let tags = app.store.all_tags();
+                        // Enter on a tag with nothing toggled filters by just that tag
+                        if app.tag_selection.is_empty()
+                            && let Some(i) = app.tag_list_state.selected()
+                            && let Some(tag) = i.checked_sub(1).and_then(|i| tags.get(i))
+                        {
+                            app.tag_selection.insert(tag.clone());
                         }
+                        app.tag_filter = std::mem::take(&mut app.tag_selection);
                         app.update_filter();
                         app.mode = Mode::Normal;
                     }
                     KeyCode::Down | KeyCode::Char('j') => {
-                        let tags = get_all_tags(&app.bookmarks);
+                        let tags = app.store.all_tags();
                         let len = tags.len() + 1;
                         let i = app.tag_list_state.selected().unwrap_or(0);
                         app.tag_list_state.select(Some((i + 1) % len));
                     }
                     KeyCode::Up | KeyCode::Char('k') => {
-                        let tags = get_all_tags(&app.bookmarks);
+                        let tags = app.store.all_tags();
                         let len = tags.len() + 1;
                         let i = app.tag_list_state.selected().unwrap_or(0);
                         app.tag_list_state
@@ -523,6 +1210,29 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                     }
                     _ => {}
                 },
+                Mode::GroupFilter => {
+                    let len = get_group_counts(app.store.all()).len() + 1;
+                    let i = app.group_list_state.selected().unwrap_or(0);
+                    match key.code {
+                        KeyCode::Esc => app.mode = Mode::Normal,
+                        KeyCode::Enter => {
+                            let groups = get_group_counts(app.store.all());
+                            app.group_filter = i
+                                .checked_sub(1)
+                                .and_then(|i| groups.into_iter().nth(i))
+                                .map(|(group, _)| group);
+                            app.update_filter();
+                            app.mode = Mode::Normal;
+                        }
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            app.group_list_state.select(Some((i + 1) % len))
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => app
+                            .group_list_state
+                            .select(Some(if i == 0 { len - 1 } else { i - 1 })),
+                        _ => {}
+                    }
+                }
             }
         }
 
@@ -532,12 +1242,25 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
     }
 }
 
-fn ui(f: &mut Frame, app: &App) {
+/// First row to show of a `len`-row list that fits `fit` rows, given the
+/// first row shown before. The view only moves when the selection would
+/// leave it, or when the list got shorter and rows would be left empty.
+pub fn scroll_offset(offset: usize, selected: Option<usize>, fit: usize, len: usize) -> usize {
+    let start = match selected {
+        Some(sel) if sel < offset => sel,
+        Some(sel) if sel >= offset + fit => sel + 1 - fit,
+        _ => offset,
+    };
+    start.min(len.saturating_sub(fit))
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
+    let theme = &app.theme;
     let size = f.area();
 
     // Background
     f.render_widget(
-        Block::default().style(Style::default().bg(colors::BASE)),
+        Block::default().style(Style::default().bg(theme.base)),
         size,
     );
 
@@ -551,40 +1274,102 @@ fn ui(f: &mut Frame, app: &App) {
         .split(size);
 
     // Title with tag filter indicator
-    let title = if let Some(ref tag) = app.tag_filter {
-        format!(" Bookmarks [tag: {}] ", tag)
-    } else {
+    let mut title = if app.tag_filter.is_empty() {
         " Bookmarks ".to_string()
+    } else {
+        format!(
+            " Bookmarks [tags: {}] ",
+            tag_filter_label(&app.tag_filter, app.tag_match)
+        )
     };
+    if let Some(ref group) = app.group_filter {
+        title.push_str(&format!("[group: {}] ", group));
+    }
+    if app.sort_mode != SortMode::File {
+        title.push_str(&format!("[sort: {}] ", app.sort_mode.label()));
+    }
+    if !app.marked.is_empty() {
+        title.push_str(&format!("[{} marked] ", app.marked.len()));
+    }
 
-    // Bookmark list
-    let items: Vec<ListItem> = app
-        .filtered_indices
+    let main = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(chunks[0]);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.surface1))
+        .title(Span::styled(title, Style::default().fg(theme.mauve).bold()))
+        .style(Style::default().bg(theme.base));
+
+    // Bookmark list. Only the rows that fit are built: every entry takes two
+    // lines, and the list keeps its scroll offset between frames, scrolling
+    // only as far as needed when the selection leaves the view.
+    let fit = (block.inner(main[0]).height as usize / 2).max(1);
+    let selected = app.list_state.selected();
+    let start = scroll_offset(
+        app.list_state.offset(),
+        selected,
+        fit,
+        app.filtered_indices.len(),
+    );
+    *app.list_state.offset_mut() = start;
+    let end = (start + fit).min(app.filtered_indices.len());
+    let query: Vec<char> = app.search_query.to_lowercase().chars().collect();
+    let icon_width = icon_width(app.store.all());
+    // Columns left for a row after the selection marker
+    let row_width = (block.inner(main[0]).width as usize).saturating_sub("▶ ".width());
+    let items: Vec<ListItem> = app.filtered_indices[start..end]
         .iter()
-        .filter_map(|&i| app.bookmarks.get(i))
-        .map(|bm| {
+        .filter_map(|&i| app.store.all().get(i).map(|bm| (i, bm)))
+        .map(|(i, bm)| {
             let tags = if bm.tags.is_empty() {
                 String::new()
             } else {
                 format!(" [{}]", bm.tags.join(", "))
             };
 
-            let desc = if bm.desc.is_empty() {
+            let mark = if app.marked.contains(&i) { "* " } else { "" };
+            let icon = icon_label(bm, icon_width);
+
+            // Shorten a long description so the tags after it stay in view
+            let room = row_width
+                .saturating_sub(mark.width() + icon.width() + bm.name.width() + tags.width())
+                .saturating_sub(" - ".width());
+            let desc = if bm.desc.is_empty() || room == 0 {
                 String::new()
             } else {
-                format!(" - {}", bm.desc)
+                format!(" - {}", truncate_to_width(&bm.desc, room))
             };
 
-            let line = Line::from(vec![
-                Span::styled(&bm.name, Style::default().fg(colors::LAVENDER).bold()),
-                Span::styled(desc, Style::default().fg(colors::SUBTEXT0)),
-                Span::styled(tags, Style::default().fg(colors::MAUVE)),
-            ]);
-
-            let url_line = Line::from(Span::styled(
-                format!("  {}", bm.url),
-                Style::default().fg(colors::SUBTEXT0).dim(),
+            let mut spans = vec![
+                Span::styled(mark, Style::default().fg(theme.green).bold()),
+                Span::styled(icon, Style::default().fg(theme.subtext0)),
+            ];
+            spans.extend(highlighted(
+                &bm.name,
+                &query,
+                Style::default().fg(theme.lavender).bold(),
+                theme.mauve,
+            ));
+            spans.push(Span::styled(desc, Style::default().fg(theme.subtext0)));
+            spans.push(Span::styled(tags, Style::default().fg(theme.mauve)));
+            let line = Line::from(spans);
+
+            let mut url_spans = vec![Span::raw("  ")];
+            url_spans.extend(highlighted(
+                &bm.url,
+                &query,
+                Style::default().fg(theme.subtext0).dim(),
+                theme.mauve,
             ));
+            if !bm.urls.is_empty() {
+                url_spans.push(Span::styled(
+                    format!(" (+{} more)", bm.urls.len()),
+                    Style::default().fg(theme.subtext0).italic(),
+                ));
+            }
+            let url_line = Line::from(url_spans);
 
             ListItem::new(vec![line, url_line])
         })
@@ -593,54 +1378,53 @@ fn ui(f: &mut Frame, app: &App) {
     let items = if items.is_empty() {
         vec![ListItem::new(Line::from(Span::styled(
             "No bookmarks. Press 'a' to add one.",
-            Style::default().fg(colors::SUBTEXT0).italic(),
+            Style::default().fg(theme.subtext0).italic(),
         )))]
     } else {
         items
     };
 
     let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(colors::SURFACE1))
-                .title(Span::styled(
-                    title,
-                    Style::default().fg(colors::MAUVE).bold(),
-                ))
-                .style(Style::default().bg(colors::BASE)),
-        )
+        .block(block)
         .highlight_style(
             Style::default()
-                .bg(colors::SURFACE0)
+                .bg(theme.surface0)
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol("▶ ");
 
-    f.render_stateful_widget(list, chunks[0], &mut app.list_state.clone());
+    let mut list_state = ListState::default().with_selected(selected.map(|sel| sel - start));
+    f.render_stateful_widget(list, main[0], &mut list_state);
+    render_details(f, app, main[1]);
 
     // Search bar / status
-    let search_block = Block::default()
+    let mut search_block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(colors::SURFACE1))
-        .style(Style::default().bg(colors::BASE));
+        .border_style(Style::default().fg(theme.surface1))
+        .style(Style::default().bg(theme.base));
+    if app.search_scope == SearchScope::All {
+        search_block = search_block.title(Span::styled(
+            " all fields ",
+            Style::default().fg(theme.subtext0),
+        ));
+    }
 
     let search_content = match &app.mode {
         Mode::Search => {
             let cursor = "█";
             Paragraph::new(Line::from(vec![
-                Span::styled(" / ", Style::default().fg(colors::MAUVE)),
-                Span::styled(&app.search_query, Style::default().fg(colors::TEXT)),
-                Span::styled(cursor, Style::default().fg(colors::LAVENDER)),
+                Span::styled(" / ", Style::default().fg(theme.mauve)),
+                Span::styled(&app.search_query, Style::default().fg(theme.text)),
+                Span::styled(cursor, Style::default().fg(theme.lavender)),
             ]))
         }
         _ if !app.search_query.is_empty() => Paragraph::new(Line::from(vec![
-            Span::styled(" Filter: ", Style::default().fg(colors::SUBTEXT0)),
-            Span::styled(&app.search_query, Style::default().fg(colors::TEXT)),
+            Span::styled(" Filter: ", Style::default().fg(theme.subtext0)),
+            Span::styled(&app.search_query, Style::default().fg(theme.text)),
         ])),
         _ => Paragraph::new(Line::from(Span::styled(
             " Type / to search",
-            Style::default().fg(colors::SUBTEXT0),
+            Style::default().fg(theme.subtext0),
         ))),
     };
 
@@ -649,18 +1433,34 @@ fn ui(f: &mut Frame, app: &App) {
     // Help bar
     let help = match &app.mode {
         Mode::Normal => {
-            "↑↓/jk: Navigate │ Enter: Open │ /: Search │ a: Add │ e: Edit │ d: Delete │ t: Tags │ c: Clear filter │ q: Quit"
+            "↑↓/jk: Navigate │ gg/G: Top/Bottom │ J/K: Move │ Enter: Open │ o: Open, stay │ p: Private │ y: Copy URL │ Space: Mark │ /: Search │ a: Add │ e: Edit │ D: Duplicate │ d: Delete │ u: Undo │ t: Tags │ f: Groups │ c: Clear filter │ s: Sort │ q: Quit"
+        }
+        Mode::Search => {
+            "Type to filter │ ↑↓: Navigate │ Ctrl+f: All fields │ Enter: Confirm │ Esc: Cancel"
         }
-        Mode::Search => "Type to filter │ ↑↓: Navigate │ Enter: Confirm │ Esc: Cancel",
         Mode::Add(_) | Mode::Edit(_) => {
-            "Tab: Next field │ Shift+Tab: Previous │ Enter on Tags: Save │ Esc: Cancel"
+            "Tab: Next field │ Shift+Tab: Previous │ Enter in Notes: New line │ Enter on Tags: Save │ Esc: Cancel"
         }
         Mode::Delete => "y/Enter: Confirm │ n/Esc: Cancel",
-        Mode::TagFilter => "↑↓/jk: Navigate │ Enter: Select │ Esc: Cancel",
+        Mode::TagFilter => {
+            "↑↓/jk: Navigate │ Space: Toggle tag │ m: Match all/any │ Enter: Apply │ Esc: Cancel"
+        }
+        Mode::GroupFilter => "↑↓/jk: Navigate │ Enter: Show group │ Esc: Cancel",
+    };
+
+    let help_line = match &app.status {
+        Some(status) => Span::styled(
+            status.text.as_str(),
+            Style::default().fg(if status.is_error {
+                theme.red
+            } else {
+                theme.green
+            }),
+        ),
+        None => Span::styled(help, Style::default().fg(theme.subtext0)),
     };
 
-    let help_paragraph = Paragraph::new(Span::styled(help, Style::default().fg(colors::SUBTEXT0)))
-        .style(Style::default().bg(colors::BASE));
+    let help_paragraph = Paragraph::new(help_line).style(Style::default().bg(theme.base));
 
     f.render_widget(help_paragraph, chunks[2]);
 
@@ -670,22 +1470,117 @@ fn ui(f: &mut Frame, app: &App) {
         Mode::Edit(field) => render_form_modal(f, "Edit Bookmark", field, app),
         Mode::Delete => render_delete_modal(f, app),
         Mode::TagFilter => render_tag_modal(f, app),
+        Mode::GroupFilter => render_group_modal(f, app),
         _ => {}
     }
 }
 
+/// Split `text` into spans, styling the characters matched by `query` in `highlight`.
+/// Without a query, or without a match, the text is a single plain span.
+fn highlighted<'a>(text: &'a str, query: &[char], style: Style, highlight: Color) -> Vec<Span<'a>> {
+    let lower = text.to_lowercase();
+    let indices = match fuzzy_match_indices(query, &lower) {
+        // Lowercasing can change the length of some characters; skip highlighting then
+        Some(indices) if !query.is_empty() && lower.chars().count() == text.chars().count() => {
+            indices
+        }
+        _ => return vec![Span::styled(text, style)],
+    };
+
+    let match_style = style.fg(highlight).bold().not_dim();
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = indices.binary_search(&i).is_ok();
+        if matched != run_matched && !run.is_empty() {
+            let style = if run_matched { match_style } else { style };
+            spans.push(Span::styled(std::mem::take(&mut run), style));
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(
+            run,
+            if run_matched { match_style } else { style },
+        ));
+    }
+    spans
+}
+
+/// Full fields of the selected bookmark, which the list may cut off.
+fn render_details(f: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.surface1))
+        .title(Span::styled(
+            " Details ",
+            Style::default().fg(theme.mauve).bold(),
+        ))
+        .style(Style::default().bg(theme.base));
+
+    let Some(bm) = app.selected_bookmark() else {
+        let empty = Paragraph::new(Span::styled(
+            "No matching bookmarks",
+            Style::default().fg(theme.subtext0).italic(),
+        ))
+        .block(block);
+        f.render_widget(empty, area);
+        return;
+    };
+
+    let field = |label: &'static str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{:<8}", label), Style::default().fg(theme.subtext0)),
+            Span::styled(value, Style::default().fg(theme.text)),
+        ])
+    };
+
+    let mut lines = vec![field("Name", bm.name.clone())];
+    if !bm.aliases.is_empty() {
+        lines.push(field("Aliases", bm.aliases.join(", ")));
+    }
+    lines.push(field("URL", bm.url.clone()));
+    lines.extend(bm.urls.iter().map(|url| field("", url.clone())));
+    lines.extend([
+        field("Desc", bm.desc.clone()),
+        field("Tags", bm.tags.join(", ")),
+        field("Visits", bm.visits.to_string()),
+    ]);
+    if let Some(ref last_opened) = bm.last_opened {
+        lines.push(field("Opened", last_opened.clone()));
+    }
+    if let Some(ref browser) = bm.browser {
+        lines.push(field("Browser", browser.clone()));
+    }
+    for (i, line) in bm.notes.lines().enumerate() {
+        lines.push(field(if i == 0 { "Notes" } else { "" }, line.to_string()));
+    }
+
+    let details = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(details, area);
+}
+
+/// Lines of notes visible at once in the add/edit form.
+const NOTES_FIELD_LINES: u16 = 4;
+
 fn render_form_modal(f: &mut Frame, title: &str, current_field: &AddField, app: &App) {
-    let area = centered_rect(60, 50, f.area());
+    let theme = &app.theme;
+    let area = centered_rect(60, 80, f.area());
     f.render_widget(Clear, area);
 
     let block = Block::default()
         .title(Span::styled(
             format!(" {} ", title),
-            Style::default().fg(colors::MAUVE).bold(),
+            Style::default().fg(theme.mauve).bold(),
         ))
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(colors::LAVENDER))
-        .style(Style::default().bg(colors::BASE));
+        .border_style(Style::default().fg(theme.lavender))
+        .style(Style::default().bg(theme.base));
 
     f.render_widget(block, area);
 
@@ -697,29 +1592,41 @@ fn render_form_modal(f: &mut Frame, title: &str, current_field: &AddField, app:
             Constraint::Length(3),
             Constraint::Length(3),
             Constraint::Length(3),
+            Constraint::Length(NOTES_FIELD_LINES + 2),
+            Constraint::Length(3),
+            Constraint::Length(1),
         ])
         .split(area);
 
     let fields = [
         ("Name", &app.form_name, AddField::Name),
+        (
+            "Icon (emoji or short glyph)",
+            &app.form_icon,
+            AddField::Icon,
+        ),
         ("URL", &app.form_url, AddField::Url),
         ("Description", &app.form_desc, AddField::Desc),
+        ("Notes", &app.form_notes, AddField::Notes),
         ("Tags (comma-separated)", &app.form_tags, AddField::Tags),
     ];
 
     for (i, (label, value, field)) in fields.iter().enumerate() {
         let is_active = current_field == field;
         let style = if is_active {
-            Style::default().fg(colors::LAVENDER)
+            Style::default().fg(theme.lavender)
         } else {
-            Style::default().fg(colors::SURFACE1)
+            Style::default().fg(theme.surface1)
         };
 
         let cursor = if is_active { "█" } else { "" };
         let content = format!("{}{}", value, cursor);
+        // Keep the end of long notes, where typing happens, in view
+        let scroll = (content.lines().count() as u16).saturating_sub(NOTES_FIELD_LINES);
 
         let input = Paragraph::new(content)
-            .style(Style::default().fg(colors::TEXT))
+            .scroll((scroll, 0))
+            .style(Style::default().fg(theme.text))
             .block(
                 Block::default()
                     .borders(Borders::ALL)
@@ -727,48 +1634,59 @@ fn render_form_modal(f: &mut Frame, title: &str, current_field: &AddField, app:
                     .title(Span::styled(
                         format!(" {} ", label),
                         if is_active {
-                            Style::default().fg(colors::LAVENDER)
+                            Style::default().fg(theme.lavender)
                         } else {
-                            Style::default().fg(colors::SUBTEXT0)
+                            Style::default().fg(theme.subtext0)
                         },
                     ))
-                    .style(Style::default().bg(colors::BASE)),
+                    .style(Style::default().bg(theme.base)),
             );
 
         f.render_widget(input, inner[i]);
     }
+
+    if let Some(ref error) = app.form_error {
+        let error = Paragraph::new(Span::styled(
+            format!(" {}", error),
+            Style::default().fg(theme.red),
+        ));
+        f.render_widget(error, inner[fields.len()]);
+    }
 }
 
 fn render_delete_modal(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
     let area = centered_rect(50, 20, f.area());
     f.render_widget(Clear, area);
 
-    let name = app
-        .selected_bookmark()
-        .map(|b| b.name.as_str())
-        .unwrap_or("this bookmark");
+    let prompt = if app.marked.is_empty() {
+        let name = app
+            .selected_bookmark()
+            .map(|b| b.name.as_str())
+            .unwrap_or("this bookmark");
+        format!("Delete '{}'?", name)
+    } else {
+        format!("Delete {} bookmarks?", app.marked.len())
+    };
 
     let block = Block::default()
         .title(Span::styled(
             " Delete ",
-            Style::default().fg(colors::RED).bold(),
+            Style::default().fg(theme.red).bold(),
         ))
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(colors::RED))
-        .style(Style::default().bg(colors::BASE));
+        .border_style(Style::default().fg(theme.red))
+        .style(Style::default().bg(theme.base));
 
     let text = Paragraph::new(vec![
         Line::from(""),
-        Line::from(Span::styled(
-            format!("Delete '{}'?", name),
-            Style::default().fg(colors::TEXT),
-        )),
+        Line::from(Span::styled(prompt, Style::default().fg(theme.text))),
         Line::from(""),
         Line::from(vec![
-            Span::styled("y", Style::default().fg(colors::GREEN).bold()),
-            Span::styled(": Yes  ", Style::default().fg(colors::SUBTEXT0)),
-            Span::styled("n", Style::default().fg(colors::RED).bold()),
-            Span::styled(": No", Style::default().fg(colors::SUBTEXT0)),
+            Span::styled("y", Style::default().fg(theme.green).bold()),
+            Span::styled(": Yes  ", Style::default().fg(theme.subtext0)),
+            Span::styled("n", Style::default().fg(theme.red).bold()),
+            Span::styled(": No", Style::default().fg(theme.subtext0)),
         ]),
     ])
     .block(block)
@@ -778,39 +1696,108 @@ fn render_delete_modal(f: &mut Frame, app: &App) {
 }
 
 fn render_tag_modal(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
     let area = centered_rect(40, 50, f.area());
     f.render_widget(Clear, area);
 
-    let tags = get_all_tags(&app.bookmarks);
+    let tags = app.store.all_tags();
+    let counts: HashMap<String, usize> = get_tag_counts(app.store.all()).into_iter().collect();
     let mut items: Vec<ListItem> = vec![ListItem::new(Span::styled(
         "(All bookmarks)",
-        Style::default().fg(colors::SUBTEXT0),
+        Style::default().fg(theme.subtext0),
     ))];
 
+    items.extend(tags.iter().map(|t| {
+        let check = if app.tag_selection.contains(t) {
+            "[x] "
+        } else {
+            "[ ] "
+        };
+        ListItem::new(Line::from(vec![
+            Span::styled(check, Style::default().fg(theme.green)),
+            Span::styled(t, Style::default().fg(theme.text)),
+            Span::styled(
+                format!(" ({})", counts.get(t).copied().unwrap_or(0)),
+                Style::default().fg(theme.subtext0),
+            ),
+        ]))
+    }));
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(Span::styled(
+                    match app.tag_match {
+                        TagMatch::All => " Filter by Tags (all) ",
+                        TagMatch::Any => " Filter by Tags (any) ",
+                    },
+                    Style::default().fg(theme.mauve).bold(),
+                ))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.lavender))
+                .style(Style::default().bg(theme.base)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(theme.surface0)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+
+    f.render_stateful_widget(list, area, &mut app.tag_list_state.clone());
+}
+
+fn render_group_modal(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let area = centered_rect(40, 50, f.area());
+    f.render_widget(Clear, area);
+
+    let mut items: Vec<ListItem> = vec![ListItem::new(Span::styled(
+        "(All bookmarks)",
+        Style::default().fg(theme.subtext0),
+    ))];
     items.extend(
-        tags.iter()
-            .map(|t| ListItem::new(Span::styled(t, Style::default().fg(colors::TEXT)))),
+        get_group_counts(app.store.all())
+            .into_iter()
+            .map(|(group, count)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{}/", group), Style::default().fg(theme.text)),
+                    Span::styled(format!(" ({})", count), Style::default().fg(theme.subtext0)),
+                ]))
+            }),
     );
 
     let list = List::new(items)
         .block(
             Block::default()
                 .title(Span::styled(
-                    " Filter by Tag ",
-                    Style::default().fg(colors::MAUVE).bold(),
+                    " Groups ",
+                    Style::default().fg(theme.mauve).bold(),
                 ))
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(colors::LAVENDER))
-                .style(Style::default().bg(colors::BASE)),
+                .border_style(Style::default().fg(theme.lavender))
+                .style(Style::default().bg(theme.base)),
         )
         .highlight_style(
             Style::default()
-                .bg(colors::SURFACE0)
+                .bg(theme.surface0)
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol("▶ ");
 
-    f.render_stateful_widget(list, area, &mut app.tag_list_state.clone());
+    f.render_stateful_widget(list, area, &mut app.group_list_state.clone());
+}
+
+/// `dev+rust` when every tag must match, `dev|rust` when any may.
+fn tag_filter_label(tags: &BTreeSet<String>, mode: TagMatch) -> String {
+    let separator = match mode {
+        TagMatch::All => "+",
+        TagMatch::Any => "|",
+    };
+    tags.iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(separator)
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
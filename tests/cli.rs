@@ -1,6 +1,47 @@
 use bmk::bookmarks::{
-    Bookmark, Bookmarks, add_bookmark, delete_bookmark, get_all_tags, update_bookmark,
+    AddOptions, BOOKMARKS_FILE, BOOKMARKS_PATH_ENV, Bookmark, BookmarkError, Bookmarks, Document,
+    EXIT_NO_MATCH, EditOptions, ExportFormat, FileFormat, ImportReport, ListOptions, ListSort,
+    MAX_BACKUPS, MergeReport, MergeStrategy, OpenMode, OpenOptions, Opener, ParseError,
+    SCHEMA_VERSION, Stats, TOP_TAGS, TagMatch, XDG_CONFIG_HOME_ENV, add_bookmark,
+    add_bookmark_with, append_url_path, batch_result, bookmark_group, choose_bookmark,
+    compute_stats, config_dir, count_summary, default_bookmarks_path, delete_bookmark,
+    delete_bookmarks, delete_by_tag, edit_path, editor_command, exit_code, expand_url_template,
+    export_bookmarks, find_bookmark, find_by_url, find_unused, get_all_tags, get_group_counts,
+    get_tag_counts, handle_copy_command, handle_delete_command, handle_edit_command,
+    handle_edit_file_command, handle_merge_command, handle_open_all_command, handle_open_command,
+    handle_prune_command, handle_rename_command, handle_search_command, handle_tag_rename_command,
+    handle_validate_command, humanize_elapsed, icon_label, icon_width, import_bookmarks,
+    is_base_url, is_encrypted_path, list_backups, load_and_upgrade, load_bookmarks,
+    load_bookmarks_from, load_document_from_reader, load_piped_bookmarks, looks_like_url,
+    matches_tags, merge_bookmarks, migrate, move_bookmark, normalize_tags, open_private,
+    opener_for, pad_to_width, parse_browser, parse_netscape_html, prompt_bookmark_fields,
+    recent_bookmarks, record_open, recover_corrupt_file, rename_bookmark, rename_tag,
+    resolve_launch_args, resolve_name, restore_backup, save_bookmarks, save_bookmarks_to,
+    save_visits_to, select_bookmarks, slugify, temp_path, to_markdown, truncate_to_width,
+    unique_name, update_bookmark, validate_bookmarks, validate_tag, validate_url,
+    write_bookmark_list,
 };
+use bmk::cli::{Cli, Commands, TagsCommand, generate_completions};
+use bmk::config::{Config, load_config_from};
+use bmk::fuzzy::{
+    DEFAULT_MIN_SCORE, SearchScope, ambiguous_matches, compute_visible_items, find_best_match,
+    find_confident_match, fuzzy_match, fuzzy_match_indices, fuzzy_score, rank_bookmarks,
+    rank_candidates,
+};
+use bmk::store::BookmarkStore;
+use bmk::theme::{
+    NO_COLOR_ENV, THEME_ENV, Theme, ThemeName, no_color_requested, parse_hex_color,
+    resolve_theme_name,
+};
+use bmk::tui::{STATE_FILE, TuiState, run_plain_picker, scroll_offset, state_path};
+use clap::Parser;
+use clap_complete::Shell;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Mutex;
+
+// Tests that touch process-wide environment variables must not run concurrently.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
 
 #[test]
 fn test_add_bookmark() {
@@ -11,9 +52,10 @@ fn test_add_bookmark() {
         url: "https://github.com".to_string(),
         desc: "Code hosting".to_string(),
         tags: vec!["dev".to_string()],
+        ..Default::default()
     };
 
-    add_bookmark(&mut bookmarks, bookmark);
+    add_bookmark(&mut bookmarks, bookmark).unwrap();
 
     assert_eq!(bookmarks.len(), 1);
     assert_eq!(bookmarks[0].name, "GitHub");
@@ -29,6 +71,7 @@ fn test_update_bookmark() {
         url: "https://github.com".to_string(),
         desc: "Old desc".to_string(),
         tags: vec![],
+        ..Default::default()
     }];
 
     let updated = Bookmark {
@@ -36,9 +79,10 @@ fn test_update_bookmark() {
         url: "https://github.com/new".to_string(),
         desc: "New desc".to_string(),
         tags: vec!["updated".to_string()],
+        ..Default::default()
     };
 
-    update_bookmark(&mut bookmarks, 0, updated);
+    update_bookmark(&mut bookmarks, 0, updated).unwrap();
 
     assert_eq!(bookmarks[0].name, "GitHub Updated");
     assert_eq!(bookmarks[0].url, "https://github.com/new");
@@ -46,6 +90,33 @@ fn test_update_bookmark() {
     assert_eq!(bookmarks[0].tags, vec!["updated"]);
 }
 
+#[test]
+fn test_update_bookmark_keeps_unvalidated_url() {
+    let mut bookmarks: Bookmarks = Vec::new();
+    let weird = Bookmark {
+        name: "weird".to_string(),
+        url: "notaurl with space".to_string(),
+        ..Default::default()
+    };
+    let options = AddOptions {
+        skip_url_validation: true,
+        ..Default::default()
+    };
+    add_bookmark_with(&mut bookmarks, weird, options).unwrap();
+
+    // Other fields can still be edited
+    let mut edited = bookmarks[0].clone();
+    edited.desc = "hello".to_string();
+    update_bookmark(&mut bookmarks, 0, edited).unwrap();
+    assert_eq!(bookmarks[0].desc, "hello");
+    assert_eq!(bookmarks[0].url, "notaurl with space");
+
+    // A new URL is validated as usual
+    let mut edited = bookmarks[0].clone();
+    edited.url = "still not a url".to_string();
+    assert!(update_bookmark(&mut bookmarks, 0, edited).is_err());
+}
+
 #[test]
 fn test_update_bookmark_out_of_bounds() {
     let mut bookmarks: Bookmarks = Vec::new();
@@ -55,10 +126,11 @@ fn test_update_bookmark_out_of_bounds() {
         url: "https://test.com".to_string(),
         desc: String::new(),
         tags: vec![],
+        ..Default::default()
     };
 
     // Should not panic, just do nothing
-    update_bookmark(&mut bookmarks, 10, bookmark);
+    update_bookmark(&mut bookmarks, 10, bookmark).unwrap();
     assert!(bookmarks.is_empty());
 }
 
@@ -69,6 +141,7 @@ fn test_delete_bookmark() {
         url: "https://github.com".to_string(),
         desc: "Code hosting".to_string(),
         tags: vec![],
+        ..Default::default()
     }];
 
     delete_bookmark(&mut bookmarks, 0);
@@ -85,6 +158,30 @@ fn test_delete_bookmark_out_of_bounds() {
     assert!(bookmarks.is_empty());
 }
 
+#[test]
+fn test_delete_bookmarks_by_position() {
+    // A hand-edited file can still hold two bookmarks with the same name;
+    // only the marked one goes
+    let mut bookmarks: Bookmarks = ["gh", "docs", "gh", "mail"]
+        .iter()
+        .map(|name| Bookmark {
+            name: name.to_string(),
+            url: format!("https://{}.example.com", name),
+            ..Default::default()
+        })
+        .collect();
+    bookmarks[2].url = "https://gist.github.com".to_string();
+
+    let removed = delete_bookmarks(&mut bookmarks, &BTreeSet::from([0, 3, 10]));
+
+    assert_eq!(removed, vec!["gh", "mail"]);
+    let left: Vec<&str> = bookmarks.iter().map(|b| b.url.as_str()).collect();
+    assert_eq!(
+        left,
+        vec!["https://docs.example.com", "https://gist.github.com"]
+    );
+}
+
 #[test]
 fn test_get_all_tags() {
     let bookmarks: Bookmarks = vec![
@@ -93,12 +190,14 @@ fn test_get_all_tags() {
             url: "https://github.com".to_string(),
             desc: "Code".to_string(),
             tags: vec!["dev".to_string(), "code".to_string()],
+            ..Default::default()
         },
         Bookmark {
             name: "Docs".to_string(),
             url: "https://docs.rs".to_string(),
             desc: "Docs".to_string(),
             tags: vec!["dev".to_string(), "rust".to_string()],
+            ..Default::default()
         },
     ];
 
@@ -123,9 +222,3481 @@ fn test_bookmark_without_optional_fields() {
         url: "https://test.com".to_string(),
         desc: String::new(),
         tags: vec![],
+        ..Default::default()
     };
 
     assert_eq!(bookmark.name, "Test");
     assert!(bookmark.desc.is_empty());
     assert!(bookmark.tags.is_empty());
 }
+
+#[test]
+fn test_bookmarks_path_env_override_round_trip() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested").join("work.yaml");
+    unsafe { std::env::set_var(BOOKMARKS_PATH_ENV, &path) };
+
+    let bookmarks: Bookmarks = vec![Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        desc: "Code hosting".to_string(),
+        tags: vec!["dev".to_string()],
+        ..Default::default()
+    }];
+
+    save_bookmarks(&bookmarks).unwrap();
+    let loaded = load_bookmarks().unwrap();
+    unsafe { std::env::remove_var(BOOKMARKS_PATH_ENV) };
+
+    assert!(path.exists());
+    assert_eq!(loaded, bookmarks);
+}
+
+#[test]
+fn test_save_to_and_load_from_explicit_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.yaml");
+
+    let bookmarks: Bookmarks = vec![Bookmark {
+        name: "Work".to_string(),
+        url: "https://work.example.com".to_string(),
+        desc: String::new(),
+        tags: vec![],
+        ..Default::default()
+    }];
+
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+
+    assert_eq!(load_bookmarks_from(&path).unwrap(), bookmarks);
+}
+
+#[test]
+fn test_load_from_missing_path_is_empty() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let loaded = load_bookmarks_from(&dir.path().join("missing.yaml")).unwrap();
+
+    assert!(loaded.is_empty());
+}
+
+#[test]
+fn test_cli_config_flag_takes_precedence() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe { std::env::set_var(BOOKMARKS_PATH_ENV, "/tmp/from-env.yaml") };
+
+    let cli = Cli::try_parse_from(["bmk", "--config", "./test.yaml", "work"]).unwrap();
+    let path = cli.bookmarks_path(&Config::default()).unwrap();
+    unsafe { std::env::remove_var(BOOKMARKS_PATH_ENV) };
+
+    assert_eq!(path, std::path::PathBuf::from("./test.yaml"));
+    assert_eq!(cli.query, vec!["work"]);
+}
+
+#[test]
+fn test_load_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+
+    // No file, or an empty one, changes nothing
+    assert_eq!(load_config_from(&path).unwrap(), Config::default());
+    assert!(Config::default().confirm_delete);
+    std::fs::write(&path, "").unwrap();
+    assert_eq!(load_config_from(&path).unwrap(), Config::default());
+
+    std::fs::write(
+        &path,
+        "bookmarks_path: ~/work.yaml\nbrowser: firefox\ntheme: latte\ndefault_sort: recent\nconfirm_delete: false\n",
+    )
+    .unwrap();
+    let config = load_config_from(&path).unwrap();
+    assert_eq!(
+        config.bookmarks_path,
+        Some(home::home_dir().unwrap().join("work.yaml"))
+    );
+    assert_eq!(config.browser.as_deref(), Some("firefox"));
+    assert_eq!(config.theme, Some(ThemeName::Light));
+    assert_eq!(config.default_sort, Some(ListSort::Recent));
+    assert!(!config.confirm_delete);
+
+    // Typos are errors rather than silently ignored
+    std::fs::write(&path, "browsr: firefox\n").unwrap();
+    let err = load_config_from(&path).unwrap_err();
+    assert!(format!("{:#}", err).contains("Failed to parse config file"));
+}
+
+#[test]
+fn test_config_bookmarks_path_precedence() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let saved = std::env::var_os(BOOKMARKS_PATH_ENV);
+    let config = Config {
+        bookmarks_path: Some("/tmp/from-config.yaml".into()),
+        ..Default::default()
+    };
+
+    unsafe { std::env::remove_var(BOOKMARKS_PATH_ENV) };
+    let cli = Cli::try_parse_from(["bmk"]).unwrap();
+    assert_eq!(
+        cli.bookmarks_path(&config).unwrap(),
+        std::path::PathBuf::from("/tmp/from-config.yaml")
+    );
+
+    unsafe { std::env::set_var(BOOKMARKS_PATH_ENV, "/tmp/from-env.yaml") };
+    assert_eq!(
+        cli.bookmarks_path(&config).unwrap(),
+        std::path::PathBuf::from("/tmp/from-env.yaml")
+    );
+
+    let cli = Cli::try_parse_from(["bmk", "--config", "./flag.yaml"]).unwrap();
+    assert_eq!(
+        cli.bookmarks_path(&config).unwrap(),
+        std::path::PathBuf::from("./flag.yaml")
+    );
+
+    match saved {
+        Some(value) => unsafe { std::env::set_var(BOOKMARKS_PATH_ENV, value) },
+        None => unsafe { std::env::remove_var(BOOKMARKS_PATH_ENV) },
+    }
+}
+
+#[test]
+fn test_default_bookmarks_path_without_home() {
+    let dir = std::path::PathBuf::from("/etc/bmk");
+    assert_eq!(
+        default_bookmarks_path(Ok(dir.clone())),
+        dir.join(BOOKMARKS_FILE)
+    );
+
+    // With no home directory and no override, the working directory is used
+    let no_home = anyhow::anyhow!("Failed to find the home directory");
+    assert_eq!(
+        default_bookmarks_path(Err(no_home)),
+        std::path::PathBuf::from("bookmarks.yaml")
+    );
+}
+
+#[test]
+fn test_config_dir_honors_xdg_config_home() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let previous = std::env::var_os(XDG_CONFIG_HOME_ENV);
+
+    unsafe { std::env::set_var(XDG_CONFIG_HOME_ENV, "/tmp/xdg-config") };
+    let dir = config_dir().unwrap();
+    // Relative values are invalid per the spec and fall back to ~/.config
+    unsafe { std::env::set_var(XDG_CONFIG_HOME_ENV, "relative/config") };
+    let fallback = config_dir().unwrap();
+    match previous {
+        Some(value) => unsafe { std::env::set_var(XDG_CONFIG_HOME_ENV, value) },
+        None => unsafe { std::env::remove_var(XDG_CONFIG_HOME_ENV) },
+    }
+
+    assert_eq!(dir, std::path::PathBuf::from("/tmp/xdg-config/bmk"));
+    assert!(fallback.ends_with(".config/bmk"));
+}
+
+#[test]
+fn test_json_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.json");
+
+    let bookmarks: Bookmarks = vec![
+        Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            desc: "Code hosting".to_string(),
+            tags: vec!["dev".to_string(), "code".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "Example".to_string(),
+            url: "https://example.com".to_string(),
+            desc: String::new(),
+            tags: vec![],
+            ..Default::default()
+        },
+    ];
+
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+
+    assert!(contents.starts_with("{\n  \"version\": 1,"));
+    assert_eq!(load_bookmarks_from(&path).unwrap(), bookmarks);
+}
+
+#[test]
+fn test_file_format_from_extension() {
+    assert_eq!(
+        FileFormat::from_path(Path::new("b.yaml")).unwrap(),
+        FileFormat::Yaml
+    );
+    assert_eq!(
+        FileFormat::from_path(Path::new("b.yml")).unwrap(),
+        FileFormat::Yaml
+    );
+    assert_eq!(
+        FileFormat::from_path(Path::new("b.JSON")).unwrap(),
+        FileFormat::Json
+    );
+    assert!(FileFormat::from_path(Path::new("b.toml")).is_err());
+}
+
+#[test]
+fn test_parse_netscape_html() {
+    let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><H3>Dev</H3>
+    <DL><p>
+        <DT><A HREF="https://github.com" ADD_DATE="1" TAGS="dev,code">GitHub</A>
+        <DD>Code hosting
+        <DT><A HREF="https://docs.rs">Rust &amp; Docs</A>
+        <DT><A ADD_DATE="2">No href</A>
+        <DT><A HREF="https://github.com/rust-lang">GitHub</A>
+    </DL><p>
+</DL><p>"#;
+
+    let bookmarks = parse_netscape_html(html);
+
+    assert_eq!(bookmarks.len(), 3);
+    assert_eq!(bookmarks[0].name, "github");
+    assert_eq!(bookmarks[0].url, "https://github.com");
+    assert_eq!(bookmarks[0].desc, "Code hosting");
+    assert_eq!(bookmarks[0].tags, vec!["dev", "code"]);
+    assert_eq!(bookmarks[1].name, "rust-docs");
+    assert!(bookmarks[1].desc.is_empty());
+    assert_eq!(bookmarks[2].name, "github-2");
+}
+
+#[test]
+fn test_slugify() {
+    assert_eq!(
+        slugify("Rust Programming Language – Docs"),
+        "rust-programming-language-docs"
+    );
+    assert_eq!(slugify("  (GitHub)  "), "github");
+    assert_eq!(slugify("日本語のページ!"), "日本語のページ");
+    assert_eq!(slugify("https://Docs.rs/"), "https-docs-rs");
+    assert_eq!(slugify("–"), "");
+}
+
+#[test]
+fn test_parse_netscape_html_multibyte_text() {
+    // Text right after </A> used to be sliced at a byte offset inside a character
+    let bookmarks = parse_netscape_html(
+        "<DT><A HREF=\"https://a.example\">A</A>日本\n\
+         <DT><A HREF=\"https://b.example\">B</A>🔖\n\
+         <DT><A HREF=\"https://c.example\">日本語</A>\n<DD>ブックマーク",
+    );
+    let names: Vec<&str> = bookmarks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["a", "b", "日本語"]);
+    assert!(bookmarks[0].desc.is_empty());
+    assert_eq!(bookmarks[2].desc, "ブックマーク");
+}
+
+#[test]
+fn test_unique_name_appends_suffix() {
+    let mut bookmarks: Bookmarks = Vec::new();
+    assert_eq!(unique_name(&bookmarks, "Docs"), "Docs");
+
+    for name in ["Docs", "Docs-2"] {
+        bookmarks.push(Bookmark {
+            name: name.to_string(),
+            url: "https://docs.rs".to_string(),
+            desc: String::new(),
+            tags: vec![],
+            ..Default::default()
+        });
+    }
+
+    assert_eq!(unique_name(&bookmarks, "Docs"), "Docs-3");
+}
+
+#[test]
+fn test_cli_subcommand_vs_query() {
+    let cli =
+        Cli::try_parse_from(["bmk", "--config", "x.yaml", "import", "bookmarks.html"]).unwrap();
+    assert!(matches!(cli.command, Some(Commands::Import { .. })));
+
+    let cli = Cli::try_parse_from(["bmk", "rust", "docs"]).unwrap();
+    assert!(cli.command.is_none());
+    assert_eq!(cli.query, vec!["rust", "docs"]);
+}
+
+#[test]
+fn test_edit_command_updates_only_given_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let bookmarks: Bookmarks = vec![Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        desc: "Code hosting".to_string(),
+        tags: vec!["dev".to_string()],
+        ..Default::default()
+    }];
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+
+    handle_edit_command(
+        &path,
+        "GitHub",
+        EditOptions {
+            desc: Some("Where code lives".to_string()),
+            tags: Some(vec!["dev".to_string(), "git".to_string()]),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let loaded = load_bookmarks_from(&path).unwrap();
+    assert_eq!(loaded[0].url, "https://github.com");
+    assert_eq!(loaded[0].desc, "Where code lives");
+    assert_eq!(loaded[0].tags, vec!["dev", "git"]);
+}
+
+#[test]
+fn test_edit_command_missing_bookmark() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+
+    let err = handle_edit_command(&path, "Nope", EditOptions::default()).unwrap_err();
+
+    assert_eq!(err.to_string(), "Bookmark 'Nope' not found");
+}
+
+#[test]
+fn test_resolve_name_ignores_case() {
+    let mut bookmarks = rename_fixture();
+    bookmarks[2].aliases = vec!["Inbox".to_string()];
+    bookmarks.push(Bookmark {
+        name: "GH".to_string(),
+        url: "https://github.com".to_string(),
+        ..Default::default()
+    });
+
+    // The exact-case name wins over one that only matches case-insensitively
+    assert_eq!(resolve_name(&bookmarks, "gh"), Some(0));
+    assert_eq!(resolve_name(&bookmarks, "GH"), Some(3));
+    assert_eq!(resolve_name(&bookmarks, "Docs"), Some(1));
+    assert_eq!(resolve_name(&bookmarks, "INBOX"), Some(2));
+    assert_eq!(resolve_name(&bookmarks, "nope"), None);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    handle_edit_command(
+        &path,
+        "MAIL",
+        EditOptions {
+            desc: Some("Email".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap()[2].desc, "Email");
+}
+
+#[test]
+fn test_add_bookmark_rejects_duplicate_url() {
+    let mut bookmarks: Bookmarks = vec![Bookmark {
+        name: "gh".to_string(),
+        url: "https://github.com".to_string(),
+        desc: String::new(),
+        tags: vec![],
+        ..Default::default()
+    }];
+
+    let duplicate = Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://GitHub.com/".to_string(),
+        desc: String::new(),
+        tags: vec![],
+        ..Default::default()
+    };
+
+    let err = add_bookmark(&mut bookmarks, duplicate.clone()).unwrap_err();
+    assert_eq!(err.to_string(), "URL already bookmarked under name 'gh'");
+    assert_eq!(bookmarks.len(), 1);
+
+    let options = AddOptions {
+        allow_duplicate_url: true,
+        ..Default::default()
+    };
+    add_bookmark_with(&mut bookmarks, duplicate, options).unwrap();
+    assert_eq!(bookmarks.len(), 2);
+}
+
+#[test]
+fn test_names_stay_unique() {
+    let bookmark = |name: &str, url: &str| Bookmark {
+        name: name.to_string(),
+        url: url.to_string(),
+        ..Default::default()
+    };
+    let mut bookmarks = rename_fixture();
+    bookmarks[1].aliases = vec!["wiki".to_string()];
+    let duplicate = |name: &str| Err(BookmarkError::DuplicateName(name.to_string()));
+
+    // A name some bookmark already answers to, in any case, can't be added
+    for name in ["gh", "GH", "Wiki"] {
+        assert_eq!(
+            add_bookmark(&mut bookmarks, bookmark(name, "https://gitlab.com")),
+            duplicate(name)
+        );
+    }
+    assert_eq!(bookmarks.len(), 3);
+    add_bookmark(&mut bookmarks, bookmark("gl", "https://gitlab.com")).unwrap();
+
+    // Editing can keep a bookmark's own name, but not take another's
+    update_bookmark(&mut bookmarks, 0, bookmark("GH", "https://github.com")).unwrap();
+    assert_eq!(
+        update_bookmark(&mut bookmarks, 0, bookmark("mail", "https://github.com")),
+        duplicate("mail")
+    );
+    assert_eq!(
+        rename_bookmark(&mut bookmarks, "gl", "MAIL"),
+        duplicate("MAIL")
+    );
+    rename_bookmark(&mut bookmarks, "gl", "GL").unwrap();
+
+    // Generated names avoid other cases and aliases too
+    assert_eq!(unique_name(&bookmarks, "Mail"), "Mail-2");
+    assert_eq!(unique_name(&bookmarks, "wiki"), "wiki-2");
+    let report = merge_bookmarks(
+        &mut bookmarks,
+        vec![bookmark("WIKI", "https://wiki.example.com")],
+        MergeStrategy::Keep,
+        false,
+    );
+    assert_eq!(report.renamed, 1);
+    assert_eq!(bookmarks[4].name, "WIKI-2");
+}
+
+#[test]
+fn test_find_by_url_normalizes() {
+    let bookmarks: Bookmarks = vec![Bookmark {
+        name: "Docs".to_string(),
+        url: "https://docs.rs/serde/".to_string(),
+        desc: String::new(),
+        tags: vec![],
+        ..Default::default()
+    }];
+
+    assert_eq!(
+        find_by_url(&bookmarks, "HTTPS://DOCS.RS/serde"),
+        Some(&"Docs".to_string())
+    );
+    // Paths stay case-sensitive
+    assert_eq!(find_by_url(&bookmarks, "https://docs.rs/Serde"), None);
+}
+
+#[test]
+fn test_record_open_tracks_visits() {
+    let mut bookmarks: Bookmarks = vec![Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        ..Default::default()
+    }];
+
+    record_open(&mut bookmarks, 0);
+    record_open(&mut bookmarks, 0);
+    // Out of bounds is ignored
+    record_open(&mut bookmarks, 5);
+
+    assert_eq!(bookmarks[0].visits, 2);
+    let stamp = bookmarks[0].last_opened.as_deref().unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(stamp).is_ok());
+}
+
+#[test]
+fn test_load_yaml_without_usage_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::write(&path, "- name: Example\n  url: https://example.com\n").unwrap();
+
+    let loaded = load_bookmarks_from(&path).unwrap();
+
+    assert_eq!(loaded[0].visits, 0);
+    assert_eq!(loaded[0].last_opened, None);
+    assert!(loaded[0].urls.is_empty());
+}
+
+#[test]
+fn test_bookmark_with_multiple_urls() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::write(
+        &path,
+        "- name: project\n  url: https://github.com/me/project\n  urls:\n    - https://ci.example.com\n    - https://docs.example.com\n",
+    )
+    .unwrap();
+
+    let loaded = load_bookmarks_from(&path).unwrap();
+    let urls: Vec<&str> = loaded[0].all_urls().collect();
+    assert_eq!(
+        urls,
+        vec![
+            "https://github.com/me/project",
+            "https://ci.example.com",
+            "https://docs.example.com"
+        ]
+    );
+
+    // Single-URL bookmarks are written without an empty `urls` list
+    let single = Bookmark {
+        name: "Example".to_string(),
+        url: "https://example.com".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(single.all_urls().count(), 1);
+    assert!(!serde_yaml::to_string(&single).unwrap().contains("urls"));
+}
+
+#[test]
+fn test_load_unversioned_file_upgrades_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::write(
+        &path,
+        "- name: Example\n  url: https://example.com\n  visits: 3\n",
+    )
+    .unwrap();
+
+    // Files that are only read, like the other file of a merge, are left alone
+    let v0 = std::fs::read_to_string(&path).unwrap();
+    let loaded = load_bookmarks_from(&path).unwrap();
+    assert_eq!(loaded[0].name, "Example");
+    assert_eq!(loaded[0].visits, 3);
+    let main = dir.path().join("main.yaml");
+    handle_merge_command(&main, &path, MergeStrategy::Keep, false).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), v0);
+    assert!(list_backups(&path).unwrap().is_empty());
+
+    // The file bmk manages is rewritten once in the versioned layout, with a backup of the old one
+    assert_eq!(load_and_upgrade(&path).unwrap(), loaded);
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.starts_with("version: 1\nbookmarks:\n"));
+    assert_eq!(list_backups(&path).unwrap().len(), 1);
+    assert_eq!(load_and_upgrade(&path).unwrap(), loaded);
+    assert_eq!(list_backups(&path).unwrap().len(), 1);
+
+    // A missing file isn't created
+    let missing = dir.path().join("missing.yaml");
+    assert!(load_and_upgrade(&missing).unwrap().is_empty());
+    assert!(!missing.exists());
+}
+
+#[test]
+fn test_migrate_rejects_newer_versions() {
+    let document =
+        load_document_from_reader("version: 99\nbookmarks: []\n".as_bytes(), FileFormat::Yaml)
+            .unwrap();
+    assert_eq!(document.version, 99);
+    let err = migrate(document).unwrap_err();
+    assert!(err.to_string().contains("version 99"));
+
+    let document = migrate(Document::default()).unwrap();
+    assert_eq!(document.version, SCHEMA_VERSION);
+}
+
+#[test]
+fn test_find_best_match_prefers_frequently_opened() {
+    let mut bookmarks: Bookmarks = vec![
+        Bookmark {
+            name: "gh-work".to_string(),
+            url: "https://github.com/work".to_string(),
+            ..Default::default()
+        },
+        Bookmark {
+            name: "gh-home".to_string(),
+            url: "https://github.com/home".to_string(),
+            ..Default::default()
+        },
+    ];
+
+    bookmarks[0].visits = 20;
+    assert_eq!(
+        find_best_match(&bookmarks, "gh"),
+        Some(("gh-work".to_string(), "https://github.com/work".to_string()))
+    );
+
+    bookmarks[0].visits = 0;
+    bookmarks[1].visits = 20;
+    assert_eq!(
+        find_best_match(&bookmarks, "gh"),
+        Some(("gh-home".to_string(), "https://github.com/home".to_string()))
+    );
+}
+
+#[test]
+fn test_usage_does_not_outrank_better_field_match() {
+    let bookmarks: Bookmarks = vec![
+        Bookmark {
+            name: "Popular".to_string(),
+            url: "https://rust-lang.org".to_string(),
+            visits: 1000,
+            ..Default::default()
+        },
+        Bookmark {
+            name: "Rust".to_string(),
+            url: "https://example.com".to_string(),
+            ..Default::default()
+        },
+    ];
+
+    assert_eq!(
+        find_best_match(&bookmarks, "rust"),
+        Some(("Rust".to_string(), "https://example.com".to_string()))
+    );
+}
+
+#[test]
+fn test_copy_command_missing_bookmark() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+
+    let err = handle_copy_command(&path, "Nope", true).unwrap_err();
+
+    assert_eq!(err.to_string(), "Bookmark 'Nope' not found");
+}
+
+#[test]
+fn test_generate_completions_include_names() {
+    for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+        let mut out = Vec::new();
+        generate_completions(shell, &mut out).unwrap();
+        let script = String::from_utf8(out).unwrap();
+
+        assert!(
+            script.contains("import"),
+            "{shell} script lacks subcommands"
+        );
+        let names = script
+            .find("bmk list --names")
+            .map(|start| &script[start..])
+            .unwrap_or_else(|| panic!("{shell} script lacks names"));
+        for command in ["open", "copy", "edit", "delete", "rename", "--name"] {
+            assert!(
+                names.contains(command),
+                "{shell} script doesn't complete names for {command}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_open_command_not_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let bookmarks: Bookmarks = vec![Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        ..Default::default()
+    }];
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+
+    let err = handle_open_command(&path, "zzz", &OpenOptions::default()).unwrap_err();
+
+    assert_eq!(err.to_string(), "Bookmark 'zzz' not found");
+}
+
+fn rename_fixture() -> Bookmarks {
+    ["gh", "docs", "mail"]
+        .iter()
+        .map(|name| Bookmark {
+            name: name.to_string(),
+            url: format!("https://{}.example.com", name),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[test]
+fn test_rename_bookmark() {
+    let mut bookmarks = rename_fixture();
+
+    rename_bookmark(&mut bookmarks, "docs", "rustdocs").unwrap();
+
+    let names: Vec<&str> = bookmarks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["gh", "rustdocs", "mail"]);
+    assert_eq!(bookmarks[1].url, "https://docs.example.com");
+}
+
+#[test]
+fn test_rename_bookmark_missing_old() {
+    let mut bookmarks = rename_fixture();
+
+    let err = rename_bookmark(&mut bookmarks, "nope", "other").unwrap_err();
+
+    assert_eq!(err.to_string(), "Bookmark 'nope' not found");
+    assert_eq!(bookmarks, rename_fixture());
+}
+
+#[test]
+fn test_rename_bookmark_new_collision() {
+    let mut bookmarks = rename_fixture();
+
+    let err = rename_bookmark(&mut bookmarks, "gh", "mail").unwrap_err();
+
+    assert_eq!(err.to_string(), "Bookmark 'mail' already exists");
+    assert_eq!(bookmarks, rename_fixture());
+}
+
+#[test]
+fn test_get_tag_counts() {
+    let bookmarks: Bookmarks = vec![
+        Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            tags: vec!["dev".to_string(), "code".to_string(), "dev".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "Docs".to_string(),
+            url: "https://docs.rs".to_string(),
+            tags: vec!["dev".to_string(), "rust".to_string()],
+            ..Default::default()
+        },
+    ];
+
+    let counts = get_tag_counts(&bookmarks);
+
+    assert_eq!(
+        counts,
+        vec![
+            ("dev".to_string(), 2),
+            ("code".to_string(), 1),
+            ("rust".to_string(), 1),
+        ]
+    );
+}
+
+#[test]
+fn test_save_leaves_no_temp_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::write(temp_path(&path), "stale").unwrap();
+
+    save_bookmarks_to(&path, &rename_fixture()).unwrap();
+
+    assert!(!temp_path(&path).exists());
+    assert_eq!(load_bookmarks_from(&path).unwrap(), rename_fixture());
+}
+
+#[test]
+fn test_failed_save_keeps_original_and_cleans_up() {
+    let dir = tempfile::tempdir().unwrap();
+    // A non-empty directory at the target path makes the final rename fail
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::create_dir(&path).unwrap();
+    std::fs::write(path.join("keep"), "original").unwrap();
+
+    assert!(save_bookmarks_to(&path, &rename_fixture()).is_err());
+
+    assert!(!temp_path(&path).exists());
+    assert_eq!(
+        std::fs::read_to_string(path.join("keep")).unwrap(),
+        "original"
+    );
+}
+
+#[test]
+fn test_save_writes_rotating_backups() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let mut bookmarks = rename_fixture();
+
+    // The first save has nothing to back up
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    assert!(list_backups(&path).unwrap().is_empty());
+
+    for i in 0..MAX_BACKUPS + 3 {
+        bookmarks[0].visits = i as u64;
+        save_bookmarks_to(&path, &bookmarks).unwrap();
+    }
+
+    let backups = list_backups(&path).unwrap();
+    assert_eq!(backups.len(), MAX_BACKUPS);
+    // The newest backup holds the state before the last save
+    let newest = std::fs::read_to_string(&backups[0]).unwrap();
+    assert!(newest.contains(&format!("visits: {}", MAX_BACKUPS + 1)));
+}
+
+#[test]
+fn test_visits_keep_backups() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let mut bookmarks = rename_fixture();
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    bookmarks.remove(0);
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    let backups = list_backups(&path).unwrap();
+    assert_eq!(backups.len(), 1);
+
+    // Opening bookmarks many times doesn't rotate out the backup taken
+    // before the delete
+    for _ in 0..MAX_BACKUPS + 3 {
+        record_open(&mut bookmarks, 0);
+        save_visits_to(&path, &bookmarks).unwrap();
+    }
+    let mut store = BookmarkStore::open(&path).unwrap().with_autosave(true);
+    store.record_open(0).unwrap();
+
+    assert_eq!(list_backups(&path).unwrap(), backups);
+    assert_eq!(
+        load_bookmarks_from(&path).unwrap()[0].visits,
+        MAX_BACKUPS as u64 + 4
+    );
+}
+
+#[test]
+fn test_restore_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    save_bookmarks_to(&path, &rename_fixture()).unwrap();
+    save_bookmarks_to(&path, &Vec::new()).unwrap();
+
+    let backups = list_backups(&path).unwrap();
+    restore_backup(&path, &backups[0]).unwrap();
+
+    assert_eq!(load_bookmarks_from(&path).unwrap(), rename_fixture());
+    // The emptied file was backed up before being replaced
+    assert_eq!(list_backups(&path).unwrap().len(), 2);
+}
+
+#[test]
+fn test_validate_url() {
+    assert_eq!(
+        validate_url("github.com/rust-lang").unwrap(),
+        "https://github.com/rust-lang"
+    );
+    assert_eq!(
+        validate_url("localhost:8080/admin").unwrap(),
+        "https://localhost:8080/admin"
+    );
+    assert_eq!(
+        validate_url(" https://example.com ").unwrap(),
+        "https://example.com"
+    );
+    assert_eq!(
+        validate_url("mailto:me@example.com").unwrap(),
+        "mailto:me@example.com"
+    );
+    assert_eq!(
+        validate_url("file:///home/me/notes.md").unwrap(),
+        "file:///home/me/notes.md"
+    );
+
+    assert!(validate_url("").is_err());
+    assert!(validate_url("not a url").is_err());
+    assert!(validate_url("https://").is_err());
+}
+
+#[test]
+fn test_add_bookmark_prepends_scheme() {
+    let mut bookmarks: Bookmarks = Vec::new();
+    let bookmark = Bookmark {
+        name: "GitHub".to_string(),
+        url: "github.com".to_string(),
+        ..Default::default()
+    };
+
+    add_bookmark(&mut bookmarks, bookmark).unwrap();
+
+    assert_eq!(bookmarks[0].url, "https://github.com");
+}
+
+#[test]
+fn test_opener_for_scheme() {
+    assert_eq!(opener_for("https://github.com"), Opener::Browser);
+    assert_eq!(opener_for("HTTP://example.com"), Opener::Browser);
+    assert_eq!(opener_for("mailto:me@example.com"), Opener::System);
+    assert_eq!(opener_for("file:///tmp/notes.md"), Opener::System);
+    assert_eq!(opener_for("dummy-scheme://whatever"), Opener::System);
+}
+
+#[test]
+fn test_parse_browser() {
+    use webbrowser::Browser;
+
+    assert_eq!(parse_browser("firefox").unwrap(), Browser::Firefox);
+    assert_eq!(parse_browser("Chrome").unwrap(), Browser::Chrome);
+    assert_eq!(parse_browser("default").unwrap(), Browser::Default);
+
+    let err = parse_browser("netscape").unwrap_err().to_string();
+    assert!(err.starts_with("Unknown browser 'netscape' (supported: default, firefox"));
+}
+
+#[test]
+fn test_browser_precedence() {
+    let mut bookmark = Bookmark {
+        name: "gh".to_string(),
+        url: "https://github.com".to_string(),
+        browser: Some("chrome".to_string()),
+        ..Default::default()
+    };
+    let mut options = OpenOptions {
+        default_browser: Some("firefox".to_string()),
+        ..Default::default()
+    };
+
+    // The configured default only applies to bookmarks without their own browser
+    assert_eq!(options.browser_for(&bookmark), Some("chrome"));
+    options.browser = Some("opera".to_string());
+    assert_eq!(options.browser_for(&bookmark), Some("opera"));
+    options.browser = None;
+    bookmark.browser = None;
+    assert_eq!(options.browser_for(&bookmark), Some("firefox"));
+    options.default_browser = None;
+    assert_eq!(options.browser_for(&bookmark), None);
+}
+
+#[test]
+fn test_private_flag_parses() {
+    let cli = Cli::try_parse_from(["bmk", "--private", "github"]).unwrap();
+    assert!(cli.private);
+    assert_eq!(cli.query, vec!["github"]);
+
+    let cli = Cli::try_parse_from(["bmk", "open", "github", "--private"]).unwrap();
+    match cli.command {
+        Some(Commands::Open { private, .. }) => assert!(private),
+        other => panic!("unexpected command: {:?}", other),
+    }
+}
+
+#[test]
+fn test_open_private_rejects_unknown_browser() {
+    let err = open_private("https://example.com", Some("netscape"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("Unknown browser 'netscape'"));
+}
+
+#[test]
+fn test_fuzzy_match_indices() {
+    let pattern: Vec<char> = "gh".chars().collect();
+    assert_eq!(fuzzy_match_indices(&pattern, "github"), Some(vec![0, 3]));
+    assert_eq!(fuzzy_match_indices(&pattern, "gitlab"), None);
+    assert_eq!(fuzzy_match_indices(&[], "github"), Some(vec![]));
+}
+
+#[test]
+fn test_parse_hex_color() {
+    use ratatui::style::Color;
+
+    assert_eq!(parse_hex_color("#1e1e2e").unwrap(), Color::Rgb(30, 30, 46));
+    assert_eq!(
+        parse_hex_color("FFFFFF").unwrap(),
+        Color::Rgb(255, 255, 255)
+    );
+    assert!(parse_hex_color("#fff").is_err());
+    assert!(parse_hex_color("#gggggg").is_err());
+}
+
+#[test]
+fn test_theme_overrides() {
+    use ratatui::style::Color;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("theme.yaml");
+    std::fs::write(&path, "base: \"#000000\"\nred: \"#ff0000\"\n").unwrap();
+
+    let theme = Theme::builtin(ThemeName::Light)
+        .with_overrides(&path)
+        .unwrap();
+    assert_eq!(theme.base, Color::Rgb(0, 0, 0));
+    assert_eq!(theme.red, Color::Rgb(255, 0, 0));
+    assert_eq!(theme.text, Theme::builtin(ThemeName::Light).text);
+
+    std::fs::write(&path, "background: \"#000000\"\n").unwrap();
+    assert!(
+        Theme::builtin(ThemeName::Dark)
+            .with_overrides(&path)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_theme_name_resolution() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    unsafe { std::env::remove_var(THEME_ENV) };
+    assert_eq!(resolve_theme_name(None, None).unwrap(), ThemeName::Dark);
+    assert_eq!(
+        resolve_theme_name(None, Some(ThemeName::Light)).unwrap(),
+        ThemeName::Light
+    );
+
+    unsafe { std::env::set_var(THEME_ENV, "Light") };
+    assert_eq!(resolve_theme_name(None, None).unwrap(), ThemeName::Light);
+    assert_eq!(
+        resolve_theme_name(Some(ThemeName::Dark), None).unwrap(),
+        ThemeName::Dark
+    );
+
+    // BMK_THEME takes precedence over the config file
+    unsafe { std::env::set_var(THEME_ENV, "dark") };
+    assert_eq!(
+        resolve_theme_name(None, Some(ThemeName::Light)).unwrap(),
+        ThemeName::Dark
+    );
+
+    unsafe { std::env::set_var(THEME_ENV, "neon") };
+    assert!(resolve_theme_name(None, None).is_err());
+
+    unsafe { std::env::remove_var(THEME_ENV) };
+}
+
+#[test]
+fn test_no_color() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let saved = std::env::var_os(NO_COLOR_ENV);
+
+    // An empty NO_COLOR doesn't count, per no-color.org
+    unsafe { std::env::set_var(NO_COLOR_ENV, "") };
+    assert!(!no_color_requested());
+
+    unsafe { std::env::set_var(NO_COLOR_ENV, "1") };
+    assert!(no_color_requested());
+    let theme = Theme::load(None, Some(ThemeName::Light)).unwrap();
+    assert_eq!(theme, Theme::no_color());
+    assert_eq!(theme.mauve, ratatui::style::Color::Reset);
+
+    match saved {
+        Some(value) => unsafe { std::env::set_var(NO_COLOR_ENV, value) },
+        None => unsafe { std::env::remove_var(NO_COLOR_ENV) },
+    }
+}
+
+#[test]
+fn test_matches_tags() {
+    let bookmark = Bookmark {
+        name: "Rust Docs".to_string(),
+        url: "https://doc.rust-lang.org".to_string(),
+        tags: vec!["dev".to_string(), "Rust".to_string()],
+        ..Default::default()
+    };
+
+    assert!(matches_tags::<&str>(&bookmark, &[], TagMatch::All));
+    assert!(matches_tags(&bookmark, &["dev", "rust"], TagMatch::All));
+    assert!(!matches_tags(&bookmark, &["dev", "docs"], TagMatch::All));
+    assert!(matches_tags(&bookmark, &["dev", "docs"], TagMatch::Any));
+    assert!(!matches_tags(&bookmark, &["mail", "docs"], TagMatch::Any));
+}
+
+#[test]
+fn test_list_tag_flags() {
+    let cli = Cli::try_parse_from(["bmk", "list", "--tag", "dev,rust", "--any-tags"]).unwrap();
+    match cli.command {
+        Some(Commands::List { tag, any_tags, .. }) => {
+            assert_eq!(tag, vec!["dev", "rust"]);
+            assert!(any_tags);
+        }
+        other => panic!("unexpected command: {:?}", other),
+    }
+
+    assert!(Cli::try_parse_from(["bmk", "list", "--all-tags", "--any-tags"]).is_err());
+}
+
+#[test]
+fn test_list_json_output() {
+    let bookmarks = vec![
+        Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            tags: vec!["dev".to_string()],
+            visits: 3,
+            ..Default::default()
+        },
+        Bookmark {
+            name: "Mail".to_string(),
+            url: "https://mail.example.com".to_string(),
+            desc: "Inbox".to_string(),
+            ..Default::default()
+        },
+    ];
+
+    let options = ListOptions {
+        json: true,
+        tags: vec!["dev".to_string()],
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    write_bookmark_list(&bookmarks, &options, &mut out).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!([{
+            "name": "GitHub",
+            "url": "https://github.com",
+            "desc": "",
+            "tags": ["dev"],
+        }])
+    );
+
+    let mut out = Vec::new();
+    write_bookmark_list(&Vec::new(), &options, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap().trim(), "[]");
+}
+
+#[test]
+fn test_list_count() {
+    let bookmark = |name: &str, tags: &[&str]| Bookmark {
+        name: name.to_string(),
+        url: format!("https://{}.com", name),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Default::default()
+    };
+    let bookmarks = vec![
+        bookmark("gh", &["dev"]),
+        bookmark("mail", &[]),
+        bookmark("docs", &["dev", "rust"]),
+    ];
+    let list = |options: ListOptions| {
+        let mut out = Vec::new();
+        write_bookmark_list(&bookmarks, &options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    // The summary is the last line and reflects the tag filter, not --limit
+    let output = list(ListOptions {
+        names_only: true,
+        count: true,
+        ..Default::default()
+    });
+    assert_eq!(output, "gh\nmail\ndocs\n# 3 bookmarks\n");
+    let output = list(ListOptions {
+        tags: vec!["dev".to_string()],
+        limit: Some(1),
+        count: true,
+        ..Default::default()
+    });
+    assert!(output.ends_with("showing 1-1 of 2\n# 3 bookmarks (2 matching tag 'dev')\n"));
+
+    assert_eq!(
+        count_summary(
+            1,
+            0,
+            &["dev".to_string(), "rust".to_string()],
+            TagMatch::Any
+        ),
+        "# 1 bookmark (0 matching tags 'dev' or 'rust')"
+    );
+
+    // With --json the counts sit next to the array, which stays the same
+    let output = list(ListOptions {
+        json: true,
+        tags: vec!["rust".to_string()],
+        count: true,
+        ..Default::default()
+    });
+    let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(value["count"], 1);
+    assert_eq!(value["total"], 3);
+    assert_eq!(value["bookmarks"][0]["name"], "docs");
+    assert_eq!(value["bookmarks"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_rank_bookmarks() {
+    let bookmarks = vec![
+        Bookmark {
+            name: "Mail".to_string(),
+            url: "https://mail.example.com".to_string(),
+            ..Default::default()
+        },
+        Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            ..Default::default()
+        },
+        Bookmark {
+            name: "GitLab".to_string(),
+            url: "https://gitlab.com".to_string(),
+            visits: 50,
+            ..Default::default()
+        },
+    ];
+
+    assert_eq!(rank_bookmarks(&bookmarks, "git"), vec![2, 1]);
+    assert_eq!(rank_bookmarks(&bookmarks, "github"), vec![1]);
+    assert!(rank_bookmarks(&bookmarks, "zzz").is_empty());
+}
+
+#[test]
+fn test_export_netscape_round_trip() {
+    let bookmarks = vec![
+        Bookmark {
+            name: "Q&A <forum>".to_string(),
+            url: "https://example.com/?a=1&b=\"2\"".to_string(),
+            desc: "Questions & answers".to_string(),
+            tags: vec!["dev".to_string(), "help".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            ..Default::default()
+        },
+    ];
+
+    let mut out = Vec::new();
+    export_bookmarks(&bookmarks, ExportFormat::Netscape, &mut out).unwrap();
+    let html = String::from_utf8(out).unwrap();
+    assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+    assert!(html.contains(r#"TAGS="dev,help""#));
+    // Everything comes back, with the titles turned into names
+    let mut expected = bookmarks;
+    expected[0].name = "q-a-forum".to_string();
+    expected[1].name = "github".to_string();
+    assert_eq!(parse_netscape_html(&html), expected);
+}
+
+#[test]
+fn test_export_json_and_yaml() {
+    let bookmarks = vec![Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        tags: vec!["dev".to_string()],
+        ..Default::default()
+    }];
+
+    let mut out = Vec::new();
+    export_bookmarks(&bookmarks, ExportFormat::Json, &mut out).unwrap();
+    let parsed: Bookmarks = serde_json::from_slice(&out).unwrap();
+    assert_eq!(parsed, bookmarks);
+
+    let mut out = Vec::new();
+    export_bookmarks(&bookmarks, ExportFormat::Yaml, &mut out).unwrap();
+    let parsed: Bookmarks = serde_yaml::from_slice(&out).unwrap();
+    assert_eq!(parsed, bookmarks);
+}
+
+#[test]
+fn test_export_selection_as_markdown() {
+    let bookmark = |name: &str, url: &str, desc: &str, tags: &[&str]| Bookmark {
+        name: name.to_string(),
+        url: url.to_string(),
+        desc: desc.to_string(),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Default::default()
+    };
+    let bookmarks = vec![
+        bookmark("gh", "https://github.com", "", &["dev"]),
+        bookmark("mail", "https://mail.example.com", "Inbox", &[]),
+        bookmark(
+            "wiki",
+            "https://en.wikipedia.org/wiki/Rust_(language)",
+            "Rust [wiki]",
+            &["dev", "docs"],
+        ),
+    ];
+    let strings = |items: &[&str]| -> Vec<String> { items.iter().map(|s| s.to_string()).collect() };
+    let names =
+        |selected: Bookmarks| -> Vec<String> { selected.into_iter().map(|b| b.name).collect() };
+
+    // Names and tags add to the selection, which keeps file order
+    assert_eq!(
+        names(select_bookmarks(&bookmarks, &[], &[]).unwrap()).len(),
+        3
+    );
+    assert_eq!(
+        names(select_bookmarks(&bookmarks, &strings(&["MAIL"]), &[]).unwrap()),
+        vec!["mail"]
+    );
+    assert_eq!(
+        names(select_bookmarks(&bookmarks, &strings(&["wiki"]), &strings(&["dev"])).unwrap()),
+        vec!["gh", "wiki"]
+    );
+    assert_eq!(
+        names(select_bookmarks(&bookmarks, &[], &strings(&["dev", "docs"])).unwrap()),
+        vec!["wiki"]
+    );
+    let err = select_bookmarks(&bookmarks, &strings(&["nope"]), &[]).unwrap_err();
+    assert_eq!(err.to_string(), "Bookmark 'nope' not found");
+    assert!(select_bookmarks(&bookmarks, &[], &strings(&["none"])).is_err());
+
+    // The description labels a link when there is one, and brackets and
+    // parentheses can't break the link
+    let mut out = Vec::new();
+    export_bookmarks(&bookmarks, ExportFormat::Markdown, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "- [gh](https://github.com)\n\
+         - [Inbox](https://mail.example.com)\n\
+         - [Rust \\[wiki\\]](https://en.wikipedia.org/wiki/Rust_%28language%29)\n"
+    );
+    assert_eq!(to_markdown(&[]), "");
+}
+
+#[test]
+fn test_normalize_tags() {
+    let tags = vec!["Dev".to_string(), "dev".to_string(), " dev ".to_string()];
+    assert_eq!(normalize_tags(tags), vec!["dev"]);
+
+    let tags = vec![
+        " Rust".to_string(),
+        String::new(),
+        "docs".to_string(),
+        "RUST".to_string(),
+    ];
+    assert_eq!(normalize_tags(tags), vec!["rust", "docs"]);
+}
+
+#[test]
+fn test_validate_tag() {
+    assert!(validate_tag("dev").is_ok());
+    assert!(validate_tag("c++ / rust").is_ok());
+    for tag in ["dev,rust", "", " dev", "dev ", "two\nlines"] {
+        assert!(
+            matches!(validate_tag(tag), Err(BookmarkError::InvalidTag { .. })),
+            "{:?}",
+            tag
+        );
+    }
+
+    // `--tag` takes a tag as typed, so a comma reaches add_bookmark and is refused
+    let mut bookmarks = Bookmarks::new();
+    let bookmark = Bookmark {
+        name: "gh".to_string(),
+        url: "https://github.com".to_string(),
+        tags: vec!["dev,git".to_string()],
+        ..Default::default()
+    };
+    let err = add_bookmark(&mut bookmarks, bookmark.clone()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid tag 'dev,git': tags must not contain commas"
+    );
+    assert!(bookmarks.is_empty());
+
+    // Surrounding whitespace is trimmed rather than refused
+    let bookmark = Bookmark {
+        tags: vec!["  Dev ".to_string()],
+        ..bookmark
+    };
+    add_bookmark(&mut bookmarks, bookmark.clone()).unwrap();
+    assert_eq!(bookmarks[0].tags, vec!["dev"]);
+
+    let updated = Bookmark {
+        tags: vec!["a,b".to_string()],
+        ..bookmark
+    };
+    assert!(update_bookmark(&mut bookmarks, 0, updated).is_err());
+    assert_eq!(bookmarks[0].tags, vec!["dev"]);
+
+    assert!(rename_tag(&mut bookmarks, "dev", "dev,ops").is_err());
+    assert_eq!(bookmarks[0].tags, vec!["dev"]);
+}
+
+#[test]
+fn test_add_and_update_normalize_tags() {
+    let mut bookmarks = Vec::new();
+    let bookmark = Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        tags: vec!["Dev".to_string(), " code".to_string(), "dev".to_string()],
+        ..Default::default()
+    };
+    add_bookmark(&mut bookmarks, bookmark.clone()).unwrap();
+    assert_eq!(bookmarks[0].tags, vec!["dev", "code"]);
+
+    let updated = Bookmark {
+        tags: vec!["Code ".to_string(), "CODE".to_string()],
+        ..bookmark
+    };
+    update_bookmark(&mut bookmarks, 0, updated).unwrap();
+    assert_eq!(bookmarks[0].tags, vec!["code"]);
+}
+
+#[test]
+fn test_rename_tag() {
+    let mut bookmarks = vec![
+        Bookmark {
+            name: "MDN".to_string(),
+            url: "https://developer.mozilla.org".to_string(),
+            tags: vec!["JS".to_string(), "docs".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "Node".to_string(),
+            url: "https://nodejs.org".to_string(),
+            tags: vec!["javascript".to_string(), "js".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "Rust".to_string(),
+            url: "https://rust-lang.org".to_string(),
+            tags: vec!["rust".to_string()],
+            ..Default::default()
+        },
+    ];
+
+    assert_eq!(rename_tag(&mut bookmarks, "js", "javascript").unwrap(), 2);
+    assert_eq!(bookmarks[0].tags, vec!["javascript", "docs"]);
+    assert_eq!(bookmarks[1].tags, vec!["javascript"]);
+    assert_eq!(bookmarks[2].tags, vec!["rust"]);
+
+    assert_eq!(rename_tag(&mut bookmarks, "js", "javascript").unwrap(), 0);
+    assert!(rename_tag(&mut bookmarks, "rust", "  ").is_err());
+}
+
+#[test]
+fn test_tag_rename_subcommand_parses() {
+    for cmd in ["tags", "tag"] {
+        let cli = Cli::try_parse_from(["bmk", cmd, "rename", "js", "javascript"]).unwrap();
+        match cli.command {
+            Some(Commands::Tags {
+                action: Some(TagsCommand::Rename { from, to }),
+            }) => assert_eq!((from.as_str(), to.as_str()), ("js", "javascript")),
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    let cli = Cli::try_parse_from(["bmk", "tags"]).unwrap();
+    assert!(matches!(cli.command, Some(Commands::Tags { action: None })));
+}
+
+#[test]
+fn test_delete_by_tag() {
+    let mut bookmarks = vec![
+        Bookmark {
+            name: "Board".to_string(),
+            url: "https://board.example.com".to_string(),
+            tags: vec!["Project-X".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            tags: vec!["dev".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "Specs".to_string(),
+            url: "https://specs.example.com".to_string(),
+            tags: vec!["docs".to_string(), "project-x".to_string()],
+            ..Default::default()
+        },
+    ];
+
+    assert!(delete_by_tag(&mut bookmarks, "missing").is_empty());
+    assert_eq!(bookmarks.len(), 3);
+
+    assert_eq!(
+        delete_by_tag(&mut bookmarks, "PROJECT-X"),
+        vec!["Board", "Specs"]
+    );
+    assert_eq!(bookmarks.len(), 1);
+    assert_eq!(bookmarks[0].name, "GitHub");
+}
+
+#[test]
+fn test_delete_subcommand_requires_name_or_tag() {
+    assert!(Cli::try_parse_from(["bmk", "delete"]).is_err());
+    assert!(Cli::try_parse_from(["bmk", "delete", "gh", "--tag", "dev"]).is_err());
+
+    let cli = Cli::try_parse_from(["bmk", "delete", "--tag", "dev", "--yes"]).unwrap();
+    match cli.command {
+        Some(Commands::Delete { name, tag, yes }) => {
+            assert_eq!(name, None);
+            assert_eq!(tag.as_deref(), Some("dev"));
+            assert!(yes);
+        }
+        other => panic!("unexpected command: {:?}", other),
+    }
+}
+
+#[test]
+fn test_prompt_bookmark_fields() {
+    let bookmark = Bookmark {
+        name: "GitHub".to_string(),
+        ..Default::default()
+    };
+    let mut input = std::io::Cursor::new("\n  github.com \nCode hosting\ndev, code\n");
+    let mut output = Vec::new();
+    let bookmark = prompt_bookmark_fields(bookmark, &mut input, &mut output).unwrap();
+
+    assert_eq!(bookmark.url, "github.com");
+    assert_eq!(bookmark.desc, "Code hosting");
+    assert_eq!(bookmark.tags, vec!["dev", "code"]);
+    let prompts = String::from_utf8(output).unwrap();
+    assert_eq!(prompts.matches("URL: ").count(), 2);
+
+    // Fields given as flags are not asked for
+    let bookmark = Bookmark {
+        name: "Docs".to_string(),
+        desc: "Rust docs".to_string(),
+        ..Default::default()
+    };
+    let mut input = std::io::Cursor::new("doc.rust-lang.org\n\n");
+    let mut output = Vec::new();
+    let bookmark = prompt_bookmark_fields(bookmark, &mut input, &mut output).unwrap();
+    assert_eq!(bookmark.desc, "Rust docs");
+    assert!(bookmark.tags.is_empty());
+    assert!(!String::from_utf8(output).unwrap().contains("Description"));
+
+    // End of input without a URL
+    let bookmark = Bookmark {
+        name: "Empty".to_string(),
+        ..Default::default()
+    };
+    let mut input = std::io::Cursor::new("\n");
+    assert!(prompt_bookmark_fields(bookmark, &mut input, &mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_open_all_dry_run_leaves_stats_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let bookmarks = vec![
+        Bookmark {
+            name: "Mail".to_string(),
+            url: "https://mail.example.com".to_string(),
+            tags: vec!["dashboard".to_string()],
+            ..Default::default()
+        },
+        Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            ..Default::default()
+        },
+    ];
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+
+    let options = OpenOptions {
+        mode: OpenMode::Print,
+        ..Default::default()
+    };
+    handle_open_all_command(&path, "Dashboard", &options).unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap(), bookmarks);
+
+    assert!(handle_open_all_command(&path, "missing", &options).is_err());
+}
+
+#[test]
+fn test_open_all_flags() {
+    let cli =
+        Cli::try_parse_from(["bmk", "open", "--all", "--tag", "dashboard", "--dry-run"]).unwrap();
+    match cli.command {
+        Some(Commands::Open {
+            name, tag, dry_run, ..
+        }) => {
+            assert!(name.is_empty());
+            assert_eq!(tag.as_deref(), Some("dashboard"));
+            assert!(dry_run);
+        }
+        other => panic!("unexpected command: {:?}", other),
+    }
+
+    assert!(Cli::try_parse_from(["bmk", "open", "--all"]).is_err());
+    assert!(Cli::try_parse_from(["bmk", "open", "--tag", "dashboard"]).is_err());
+    assert!(Cli::try_parse_from(["bmk", "open", "gh", "--all", "--tag", "x"]).is_err());
+}
+
+#[test]
+fn test_move_bookmark() {
+    let mut bookmarks = rename_fixture();
+    move_bookmark(&mut bookmarks, 0, 2);
+    let names: Vec<&str> = bookmarks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["docs", "mail", "gh"]);
+
+    move_bookmark(&mut bookmarks, 2, 1);
+    let names: Vec<&str> = bookmarks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["docs", "gh", "mail"]);
+
+    move_bookmark(&mut bookmarks, 1, 5);
+    let names: Vec<&str> = bookmarks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["docs", "gh", "mail"]);
+}
+
+#[test]
+fn test_list_pagination() {
+    let bookmarks = rename_fixture();
+    let list = |options: ListOptions| {
+        let mut out = Vec::new();
+        write_bookmark_list(&bookmarks, &options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let page = list(ListOptions {
+        limit: Some(2),
+        ..Default::default()
+    });
+    let lines: Vec<&str> = page.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("gh "));
+    assert!(lines[1].starts_with("docs "));
+    assert_eq!(lines[2], "showing 1-2 of 3");
+
+    let page = list(ListOptions {
+        names_only: true,
+        limit: Some(2),
+        offset: 2,
+        ..Default::default()
+    });
+    // The footer is only added to the table, not to machine-readable output
+    assert_eq!(page, "mail\n");
+
+    let page = list(ListOptions {
+        offset: 5,
+        ..Default::default()
+    });
+    assert_eq!(page, "showing 0 of 3\n");
+
+    // No footer without pagination
+    assert_eq!(
+        list(ListOptions {
+            names_only: true,
+            ..Default::default()
+        }),
+        "gh\ndocs\nmail\n"
+    );
+}
+
+#[test]
+fn test_created_and_updated_timestamps() {
+    let mut bookmarks: Bookmarks = Vec::new();
+    add_bookmark(
+        &mut bookmarks,
+        Bookmark {
+            name: "Example".to_string(),
+            url: "https://example.com".to_string(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let created = bookmarks[0].created_at.clone().unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(&created).is_ok());
+    assert_eq!(bookmarks[0].updated_at, None);
+
+    // An edit built from scratch still keeps the original creation time
+    let edited = Bookmark {
+        name: "Example".to_string(),
+        url: "https://example.org".to_string(),
+        ..Default::default()
+    };
+    update_bookmark(&mut bookmarks, 0, edited).unwrap();
+    assert_eq!(bookmarks[0].created_at.as_deref(), Some(created.as_str()));
+    assert!(bookmarks[0].updated_at.is_some());
+}
+
+#[test]
+fn test_list_sort_recent() {
+    let mut bookmarks = rename_fixture();
+    bookmarks[0].created_at = Some("2024-01-01T09:00:00+00:00".to_string());
+    bookmarks[2].created_at = Some("2025-06-01T09:00:00+02:00".to_string());
+
+    let mut out = Vec::new();
+    let options = ListOptions {
+        names_only: true,
+        sort: ListSort::Recent,
+        ..Default::default()
+    };
+    write_bookmark_list(&bookmarks, &options, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "mail\ngh\ndocs\n");
+}
+
+#[test]
+fn test_compute_stats() {
+    assert_eq!(compute_stats(&Vec::new()), Stats::default());
+
+    let tagged = |name: &str, tags: &[&str], visits: u64| Bookmark {
+        name: name.to_string(),
+        url: format!("https://{}.example.com", name),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        visits,
+        ..Default::default()
+    };
+    let bookmarks = vec![
+        tagged("a", &["dev", "rust", "t1"], 3),
+        tagged("b", &["dev", "t2", "t3"], 0),
+        tagged("c", &["dev", "rust", "t4"], 7),
+    ];
+
+    let stats = compute_stats(&bookmarks);
+    assert_eq!(stats.total, 3);
+    assert_eq!(stats.unique_tags, 6);
+    assert_eq!(stats.total_opens, 10);
+    assert_eq!(stats.top_tags.len(), TOP_TAGS);
+    assert_eq!(stats.top_tags[0], ("dev".to_string(), 3));
+    assert_eq!(stats.top_tags[1], ("rust".to_string(), 2));
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn test_check_links() {
+    use bmk::health::{LinkStatus, check_links};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    // Minimal HTTP server: /ok answers 200, anything else 404
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let status = if request_line.contains(" /ok ") {
+                "200 OK"
+            } else {
+                "404 Not Found"
+            };
+            let mut stream = &stream;
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+        }
+    });
+
+    // A port that nothing listens on
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let bookmark = |name: &str, url: String| Bookmark {
+        name: name.to_string(),
+        url,
+        ..Default::default()
+    };
+    let bookmarks = [
+        bookmark("ok", format!("http://{}/ok", addr)),
+        bookmark("missing", format!("http://{}/missing", addr)),
+        bookmark("down", format!("http://{}/", closed)),
+        bookmark("mail", "mailto:me@example.com".to_string()),
+    ];
+    let refs: Vec<&Bookmark> = bookmarks.iter().collect();
+
+    let results = check_links(&refs, Duration::from_secs(5)).unwrap();
+    assert_eq!(results[0], ("ok".to_string(), LinkStatus::Ok(200)));
+    assert_eq!(results[1], ("missing".to_string(), LinkStatus::Broken(404)));
+    assert!(matches!(results[2].1, LinkStatus::Unreachable(_)));
+    assert_eq!(results[3], ("mail".to_string(), LinkStatus::Skipped));
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn test_fetch_text() {
+    use bmk::health::{fetch_text, is_text};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    // /json answers JSON, /png binary, anything else 404
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let (status, content_type, body): (&str, &str, &[u8]) =
+                if request_line.contains(" /json ") {
+                    (
+                        "200 OK",
+                        "application/json; charset=utf-8",
+                        b"{\"ok\":true}",
+                    )
+                } else if request_line.contains(" /png ") {
+                    ("200 OK", "image/png", b"\x89PNG\0")
+                } else {
+                    ("404 Not Found", "text/plain", b"")
+                };
+            let mut stream = &stream;
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                content_type,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+
+    let url = |path: &str| format!("http://{}/{}", addr, path);
+    assert_eq!(fetch_text(&url("json"), false).unwrap(), b"{\"ok\":true}");
+    let err = fetch_text(&url("png"), false).unwrap_err();
+    assert!(err.to_string().contains("--force"), "{}", err);
+    assert_eq!(fetch_text(&url("png"), true).unwrap(), b"\x89PNG\0");
+    assert!(fetch_text(&url("missing"), false).is_err());
+    assert!(fetch_text("mailto:me@example.com", true).is_err());
+
+    assert!(is_text(Some("text/html"), b""));
+    assert!(is_text(Some("application/vnd.api+json"), b""));
+    assert!(!is_text(Some("application/octet-stream"), b"abc"));
+    // Without a content type, the body decides
+    assert!(is_text(None, b"plain"));
+    assert!(!is_text(None, b"a\0b"));
+
+    let cli = Cli::try_parse_from(["bmk", "open", "api", "--cat", "--force"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Commands::Open {
+            cat: true,
+            force: true,
+            ..
+        })
+    ));
+    assert!(Cli::try_parse_from(["bmk", "--force", "api"]).is_err());
+    assert!(Cli::try_parse_from(["bmk", "--cat", "--print", "api"]).is_err());
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn test_extract_title() {
+    use bmk::health::extract_title;
+
+    let html = "<html><head><TITLE lang=\"en\">\n  Rust &amp; Cargo\n  docs </TITLE></head></html>";
+    assert_eq!(extract_title(html).as_deref(), Some("Rust & Cargo docs"));
+    assert_eq!(extract_title("<title>   </title>"), None);
+    assert_eq!(extract_title("<html><body>no title</body></html>"), None);
+}
+
+#[test]
+fn test_encrypted_file_format() {
+    assert!(is_encrypted_path(Path::new("bookmarks.yaml.age")));
+    assert!(!is_encrypted_path(Path::new("bookmarks.yaml")));
+    assert_eq!(
+        FileFormat::from_path(Path::new("bookmarks.json.age")).unwrap(),
+        FileFormat::Json
+    );
+    assert_eq!(
+        FileFormat::from_path(Path::new("bookmarks.age")).unwrap(),
+        FileFormat::Yaml
+    );
+}
+
+#[cfg(not(feature = "encryption"))]
+#[test]
+fn test_encrypted_file_requires_feature() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml.age");
+    std::fs::write(&path, "age-encryption.org/v1\n").unwrap();
+
+    let err = format!("{:#}", load_bookmarks_from(&path).unwrap_err());
+    assert!(err.contains("without the `encryption` feature"));
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_encrypted_round_trip() {
+    use bmk::encryption::PASSPHRASE_ENV;
+
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml.age");
+    let bookmarks = rename_fixture();
+
+    unsafe { std::env::set_var(PASSPHRASE_ENV, "correct horse") };
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    let raw = std::fs::read(&path).unwrap();
+    assert!(raw.starts_with(b"age-encryption.org/v1"));
+    assert!(!String::from_utf8_lossy(&raw).contains("example.com"));
+    assert_eq!(load_bookmarks_from(&path).unwrap(), bookmarks);
+
+    unsafe { std::env::set_var(PASSPHRASE_ENV, "battery staple") };
+    let err = format!("{:#}", load_bookmarks_from(&path).unwrap_err());
+    assert!(err.contains("Wrong passphrase"), "{}", err);
+
+    unsafe { std::env::remove_var(PASSPHRASE_ENV) };
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_restore_encrypted_backup() {
+    use bmk::encryption::PASSPHRASE_ENV;
+
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml.age");
+
+    unsafe { std::env::set_var(PASSPHRASE_ENV, "correct horse") };
+    save_bookmarks_to(&path, &rename_fixture()).unwrap();
+    save_bookmarks_to(&path, &Vec::new()).unwrap();
+
+    let backups = list_backups(&path).unwrap();
+    restore_backup(&path, &backups[0]).unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap(), rename_fixture());
+    // The restored file stays encrypted
+    assert!(
+        std::fs::read(&path)
+            .unwrap()
+            .starts_with(b"age-encryption.org/v1")
+    );
+
+    unsafe { std::env::set_var(PASSPHRASE_ENV, "battery staple") };
+    let backups = list_backups(&path).unwrap();
+    let err = format!("{:#}", restore_backup(&path, &backups[0]).unwrap_err());
+    assert!(err.contains("Failed to decrypt backup"), "{}", err);
+
+    unsafe { std::env::remove_var(PASSPHRASE_ENV) };
+}
+
+#[test]
+fn test_expand_url_template() {
+    assert_eq!(
+        expand_url_template("https://github.com/search?q={query}", "rust cli"),
+        "https://github.com/search?q=rust%20cli"
+    );
+    assert_eq!(
+        expand_url_template("https://example.com/{query}?q={query}", "a&b/c-d"),
+        "https://example.com/a%26b%2Fc-d?q=a%26b%2Fc-d"
+    );
+    assert_eq!(
+        expand_url_template("https://example.com", "ignored"),
+        "https://example.com"
+    );
+}
+
+#[test]
+fn test_resolve_launch_args() {
+    let mut bookmarks = rename_fixture();
+    bookmarks[0].url = "https://github.com/search?q={query}".to_string();
+    let args = |s: &str| -> Vec<String> { s.split(' ').map(String::from).collect() };
+
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args("gh rust cli"), DEFAULT_MIN_SCORE),
+        Some((0, Some("rust cli".to_string())))
+    );
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args("gh"), DEFAULT_MIN_SCORE),
+        Some((0, None))
+    );
+
+    // Without a template, all words form a single query as before
+    bookmarks[1].desc = "api reference".to_string();
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args("api reference"), DEFAULT_MIN_SCORE),
+        Some((1, None))
+    );
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args("docs zzz"), DEFAULT_MIN_SCORE),
+        None
+    );
+
+    // A base URL takes the rest as a path, but only when picked by name
+    bookmarks[2].url = "https://mail.example.com/inbox/".to_string();
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args("mail PROJ-123"), DEFAULT_MIN_SCORE),
+        Some((2, Some("PROJ-123".to_string())))
+    );
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args("mal PROJ-123"), DEFAULT_MIN_SCORE),
+        None
+    );
+
+    // The placeholder wins over a trailing slash
+    bookmarks[2].url = "https://mail.example.com/{query}/".to_string();
+    assert!(!is_base_url(&bookmarks[2].url));
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args("mal rust"), DEFAULT_MIN_SCORE),
+        Some((2, Some("rust".to_string())))
+    );
+}
+
+#[test]
+fn test_append_url_path() {
+    assert!(is_base_url("https://jira.example.com/browse/"));
+    assert!(!is_base_url("https://jira.example.com/browse"));
+    assert_eq!(
+        append_url_path("https://jira.example.com/browse/", "PROJ-123"),
+        "https://jira.example.com/browse/PROJ-123"
+    );
+    assert_eq!(
+        append_url_path("https://wiki.example.com/", "/team//notes/ q&a 2024"),
+        "https://wiki.example.com/team/notes/q%26a/2024"
+    );
+    assert_eq!(
+        append_url_path("https://wiki.example.com/", " "),
+        "https://wiki.example.com/"
+    );
+}
+
+#[test]
+fn test_bookmark_aliases() {
+    let mut bookmarks = rename_fixture();
+    let jira = Bookmark {
+        name: "jira".to_string(),
+        url: "https://jira.example.com".to_string(),
+        aliases: vec![" tickets ".to_string(), "tickets".to_string()],
+        ..Default::default()
+    };
+    add_bookmark(&mut bookmarks, jira).unwrap();
+    assert_eq!(bookmarks[3].aliases, vec!["tickets"]);
+
+    assert_eq!(find_bookmark(&bookmarks, "tickets"), Some(3));
+    assert_eq!(
+        find_best_match(&bookmarks, "tckts"),
+        Some(("jira".to_string(), "https://jira.example.com".to_string()))
+    );
+
+    // Aliases must not clash with names or aliases of other bookmarks
+    let mut clash = bookmarks[0].clone();
+    clash.aliases = vec!["tickets".to_string()];
+    let err = update_bookmark(&mut bookmarks, 0, clash).unwrap_err();
+    assert_eq!(err.to_string(), "Alias 'tickets' is already used by 'jira'");
+
+    let mut clash = bookmarks[0].clone();
+    clash.aliases = vec!["docs".to_string()];
+    assert!(update_bookmark(&mut bookmarks, 0, clash).is_err());
+
+    // Lookups ignore case, so the checks do too
+    let mut clash = bookmarks[0].clone();
+    clash.aliases = vec!["TICKETS".to_string()];
+    let err = update_bookmark(&mut bookmarks, 0, clash).unwrap_err();
+    assert_eq!(err.to_string(), "Alias 'TICKETS' is already used by 'jira'");
+    let other = Bookmark {
+        name: "other".to_string(),
+        url: "https://other.com".to_string(),
+        aliases: vec!["JIRA".to_string()],
+        ..Default::default()
+    };
+    assert!(add_bookmark(&mut bookmarks, other).is_err());
+    let mut own = bookmarks[3].clone();
+    own.aliases = vec!["JIRA".to_string()];
+    assert_eq!(
+        update_bookmark(&mut bookmarks, 3, own),
+        Err(BookmarkError::AliasIsOwnName("JIRA".to_string()))
+    );
+
+    // A bookmark may keep its own aliases when edited
+    let mut edited = bookmarks[3].clone();
+    edited.desc = "Issue tracker".to_string();
+    update_bookmark(&mut bookmarks, 3, edited).unwrap();
+}
+
+#[test]
+fn test_merge_command_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    save_bookmarks_to(&path, &rename_fixture()).unwrap();
+    let before = std::fs::read_to_string(&path).unwrap();
+    let backups = list_backups(&path).unwrap().len();
+
+    let missing = dir.path().join("nosuch.yaml");
+    let err = handle_merge_command(&path, &missing, MergeStrategy::Keep, false).unwrap_err();
+    assert!(err.to_string().contains("does not exist"), "{}", err);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+    assert_eq!(list_backups(&path).unwrap().len(), backups);
+}
+
+#[test]
+fn test_merge_bookmarks() {
+    let incoming = || -> Bookmarks {
+        vec![
+            Bookmark {
+                name: "gh".to_string(),
+                url: "https://github.com".to_string(),
+                ..Default::default()
+            },
+            Bookmark {
+                name: "wiki".to_string(),
+                url: "https://wiki.example.com".to_string(),
+                aliases: vec!["docs".to_string(), "kb".to_string()],
+                ..Default::default()
+            },
+        ]
+    };
+    let names = |bookmarks: &Bookmarks| -> Vec<String> {
+        bookmarks.iter().map(|b| b.name.clone()).collect()
+    };
+
+    let mut bookmarks = rename_fixture();
+    let report = merge_bookmarks(&mut bookmarks, incoming(), MergeStrategy::Keep, false);
+    assert_eq!(
+        report,
+        MergeReport {
+            added: 1,
+            skipped: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(names(&bookmarks), vec!["gh", "docs", "mail", "wiki"]);
+    assert_eq!(bookmarks[0].url, "https://gh.example.com");
+    // `docs` already names a bookmark, so only the free alias survives
+    assert_eq!(bookmarks[3].aliases, vec!["kb"]);
+
+    let mut bookmarks = rename_fixture();
+    let report = merge_bookmarks(&mut bookmarks, incoming(), MergeStrategy::Overwrite, false);
+    assert_eq!(report.overwritten, 1);
+    assert_eq!(names(&bookmarks), vec!["gh", "docs", "mail", "wiki"]);
+    assert_eq!(bookmarks[0].url, "https://github.com");
+
+    let mut bookmarks = rename_fixture();
+    let report = merge_bookmarks(&mut bookmarks, incoming(), MergeStrategy::Rename, false);
+    assert_eq!(report.renamed, 1);
+    assert_eq!(
+        names(&bookmarks),
+        vec!["gh", "docs", "mail", "gh-2", "wiki"]
+    );
+
+    // A URL that is already bookmarked is skipped whatever the name, except
+    // by the bookmark it overwrites
+    let same_urls = || -> Bookmarks {
+        vec![
+            Bookmark {
+                name: "gh".to_string(),
+                url: "https://GH.example.com/".to_string(),
+                ..Default::default()
+            },
+            Bookmark {
+                name: "mailbox".to_string(),
+                url: "https://mail.example.com".to_string(),
+                ..Default::default()
+            },
+        ]
+    };
+    let mut bookmarks = rename_fixture();
+    let report = merge_bookmarks(&mut bookmarks, same_urls(), MergeStrategy::Rename, false);
+    assert_eq!(
+        report,
+        MergeReport {
+            skipped_duplicate: 2,
+            ..Default::default()
+        }
+    );
+    assert_eq!(bookmarks, rename_fixture());
+
+    let report = merge_bookmarks(&mut bookmarks, same_urls(), MergeStrategy::Overwrite, false);
+    assert_eq!((report.overwritten, report.skipped_duplicate), (1, 1));
+
+    let mut bookmarks = rename_fixture();
+    let report = merge_bookmarks(&mut bookmarks, same_urls(), MergeStrategy::Rename, true);
+    assert_eq!((report.renamed, report.added), (1, 1));
+    assert_eq!(bookmarks.len(), 5);
+}
+
+#[test]
+fn test_import_bookmarks_skips_duplicates() {
+    let exported = || -> Bookmarks {
+        [
+            ("gh", "https://github.com/"),
+            ("docs", "https://docs.rs"),
+            ("docs", "https://doc.rust-lang.org"),
+            ("again", "https://docs.rs"),
+        ]
+        .iter()
+        .map(|(name, url)| Bookmark {
+            name: name.to_string(),
+            url: url.to_string(),
+            ..Default::default()
+        })
+        .collect()
+    };
+    let mut bookmarks = vec![Bookmark {
+        name: "hub".to_string(),
+        url: "https://github.com".to_string(),
+        ..Default::default()
+    }];
+
+    // Duplicates of existing bookmarks and within the import are skipped
+    let report = import_bookmarks(&mut bookmarks, exported(), false);
+    assert_eq!(
+        report,
+        ImportReport {
+            added: 1,
+            skipped_duplicate: 2,
+            renamed: 1,
+            skipped_invalid: 0,
+        }
+    );
+    let names: Vec<&str> = bookmarks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["hub", "docs", "docs-2"]);
+
+    // Importing the same export again changes nothing
+    let report = import_bookmarks(&mut bookmarks, exported(), false);
+    assert_eq!(report.skipped_duplicate, 4);
+    assert_eq!(bookmarks.len(), 3);
+
+    let report = import_bookmarks(&mut bookmarks, exported(), true);
+    assert_eq!((report.added, report.renamed), (2, 2));
+    assert_eq!(bookmarks.len(), 7);
+}
+
+#[test]
+fn test_import_bookmarks_skips_invalid() {
+    let html = "<DT><A HREF=\"https://a.example\" TAGS=\"ok,bad\u{7}tag\">A</A>\n\
+                <DT><A HREF=\"https://b.example\">B</A>";
+    let mut bookmarks = Bookmarks::new();
+
+    // One bad entry doesn't stop the rest from being imported
+    let report = import_bookmarks(&mut bookmarks, parse_netscape_html(html), false);
+    assert_eq!((report.added, report.skipped_invalid), (1, 1));
+    let names: Vec<&str> = bookmarks.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, vec!["b"]);
+}
+
+#[test]
+fn test_malformed_yaml_is_a_parse_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::write(
+        &path,
+        "- name: Example\n  url: https://example.com\n- name: [broken\n",
+    )
+    .unwrap();
+
+    let err = load_bookmarks_from(&path).unwrap_err();
+    let parse_error = err.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(parse_error.format, FileFormat::Yaml);
+    assert_eq!(parse_error.line, Some(3));
+    assert!(format!("{:#}", err).contains("Invalid YAML"));
+
+    let corrupt = recover_corrupt_file(&path).unwrap();
+    assert_eq!(corrupt, dir.path().join("bookmarks.yaml.corrupt"));
+    assert!(corrupt.exists());
+    assert!(load_bookmarks_from(&path).unwrap().is_empty());
+}
+
+#[test]
+fn test_malformed_json_is_a_parse_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.json");
+    std::fs::write(&path, "[\n  {\"name\": \"Example\",}\n]\n").unwrap();
+
+    let err = load_bookmarks_from(&path).unwrap_err();
+    let parse_error = err.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(parse_error.format, FileFormat::Json);
+    assert_eq!((parse_error.line, parse_error.column), (Some(2), Some(22)));
+}
+
+#[test]
+fn test_list_aligns_wide_characters() {
+    let bookmarks: Bookmarks = ["日本語", "gh"]
+        .iter()
+        .map(|name| Bookmark {
+            name: name.to_string(),
+            url: "https://example.com".to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    write_bookmark_list(&bookmarks, &ListOptions::default(), &mut out).unwrap();
+    // The three CJK characters take six columns, so `gh` is padded to six as well
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "日本語  https://example.com\ngh      https://example.com\n"
+    );
+    assert_eq!(pad_to_width("ブック", 8), "ブック  ");
+    assert_eq!(pad_to_width("toolong", 3), "toolong");
+}
+
+#[test]
+fn test_truncate_to_width() {
+    // Each character takes two columns and three bytes, so no cut may split one
+    let text = "日本語のブックマーク";
+    assert_eq!(truncate_to_width(text, 20), text);
+    assert_eq!(truncate_to_width(text, 7), "日本語…");
+    assert_eq!(truncate_to_width(text, 6), "日本…");
+    assert_eq!(truncate_to_width(text, 2), "…");
+    assert_eq!(truncate_to_width(text, 1), "…");
+    assert_eq!(truncate_to_width(text, 0), "");
+    assert_eq!(truncate_to_width("Закладки 🔖 docs", 12), "Закладки 🔖…");
+    // The emoji takes two columns and doesn't fit next to the ellipsis
+    assert_eq!(truncate_to_width("Закладки 🔖 docs", 11), "Закладки …");
+    assert_eq!(truncate_to_width("short", 10), "short");
+}
+
+#[test]
+fn test_fuzzy_scorer() {
+    let chars = |q: &str| q.chars().collect::<Vec<char>>();
+
+    // An empty pattern matches anything with no points, a missing character fails
+    assert_eq!(fuzzy_match(&chars(""), "github"), 0);
+    assert_eq!(fuzzy_match(&chars("gx"), "github"), -1);
+    assert_eq!(fuzzy_match(&chars("bg"), "github"), -1);
+
+    // Each character earns 10, +20 at a word start, up to +10 near the start
+    // of the text, and +10 more per character in an unbroken run
+    assert_eq!(fuzzy_match(&chars("g"), "github"), 40);
+    assert_eq!(fuzzy_match(&chars("gh"), "github"), 57);
+    assert_eq!(fuzzy_match(&chars("gi"), "github"), 69);
+    assert!(fuzzy_match(&chars("ab"), "xab") > fuzzy_match(&chars("ab"), "xaxb"));
+
+    // A word start beats an earlier position inside a word
+    for separator in ['/', '.', '-', '_', ' '] {
+        let text = format!("ab{}cd", separator);
+        assert!(fuzzy_match(&chars("c"), &text) > fuzzy_match(&chars("b"), &text));
+    }
+
+    let bookmark = |name: &str, url: &str| Bookmark {
+        name: name.to_string(),
+        url: url.to_string(),
+        ..Default::default()
+    };
+    // A match in the name outranks a better one in the URL, which outranks the
+    // description and then the tags
+    let by_name = bookmark("gthb", "https://example.com");
+    let by_url = bookmark("code", "https://github.com");
+    assert!(
+        fuzzy_score(&chars("github"), &by_name, SearchScope::Fields) < 0,
+        "not a subsequence of the name"
+    );
+    assert!(
+        fuzzy_score(&chars("gh"), &by_name, SearchScope::Fields)
+            > fuzzy_score(&chars("gh"), &by_url, SearchScope::Fields)
+    );
+    let by_desc = Bookmark {
+        desc: "gh mirror".to_string(),
+        ..bookmark("code", "https://example.com")
+    };
+    let by_tag = Bookmark {
+        tags: vec!["gh".to_string()],
+        ..bookmark("code", "https://example.com")
+    };
+    let scores: Vec<i64> = [&by_url, &by_desc, &by_tag]
+        .iter()
+        .map(|bm| fuzzy_score(&chars("gh"), bm, SearchScope::Fields))
+        .collect();
+    assert!(scores[0] > scores[1] && scores[1] > scores[2] && scores[2] >= 0);
+    assert_eq!(fuzzy_score(&chars("zz"), &by_tag, SearchScope::Fields), -1);
+}
+
+#[test]
+fn test_fuzzy_prefers_exact_case() {
+    let bookmarks: Bookmarks = ["api-client", "API"]
+        .iter()
+        .map(|name| Bookmark {
+            name: name.to_string(),
+            url: "https://example.com".to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+    // Both match regardless of case, but the exact-case entry ranks first
+    assert_eq!(rank_bookmarks(&bookmarks, "API"), vec![1, 0]);
+    assert_eq!(rank_bookmarks(&bookmarks, "api"), vec![0, 1]);
+}
+
+#[test]
+fn test_fuzzy_acronym_bonus() {
+    let bookmarks: Bookmarks = [
+        "gc-something-else",
+        "google-calendar",
+        "blog",
+        "bookmark-launcher",
+    ]
+    .iter()
+    .map(|name| Bookmark {
+        name: name.to_string(),
+        url: "https://example.com".to_string(),
+        ..Default::default()
+    })
+    .collect();
+
+    assert_eq!(rank_bookmarks(&bookmarks, "gc")[0], 1);
+    assert_eq!(rank_bookmarks(&bookmarks, "bl")[0], 3);
+    // Plain subsequence matches still count
+    assert_eq!(rank_bookmarks(&bookmarks, "gcal"), vec![1]);
+}
+
+#[test]
+fn test_find_confident_match_threshold() {
+    let bookmarks = vec![Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        desc: "Code hosting and collaboration for software projects".to_string(),
+        ..Default::default()
+    }];
+
+    // A dropped letter still finds the intended bookmark
+    assert_eq!(
+        find_confident_match(&bookmarks, "githb", DEFAULT_MIN_SCORE),
+        Some(0)
+    );
+    assert_eq!(
+        find_confident_match(&bookmarks, "gthub", DEFAULT_MIN_SCORE),
+        Some(0)
+    );
+    // Letters scattered through the description are not confident
+    assert_eq!(
+        find_confident_match(&bookmarks, "oolo", DEFAULT_MIN_SCORE),
+        None
+    );
+    assert_eq!(
+        find_confident_match(&bookmarks, "ssrt", DEFAULT_MIN_SCORE),
+        None
+    );
+    assert_eq!(find_best_match(&bookmarks, "ssrt"), None);
+    assert_eq!(find_confident_match(&bookmarks, "ssrt", 0), Some(0));
+}
+
+#[test]
+fn test_ambiguous_matches() {
+    let bookmark = |name: &str| Bookmark {
+        name: name.to_string(),
+        url: format!("https://{}.example.com", name),
+        ..Default::default()
+    };
+    let bookmarks = vec![
+        bookmark("rust-docs"),
+        bookmark("rust-blog"),
+        bookmark("python"),
+    ];
+
+    // Two names matching the same prefix tie
+    let candidates = ambiguous_matches(&bookmarks, "rust");
+    assert_eq!(candidates.len(), 2);
+    assert!(candidates.contains(&0) && candidates.contains(&1));
+    // A single clear winner is not ambiguous
+    assert!(ambiguous_matches(&bookmarks, "python").is_empty());
+    assert!(ambiguous_matches(&bookmarks, "zzz").is_empty());
+
+    // An exact name resolves without considering close matches
+    let args = vec!["rust-docs".to_string()];
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args, DEFAULT_MIN_SCORE),
+        Some((0, None))
+    );
+}
+
+#[test]
+fn test_choose_bookmark() {
+    let bookmarks = vec![
+        Bookmark {
+            name: "rust-docs".to_string(),
+            url: "https://doc.rust-lang.org".to_string(),
+            ..Default::default()
+        },
+        Bookmark {
+            name: "rust-blog".to_string(),
+            url: "https://blog.rust-lang.org".to_string(),
+            ..Default::default()
+        },
+    ];
+
+    // Invalid answers are asked again
+    let mut input = std::io::Cursor::new("3\nx\n2\n");
+    let mut output = Vec::new();
+    assert_eq!(
+        choose_bookmark(&bookmarks, &[1, 0], &mut input, &mut output).unwrap(),
+        0
+    );
+    let text = String::from_utf8(output).unwrap();
+    assert!(text.contains(" 1) rust-blog  https://blog.rust-lang.org"));
+    assert_eq!(text.matches("Open which? [1-2]: ").count(), 3);
+
+    // An empty answer or end of input chooses nothing
+    let mut output = Vec::new();
+    assert!(
+        choose_bookmark(
+            &bookmarks,
+            &[0, 1],
+            &mut std::io::Cursor::new("\n"),
+            &mut output
+        )
+        .is_err()
+    );
+    assert!(
+        choose_bookmark(
+            &bookmarks,
+            &[0, 1],
+            &mut std::io::Cursor::new(""),
+            &mut output
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_first_flag() {
+    let cli = Cli::try_parse_from(["bmk", "--first", "rust"]).unwrap();
+    assert!(cli.first);
+    assert_eq!(cli.query, vec!["rust"]);
+}
+
+#[test]
+fn test_print_mode() {
+    let cli = Cli::try_parse_from(["bmk", "--print", "gh", "rust"]).unwrap();
+    assert!(cli.dry_run);
+    assert_eq!(cli.query, vec!["gh", "rust"]);
+    let cli = Cli::try_parse_from(["bmk", "open", "gh", "--print"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Commands::Open { dry_run: true, .. })
+    ));
+
+    // Printing a bookmark does not count as opening it
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let bookmarks = vec![Bookmark {
+        name: "gh".to_string(),
+        url: "https://github.com/search?q={query}".to_string(),
+        ..Default::default()
+    }];
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    let options = OpenOptions {
+        mode: OpenMode::Print,
+        query: Some("rust cli".to_string()),
+        ..Default::default()
+    };
+    handle_open_command(&path, "gh", &options).unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap(), bookmarks);
+}
+
+#[test]
+fn test_batch_result() {
+    assert!(batch_result(3, Vec::new()).is_ok());
+
+    // A lone failure keeps its own message
+    let err = batch_result(0, vec![("a".to_string(), anyhow::anyhow!("boom"))]).unwrap_err();
+    assert_eq!(err.to_string(), "boom");
+
+    let failures = vec![
+        ("Mail".to_string(), anyhow::anyhow!("no browser")),
+        ("Calendar".to_string(), anyhow::anyhow!("timed out")),
+    ];
+    let err = batch_result(5, failures).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Opened 5, failed 2: 'Mail' — no browser; 'Calendar' — timed out"
+    );
+}
+
+#[test]
+fn test_open_all_reports_every_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let bookmarks: Bookmarks = ["Mail", "Calendar"]
+        .iter()
+        .map(|name| Bookmark {
+            name: name.to_string(),
+            url: format!("https://{}.example.com", name.to_lowercase()),
+            tags: vec!["dashboard".to_string()],
+            ..Default::default()
+        })
+        .collect();
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+
+    // An unknown browser fails every open without launching anything
+    let options = OpenOptions {
+        browser: Some("netscape".to_string()),
+        ..Default::default()
+    };
+    let err = handle_open_all_command(&path, "dashboard", &options).unwrap_err();
+    let message = err.to_string();
+    assert!(message.starts_with("Opened 0, failed 2: 'Mail' — Unknown browser"));
+    assert!(message.contains("'Calendar' — Unknown browser"));
+}
+
+#[test]
+fn test_bookmark_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+
+    // A missing file opens as an empty store
+    let mut store = BookmarkStore::open(&path).unwrap();
+    assert!(store.all().is_empty());
+    assert_eq!(store.path(), path);
+
+    store
+        .add(Bookmark {
+            name: "GitHub".to_string(),
+            url: "github.com".to_string(),
+            tags: vec!["dev".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+    store
+        .add(Bookmark {
+            name: "Docs".to_string(),
+            url: "https://docs.rs".to_string(),
+            tags: vec!["rust".to_string(), "dev".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(
+        store
+            .add(Bookmark {
+                name: "Bad".to_string(),
+                url: "not a url".to_string(),
+                ..Default::default()
+            })
+            .is_err()
+    );
+    assert_eq!(store.get("github").unwrap().url, "https://github.com");
+    assert_eq!(store.all_tags(), vec!["dev", "rust"]);
+
+    // Nothing reaches the file until saved
+    assert!(!path.exists());
+    store.save().unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap(), *store.all());
+
+    store
+        .update(
+            "Docs",
+            Bookmark {
+                name: "Docs".to_string(),
+                url: "https://doc.rust-lang.org".to_string(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(store.get("docs").unwrap().url, "https://doc.rust-lang.org");
+    assert!(store.get("Docs").unwrap().updated_at.is_some());
+
+    let deleted = store.delete("GITHUB").unwrap();
+    assert_eq!(deleted.name, "GitHub");
+    assert!(store.delete("GitHub").is_err());
+    assert!(store.update("missing", Bookmark::default()).is_err());
+
+    store.save().unwrap();
+    let reopened = BookmarkStore::open(&path).unwrap();
+    assert_eq!(reopened.all().len(), 1);
+    assert_eq!(reopened.all_tags(), Vec::<String>::new());
+}
+
+#[test]
+fn test_bookmark_store_autosave() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    let mut store = BookmarkStore::open(&path).unwrap().with_autosave(true);
+
+    // Each change is on disk without an explicit save
+    store
+        .add(Bookmark {
+            name: "GitHub".to_string(),
+            url: "https://github.com".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap(), *store.all());
+
+    store
+        .modify(|bookmarks| {
+            bookmarks[0].desc = "Code hosting".to_string();
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap()[0].desc, "Code hosting");
+
+    // A rejected change leaves the file alone
+    let before = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        store
+            .update(
+                "GitHub",
+                Bookmark {
+                    name: "GitHub".to_string(),
+                    url: "not a url".to_string(),
+                    ..Default::default()
+                }
+            )
+            .is_err()
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+
+    store.delete("GitHub").unwrap();
+    assert!(load_bookmarks_from(&path).unwrap().is_empty());
+
+    // Changes made elsewhere are picked up by reload
+    save_bookmarks_to(
+        &path,
+        &vec![Bookmark {
+            name: "Docs".to_string(),
+            url: "https://docs.rs".to_string(),
+            ..Default::default()
+        }],
+    )
+    .unwrap();
+    store.reload().unwrap();
+    assert!(store.get("Docs").is_some());
+}
+
+#[test]
+fn test_bookmark_store_failed_autosave_rolls_back() {
+    let dir = tempfile::tempdir().unwrap();
+    // The bookmarks directory can't be created under a regular file
+    std::fs::write(dir.path().join("blocked"), "").unwrap();
+    let path = dir.path().join("blocked").join("bookmarks.yaml");
+    let mut store = BookmarkStore::new(&path, Vec::new()).with_autosave(true);
+    let github = Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        ..Default::default()
+    };
+
+    assert!(store.add(github.clone()).is_err());
+    assert!(store.all().is_empty());
+
+    // Trying again fails the same way instead of finding the first attempt
+    let err = store.add(github).unwrap_err();
+    assert!(
+        !matches!(
+            err.downcast_ref::<BookmarkError>(),
+            Some(BookmarkError::DuplicateUrl(_) | BookmarkError::DuplicateName(_))
+        ),
+        "{err}"
+    );
+    assert!(store.all().is_empty());
+}
+
+#[test]
+fn test_bookmark_errors_are_matchable() {
+    let mut bookmarks: Bookmarks = Vec::new();
+    let bookmark = |name: &str, url: &str| Bookmark {
+        name: name.to_string(),
+        url: url.to_string(),
+        ..Default::default()
+    };
+    add_bookmark(&mut bookmarks, bookmark("gh", "https://github.com")).unwrap();
+
+    assert_eq!(
+        add_bookmark(&mut bookmarks, bookmark("hub", "github.com")),
+        Err(BookmarkError::DuplicateUrl("gh".to_string()))
+    );
+    assert_eq!(
+        add_bookmark(&mut bookmarks, bookmark("empty", " ")),
+        Err(BookmarkError::EmptyUrl)
+    );
+    let err = add_bookmark(&mut bookmarks, bookmark("spaced", "a b.com")).unwrap_err();
+    assert!(matches!(err, BookmarkError::InvalidUrl { ref url, .. } if url == "a b.com"));
+    assert_eq!(
+        err.to_string(),
+        "Invalid URL 'a b.com': contains whitespace"
+    );
+
+    assert_eq!(
+        rename_bookmark(&mut bookmarks, "nope", "other"),
+        Err(BookmarkError::NotFound("nope".to_string()))
+    );
+    let mut aliased = bookmark("mail", "https://mail.example.com");
+    aliased.aliases = vec!["gh".to_string()];
+    assert_eq!(
+        update_bookmark(&mut bookmarks, 0, {
+            let mut own = bookmark("gh", "https://github.com");
+            own.aliases = vec!["gh".to_string()];
+            own
+        }),
+        Err(BookmarkError::AliasIsOwnName("gh".to_string()))
+    );
+    assert_eq!(
+        add_bookmark(&mut bookmarks, aliased),
+        Err(BookmarkError::AliasInUse {
+            alias: "gh".to_string(),
+            owner: "gh".to_string(),
+        })
+    );
+
+    // Failures from the URL parser keep it as the cause
+    let err = anyhow::Error::from(validate_url("http://[::1").unwrap_err());
+    assert_eq!(err.to_string(), "Invalid URL 'http://[::1'");
+    assert!(err.chain().nth(1).is_some());
+    assert!(matches!(
+        err.downcast_ref::<BookmarkError>(),
+        Some(BookmarkError::MalformedUrl { .. })
+    ));
+}
+
+#[test]
+fn test_add_repeated_tag_flags() {
+    let cli = Cli::try_parse_from([
+        "bmk",
+        "add",
+        "gh",
+        "github.com",
+        "--tags",
+        "dev,code",
+        "--tag",
+        "a,b",
+        "--tags",
+        "git",
+        "--tag",
+        "work",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Commands::Add { tags, tag, .. }) => {
+            assert_eq!(tags, vec!["dev", "code", "git"]);
+            assert_eq!(tag, vec!["a,b", "work"]);
+        }
+        other => panic!("unexpected command: {:?}", other),
+    }
+}
+
+#[test]
+fn test_looks_like_url() {
+    assert!(looks_like_url("https://github.com/rust-lang"));
+    assert!(looks_like_url("  http://localhost:8080/\n"));
+    assert!(looks_like_url("ftp://files.example.com"));
+
+    assert!(!looks_like_url("github.com"));
+    assert!(!looks_like_url("mailto:me@example.com"));
+    assert!(!looks_like_url("some copied text"));
+    assert!(!looks_like_url("https://example.com and more"));
+    assert!(!looks_like_url(""));
+}
+
+#[test]
+fn test_bookmark_notes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::write(&path, "- name: gh\n  url: https://github.com\n").unwrap();
+
+    // Files without notes load, and empty notes are not written
+    let bookmarks = load_bookmarks_from(&path).unwrap();
+    assert_eq!(bookmarks[0].notes, "");
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("notes"));
+
+    handle_edit_command(
+        &path,
+        "gh",
+        EditOptions {
+            notes: Some("Work account\nSSO via okta".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let bookmarks = load_bookmarks_from(&path).unwrap();
+    assert_eq!(bookmarks[0].notes, "Work account\nSSO via okta");
+
+    // Notes don't take part in fuzzy matching
+    assert!(rank_bookmarks(&bookmarks, "okta").is_empty());
+
+    let cli = Cli::try_parse_from(["bmk", "add", "gh", "github.com", "--notes", "hi"]).unwrap();
+    assert!(matches!(cli.command, Some(Commands::Add { notes, .. }) if notes == "hi"));
+}
+
+#[test]
+fn test_search_scope() {
+    let bookmark = Bookmark {
+        name: "GitHub".to_string(),
+        url: "https://github.com".to_string(),
+        desc: "Code hosting".to_string(),
+        notes: "Work account uses okta".to_string(),
+        tags: vec!["dev".to_string()],
+        ..Default::default()
+    };
+    let chars = |q: &str| q.chars().collect::<Vec<char>>();
+
+    // Notes are only searched in the all-fields scope
+    assert_eq!(
+        fuzzy_score(&chars("okta"), &bookmark, SearchScope::Fields),
+        -1
+    );
+    assert!(fuzzy_score(&chars("okta"), &bookmark, SearchScope::All) > 0);
+
+    // A query may span fields when they are searched together
+    assert_eq!(
+        fuzzy_score(&chars("hub dev"), &bookmark, SearchScope::Fields),
+        -1
+    );
+    assert!(fuzzy_score(&chars("hub dev"), &bookmark, SearchScope::All) > 0);
+
+    // The default keeps name matches ranked above the rest
+    assert_eq!(SearchScope::default(), SearchScope::Fields);
+    assert!(fuzzy_score(&chars("git"), &bookmark, SearchScope::Fields) > 1000);
+}
+
+#[test]
+fn test_tui_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bmk").join(STATE_FILE);
+
+    // No state file yet means nothing to restore
+    assert_eq!(TuiState::load(&path).unwrap(), TuiState::default());
+
+    let state = TuiState {
+        last_search: Some("rust docs".to_string()),
+    };
+    state.save(&path).unwrap();
+    assert_eq!(TuiState::load(&path).unwrap(), state);
+
+    std::fs::write(&path, "last_search: [").unwrap();
+    assert!(TuiState::load(&path).is_err());
+
+    // The state lives next to the default bookmarks file, not in it
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    assert_eq!(
+        state_path().unwrap(),
+        config_dir().unwrap().join(STATE_FILE)
+    );
+    assert!(Cli::try_parse_from(["bmk", "--resume"]).unwrap().resume);
+}
+
+#[test]
+fn test_exact_name_beats_better_fuzzy_match() {
+    let bookmarks = vec![
+        Bookmark {
+            name: "github".to_string(),
+            url: "https://github.com".to_string(),
+            visits: 500,
+            ..Default::default()
+        },
+        Bookmark {
+            name: "gh".to_string(),
+            url: "https://cli.github.com/manual".to_string(),
+            ..Default::default()
+        },
+    ];
+    let args = vec!["gh".to_string()];
+
+    // The popular bookmark ranks first for the query...
+    assert_eq!(rank_bookmarks(&bookmarks, "gh")[0], 0);
+    // ...but the bookmark named exactly that is the one opened
+    assert_eq!(
+        resolve_launch_args(&bookmarks, &args, DEFAULT_MIN_SCORE),
+        Some((1, None))
+    );
+}
+
+#[test]
+fn test_bookmark_groups() {
+    assert_eq!(bookmark_group("work/jira"), Some("work"));
+    assert_eq!(bookmark_group("work/ci/main"), Some("work"));
+    assert_eq!(bookmark_group("gh"), None);
+    assert_eq!(bookmark_group("/root"), None);
+    assert_eq!(bookmark_group("trailing/"), None);
+
+    let bookmark = |name: &str| Bookmark {
+        name: name.to_string(),
+        url: format!("https://{}.example.com", name.replace('/', "-")),
+        ..Default::default()
+    };
+    let bookmarks = vec![
+        bookmark("work/jira"),
+        bookmark("gh"),
+        bookmark("home/nas"),
+        bookmark("work/ci"),
+    ];
+    assert_eq!(
+        get_group_counts(&bookmarks),
+        vec![("home".to_string(), 1), ("work".to_string(), 2)]
+    );
+
+    // Ungrouped bookmarks first, then each group in the order it first appears
+    let options = ListOptions {
+        tree: true,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    write_bookmark_list(&bookmarks, &options, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "gh      https://gh.example.com\n\
+         work/\n  \
+           jira  https://work-jira.example.com\n  \
+           ci    https://work-ci.example.com\n\
+         home/\n  \
+           nas   https://home-nas.example.com\n"
+    );
+
+    assert!(Cli::try_parse_from(["bmk", "list", "--tree", "--json"]).is_err());
+}
+
+#[test]
+fn test_bookmark_icons() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    std::fs::write(
+        &path,
+        "- name: gh\n  url: https://github.com\n- name: docs\n  url: https://docs.rs\n",
+    )
+    .unwrap();
+
+    // Without any icons there is no icon column at all
+    let bookmarks = load_bookmarks_from(&path).unwrap();
+    assert_eq!(icon_width(&bookmarks), 0);
+    assert_eq!(icon_label(&bookmarks[0], 0), "");
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("icon"));
+
+    handle_edit_command(
+        &path,
+        "gh",
+        EditOptions {
+            icon: Some("🐙".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let bookmarks = load_bookmarks_from(&path).unwrap();
+    assert_eq!(bookmarks[0].icon, "🐙");
+
+    // Emoji take two columns, and bookmarks without an icon get a placeholder
+    // padded to the same width
+    let width = icon_width(&bookmarks);
+    assert_eq!(width, 2);
+    assert_eq!(icon_label(&bookmarks[0], width), "🐙 ");
+    assert_eq!(icon_label(&bookmarks[1], width), "·  ");
+
+    let cli = Cli::try_parse_from(["bmk", "add", "gh", "github.com", "--icon", "★"]).unwrap();
+    assert!(matches!(cli.command, Some(Commands::Add { icon, .. }) if icon == "★"));
+}
+
+#[test]
+fn test_edit_file_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    save_bookmarks_to(
+        &path,
+        &vec![Bookmark {
+            name: "gh".to_string(),
+            url: "https://github.com".to_string(),
+            ..Default::default()
+        }],
+    )
+    .unwrap();
+    let original = std::fs::read_to_string(&path).unwrap();
+    let copy = edit_path(&path);
+    assert_eq!(copy, dir.path().join("bookmarks.edit.yaml"));
+
+    // A failing editor leaves the file alone and cleans up the copy
+    let err = handle_edit_file_command(&path, "false").unwrap_err();
+    assert!(err.to_string().contains("was not changed"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+    assert!(!copy.exists());
+
+    // Quitting without changes doesn't rewrite or back up the file
+    handle_edit_file_command(&path, "true").unwrap();
+    assert!(list_backups(&path).unwrap().is_empty());
+    assert!(!copy.exists());
+
+    // Arguments in the editor command are passed before the file
+    handle_edit_file_command(&path, "sed -i s/github/gitlab/").unwrap();
+    assert_eq!(
+        load_bookmarks_from(&path).unwrap()[0].url,
+        "https://gitlab.com"
+    );
+    assert_eq!(list_backups(&path).unwrap().len(), 1);
+    assert!(!copy.exists());
+
+    // An edit that no longer parses is kept aside and the file is not touched
+    let bad = dir.path().join("bad.yaml");
+    std::fs::write(&bad, "- name: [unclosed\n").unwrap();
+    let before = std::fs::read_to_string(&path).unwrap();
+    let err = handle_edit_file_command(&path, &format!("cp {}", bad.display())).unwrap_err();
+    assert!(format!("{:#}", err).contains("your edits are in"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+    assert_eq!(
+        std::fs::read_to_string(&copy).unwrap(),
+        "- name: [unclosed\n"
+    );
+}
+
+#[test]
+fn test_editor_command() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let saved = std::env::var("EDITOR").ok();
+
+    unsafe { std::env::set_var("EDITOR", "nvim") };
+    assert_eq!(editor_command(), "nvim");
+    unsafe { std::env::set_var("EDITOR", "") };
+    assert_eq!(editor_command(), "vi");
+
+    match saved {
+        Some(editor) => unsafe { std::env::set_var("EDITOR", editor) },
+        None => unsafe { std::env::remove_var("EDITOR") },
+    }
+}
+
+#[test]
+fn test_load_piped_bookmarks() {
+    let yaml = "- name: gh\n  url: https://github.com\n";
+    let json_list = r#"[{"name": "gh", "url": "https://github.com"}]"#;
+    let json_document =
+        r#"  {"version": 1, "bookmarks": [{"name": "gh", "url": "https://github.com"}]}"#;
+    for input in [yaml, json_list, json_document] {
+        let bookmarks = load_piped_bookmarks(input.as_bytes()).unwrap();
+        assert_eq!(bookmarks.len(), 1, "{}", input);
+        assert_eq!(bookmarks[0].url, "https://github.com");
+    }
+
+    assert!(load_piped_bookmarks(" \n".as_bytes()).unwrap().is_empty());
+
+    let err = load_piped_bookmarks("[{\"name\": ".as_bytes()).unwrap_err();
+    assert!(format!("{:#}", err).contains("Failed to parse bookmarks from stdin"));
+    let cause = err.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(cause.format, FileFormat::Json);
+}
+
+#[test]
+fn test_verbosity_flags() {
+    use bmk::output::{Verbosity, set_verbosity, verbosity};
+
+    let level = |args: &[&str]| {
+        let cli = Cli::try_parse_from(args).unwrap();
+        Verbosity::from_flags(cli.quiet, cli.verbose)
+    };
+    assert_eq!(level(&["bmk", "list"]), Verbosity::Normal);
+    assert_eq!(level(&["bmk", "list", "-q"]), Verbosity::Quiet);
+    assert_eq!(level(&["bmk", "-v", "gh"]), Verbosity::Verbose);
+    assert_eq!(level(&["bmk", "-vv", "gh"]), Verbosity::VeryVerbose);
+    assert_eq!(level(&["bmk", "-vvv", "gh"]), Verbosity::VeryVerbose);
+    assert!(Cli::try_parse_from(["bmk", "-q", "-v", "gh"]).is_err());
+
+    assert_eq!(verbosity(), Verbosity::Normal);
+    set_verbosity(Verbosity::Quiet);
+    assert_eq!(verbosity(), Verbosity::Quiet);
+    set_verbosity(Verbosity::Normal);
+}
+
+#[test]
+fn test_exit_code_distinguishes_no_match() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    save_bookmarks_to(
+        &path,
+        &vec![Bookmark {
+            name: "gh".to_string(),
+            url: "https://github.com".to_string(),
+            tags: vec!["dev".to_string()],
+            ..Default::default()
+        }],
+    )
+    .unwrap();
+
+    let print = OpenOptions {
+        mode: OpenMode::Print,
+        ..Default::default()
+    };
+    let no_match = [
+        handle_open_command(&path, "zzqq", &print).unwrap_err(),
+        handle_open_all_command(&path, "nope", &print).unwrap_err(),
+        handle_delete_command(&path, "nope").unwrap_err(),
+        handle_copy_command(&path, "nope", true).unwrap_err(),
+        handle_search_command(&path, "zzqq", None).unwrap_err(),
+        handle_tag_rename_command(&path, "nope", "x").unwrap_err(),
+        select_bookmarks(
+            &load_bookmarks_from(&path).unwrap(),
+            &[],
+            &["nope".to_string()],
+        )
+        .unwrap_err(),
+    ];
+    for err in &no_match {
+        assert_eq!(exit_code(err), EXIT_NO_MATCH, "{:#}", err);
+    }
+    // Context added on top doesn't hide what went wrong
+    let wrapped =
+        anyhow::Error::from(BookmarkError::NotFound("gh".to_string())).context("While opening");
+    assert_eq!(exit_code(&wrapped), EXIT_NO_MATCH);
+
+    // Refusals and broken files are ordinary errors
+    let refused = handle_rename_command(&path, "gh", "gh").unwrap_err();
+    assert_eq!(exit_code(&refused), 1, "{:#}", refused);
+    std::fs::write(&path, ": [").unwrap();
+    assert_eq!(
+        exit_code(&handle_open_command(&path, "gh", &print).unwrap_err()),
+        1
+    );
+}
+
+#[test]
+fn test_compute_visible_items() {
+    let bookmarks: Bookmarks = ["gitlab", "github", "docs"]
+        .iter()
+        .map(|name| Bookmark {
+            name: name.to_string(),
+            url: format!("https://{}.com", name),
+            ..Default::default()
+        })
+        .collect();
+    let by_name = |a: &Bookmark, b: &Bookmark| a.name.cmp(&b.name);
+
+    // Without a query everything kept is visible with score 0, in tie-break order
+    let all = compute_visible_items(&bookmarks, "", SearchScope::Fields, |_| true, by_name);
+    assert_eq!(all, vec![(2, 0), (1, 0), (0, 0)]);
+    let kept = compute_visible_items(
+        &bookmarks,
+        "",
+        SearchScope::Fields,
+        |b| b.name != "docs",
+        |_, _| std::cmp::Ordering::Equal,
+    );
+    assert_eq!(kept, vec![(0, 0), (1, 0)]);
+
+    // With a query the score decides first and the tie-break only orders equals
+    let found = compute_visible_items(&bookmarks, "git", SearchScope::Fields, |_| true, by_name);
+    let indices: Vec<usize> = found.iter().map(|&(i, _)| i).collect();
+    assert_eq!(indices, vec![1, 0]);
+    assert_eq!(found[0].1, found[1].1);
+
+    // The CLI ranking is the same pipeline in file order, without the empty query
+    assert_eq!(rank_bookmarks(&bookmarks, "git"), vec![0, 1]);
+    assert!(rank_bookmarks(&bookmarks, "").is_empty());
+
+    // Rescoring the matches of a shorter query gives the same result
+    let prefix = compute_visible_items(&bookmarks, "gi", SearchScope::Fields, |_| true, by_name);
+    let candidates: Vec<usize> = prefix.iter().map(|&(i, _)| i).collect();
+    let narrowed = rank_candidates(&bookmarks, candidates, "git", SearchScope::Fields, by_name);
+    assert_eq!(narrowed, found);
+}
+
+#[test]
+fn test_plain_picker() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+
+    let mut output = Vec::new();
+    let picked = run_plain_picker(&path, &mut "".as_bytes(), &mut output).unwrap();
+    assert!(picked.is_none());
+    assert!(String::from_utf8(output).unwrap().contains("No bookmarks"));
+
+    save_bookmarks_to(
+        &path,
+        &vec![
+            Bookmark {
+                name: "gh".to_string(),
+                url: "https://github.com".to_string(),
+                ..Default::default()
+            },
+            Bookmark {
+                name: "docs".to_string(),
+                url: "https://docs.rs".to_string(),
+                ..Default::default()
+            },
+        ],
+    )
+    .unwrap();
+
+    // Every bookmark is listed in file order and the visit is recorded
+    let mut output = Vec::new();
+    let picked = run_plain_picker(&path, &mut "2\n".as_bytes(), &mut output)
+        .unwrap()
+        .unwrap();
+    assert_eq!(picked.bookmark.url, "https://docs.rs");
+    assert!(!picked.private);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with(" 1) gh    https://github.com\n 2) docs  https://docs.rs\n"));
+    assert_eq!(load_bookmarks_from(&path).unwrap()[1].visits, 1);
+
+    // No answer means nothing is opened
+    let err = run_plain_picker(&path, &mut "".as_bytes(), &mut Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "No bookmark chosen");
+
+    let cli = Cli::try_parse_from(["bmk", "--no-tui"]).unwrap();
+    assert!(cli.plain);
+}
+
+#[test]
+fn test_recent_bookmarks() {
+    let opened = |name: &str, last_opened: Option<&str>| Bookmark {
+        name: name.to_string(),
+        url: format!("https://{}.com", name),
+        last_opened: last_opened.map(str::to_string),
+        ..Default::default()
+    };
+    let bookmarks: Bookmarks = vec![
+        opened("old", Some("2024-01-01T09:00:00+00:00")),
+        opened("never", None),
+        opened("new", Some("2024-03-01T09:00:00+01:00")),
+        opened("broken", Some("yesterday")),
+        opened("mid", Some("2024-02-01T09:00:00-05:00")),
+    ];
+
+    let names = |limit| -> Vec<String> {
+        recent_bookmarks(&bookmarks, limit)
+            .iter()
+            .map(|b| b.name.clone())
+            .collect()
+    };
+    assert_eq!(names(10), vec!["new", "mid", "old"]);
+    assert_eq!(names(2), vec!["new", "mid"]);
+    assert!(names(0).is_empty());
+
+    assert_eq!(humanize_elapsed(-5), "just now");
+    assert_eq!(humanize_elapsed(59), "just now");
+    assert_eq!(humanize_elapsed(60), "1m ago");
+    assert_eq!(humanize_elapsed(2 * 3600 + 59 * 60), "2h ago");
+    assert_eq!(humanize_elapsed(3 * 24 * 3600), "3d ago");
+    assert_eq!(humanize_elapsed(14 * 24 * 3600), "2w ago");
+    assert_eq!(humanize_elapsed(65 * 24 * 3600), "2mo ago");
+    assert_eq!(humanize_elapsed(800 * 24 * 3600), "2y ago");
+}
+
+#[test]
+fn test_validate_bookmarks() {
+    let issues = |content: &str, format| -> Vec<String> {
+        validate_bookmarks(content.as_bytes(), format)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    };
+
+    // Every field a saved bookmark can have is known to the check
+    let full = Bookmark {
+        name: "gh".to_string(),
+        url: "https://github.com".to_string(),
+        desc: "Code".to_string(),
+        tags: vec!["dev".to_string()],
+        notes: "Work account".to_string(),
+        icon: "🐙".to_string(),
+        visits: 3,
+        last_opened: Some("2024-03-01T09:00:00+00:00".to_string()),
+        browser: Some("firefox".to_string()),
+        urls: vec!["https://gist.github.com".to_string()],
+        created_at: Some("2024-01-01T09:00:00+00:00".to_string()),
+        updated_at: Some("2024-02-01T09:00:00+00:00".to_string()),
+        aliases: vec!["hub".to_string()],
+    };
+    let dir = tempfile::tempdir().unwrap();
+    for file in ["bookmarks.yaml", "bookmarks.json"] {
+        let path = dir.path().join(file);
+        save_bookmarks_to(&path, &vec![full.clone()]).unwrap();
+        handle_validate_command(&path).unwrap();
+    }
+    assert!(issues("", FileFormat::Yaml).is_empty());
+
+    let content = "\
+version: 1
+extra: 3
+bookmarks:
+  - name: gh
+    url: https://github.com
+    tag: [dev]
+    tags: dev
+    last_opened: yesterday
+  - name: docs
+    aliases: [gh]
+  - just a string
+";
+    assert_eq!(
+        issues(content, FileFormat::Yaml),
+        vec![
+            "extra: unknown field, expected `version` or `bookmarks`",
+            "bookmarks[0].tag (gh): unknown field",
+            "bookmarks[0].tags (gh): invalid type: string \"dev\", expected a sequence",
+            "bookmarks[0].last_opened (gh): 'yesterday' is not an RFC 3339 timestamp",
+            "bookmarks[1].url (docs): missing field",
+            "bookmarks[1].aliases (docs): 'gh' is also the name or an alias of bookmarks[0]",
+            "bookmarks[2]: expected a map of bookmark fields",
+        ]
+    );
+
+    let json = r#"[{"name": "gh", "url": "https://github.com", "visits": -1}]"#;
+    assert_eq!(
+        issues(json, FileFormat::Json),
+        vec!["[0].visits (gh): invalid value: integer `-1`, expected u64"]
+    );
+    assert_eq!(
+        issues("version: 99\nbookmarks: []\n", FileFormat::Yaml),
+        vec!["version: 99 is newer than this bmk reads (1)"]
+    );
+    let syntax = issues("- name: gh\n  url: [x\n", FileFormat::Yaml);
+    assert_eq!(syntax.len(), 1);
+    assert!(
+        syntax[0].starts_with("line 3, column 1: Invalid YAML"),
+        "{}",
+        syntax[0]
+    );
+
+    let path = dir.path().join("broken.yaml");
+    std::fs::write(&path, content).unwrap();
+    let err = handle_validate_command(&path).unwrap_err();
+    assert!(err.to_string().starts_with("Found 7 problems"), "{}", err);
+    assert!(handle_validate_command(&dir.path().join("missing.yaml")).is_err());
+}
+
+#[test]
+fn test_find_unused() {
+    let bookmark =
+        |name: &str, visits, last_opened: Option<&str>, created_at: Option<&str>| Bookmark {
+            name: name.to_string(),
+            url: format!("https://{}.com", name),
+            visits,
+            last_opened: last_opened.map(str::to_string),
+            created_at: created_at.map(str::to_string),
+            ..Default::default()
+        };
+    let bookmarks: Bookmarks = vec![
+        bookmark("old", 3, Some("2024-01-01T09:00:00+00:00"), None),
+        bookmark("never", 0, None, None),
+        bookmark("recent", 1, Some("2024-03-01T09:00:00+00:00"), None),
+        bookmark("new", 0, None, Some("2024-03-01T09:00:00+00:00")),
+        bookmark("stale", 0, None, Some("2024-01-01T09:00:00+00:00")),
+        bookmark("broken", 2, Some("yesterday"), None),
+    ];
+
+    assert_eq!(find_unused(&bookmarks, None), vec!["never", "new", "stale"]);
+    let since = chrono::DateTime::parse_from_rfc3339("2024-02-01T00:00:00+00:00").unwrap();
+    assert_eq!(
+        find_unused(&bookmarks, Some(since)),
+        vec!["old", "never", "stale"]
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bookmarks.yaml");
+    save_bookmarks_to(&path, &bookmarks).unwrap();
+    handle_prune_command(&path, None, true, false).unwrap();
+    assert_eq!(load_bookmarks_from(&path).unwrap().len(), 6);
+    handle_prune_command(&path, None, false, true).unwrap();
+    let names: Vec<String> = load_bookmarks_from(&path)
+        .unwrap()
+        .into_iter()
+        .map(|b| b.name)
+        .collect();
+    assert_eq!(names, vec!["old", "recent", "broken"]);
+
+    assert!(Cli::try_parse_from(["bmk", "prune", "--days", "30"]).is_err());
+    assert!(Cli::try_parse_from(["bmk", "prune", "--unused", "--dry-run", "-y"]).is_err());
+}
+
+#[test]
+fn test_search_flag() {
+    let cli = Cli::try_parse_from(["bmk", "-s", "git hub"]).unwrap();
+    assert_eq!(cli.search.as_deref(), Some("git hub"));
+    assert!(cli.query.is_empty());
+    let cli = Cli::try_parse_from(["bmk", "--search", "gh", "--resume"]).unwrap();
+    assert_eq!(cli.search.as_deref(), Some("gh"));
+
+    // A query opens directly, so it can't be combined with a search
+    assert!(Cli::try_parse_from(["bmk", "--search", "gh", "docs"]).is_err());
+}
+
+#[cfg(feature = "skim")]
+#[test]
+fn test_skim_matcher() {
+    use bmk::fuzzy::{Matcher, current_matcher, fuzzy_match, fuzzy_match_with};
+    let chars = |q: &str| q.chars().collect::<Vec<char>>();
+
+    // The built-in matcher stays the default
+    assert_eq!(current_matcher(), Matcher::Builtin);
+    assert_eq!(
+        fuzzy_match(&chars("gh"), "github"),
+        fuzzy_match_with(Matcher::Builtin, &chars("gh"), "github")
+    );
+
+    assert!(fuzzy_match_with(Matcher::Skim, &chars("gh"), "github") > 0);
+    assert_eq!(fuzzy_match_with(Matcher::Skim, &chars("xyz"), "github"), -1);
+    assert_eq!(fuzzy_match_with(Matcher::Skim, &chars(""), "github"), 0);
+    // Case matters, like the built-in matcher; callers lowercase to ignore it
+    assert_eq!(fuzzy_match_with(Matcher::Skim, &chars("GH"), "github"), -1);
+    // A contiguous match outranks a scattered one
+    assert!(
+        fuzzy_match_with(Matcher::Skim, &chars("doc"), "docs.rs")
+            > fuzzy_match_with(Matcher::Skim, &chars("doc"), "drop-outlook-calendar")
+    );
+
+    let cli = Cli::try_parse_from(["bmk", "--matcher", "skim", "list"]).unwrap();
+    assert_eq!(cli.matcher, Matcher::Skim);
+}
+
+#[test]
+fn test_scroll_offset() {
+    // Moving down past the view scrolls just enough to show the selection
+    assert_eq!(scroll_offset(0, Some(4), 5, 20), 0);
+    assert_eq!(scroll_offset(0, Some(5), 5, 20), 1);
+    // Moving back up stays put while the selection is in view
+    assert_eq!(scroll_offset(1, Some(4), 5, 20), 1);
+    assert_eq!(scroll_offset(1, Some(1), 5, 20), 1);
+    assert_eq!(scroll_offset(1, Some(0), 5, 20), 0);
+    // Jumping to the end shows the last rows
+    assert_eq!(scroll_offset(0, Some(19), 5, 20), 15);
+    // A shorter list pulls the view back so no rows are left empty
+    assert_eq!(scroll_offset(15, Some(0), 5, 3), 0);
+    assert_eq!(scroll_offset(15, None, 5, 0), 0);
+    assert_eq!(scroll_offset(15, Some(9), 5, 10), 5);
+}